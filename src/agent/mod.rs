@@ -1,6 +1,15 @@
+use crate::client::backend::ClipboardBackendConfig;
+use crate::client::clipboard::{PullSelection, Selection};
+use crate::client::image::ImageTranscode;
 use crate::client::ssh::SshConfig;
-use crate::client::transport::{ClientConfig, make_request, send_request};
-use crate::protocol::{RequestKind, ResponseKind};
+use crate::client::transport::{
+    ClientConfig, DEFAULT_RETRY_ATTEMPTS, DEFAULT_RETRY_BACKOFF_MS, RetryPolicy, make_request,
+    send_request, send_request_with_retry,
+};
+use crate::client_actions::GuardedValue;
+use crate::client_actions::secrets::{self, SecretGuard, SecretGuardConfig};
+use crate::client_actions::transform::{self, TransformConfig};
+use crate::protocol::{ClipboardValue, RequestKind, ResponseKind};
 use eyre::{Result, WrapErr, eyre};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -18,14 +27,78 @@ pub struct AgentConfig {
     pub resync_frames: bool,
     #[serde(default = "default_resync_max_bytes")]
     pub resync_max_bytes: usize,
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: u32,
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
     pub hotkeys: HotkeyConfig,
     pub autostart_enabled: bool,
+    #[serde(default)]
+    pub offline_queue: OfflineQueueConfig,
+    #[serde(default)]
+    pub clipboard: ClipboardBackendConfig,
+    /// Re-encoding applied to large clipboard images before a push.
+    #[serde(default)]
+    pub image: ImageTranscode,
+    /// Text transform chains (same syntax as `--transform`).
+    #[serde(default)]
+    pub transforms: TransformConfig,
+    /// Secret detection for pushes; a blocked push can be confirmed from the
+    /// notification by pushing again.
+    #[serde(default)]
+    pub secrets: SecretGuardConfig,
+    /// Host, OS, and user attached to pushed values.
+    #[serde(default)]
+    pub origin: OriginConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OfflineQueueConfig {
+    pub enabled: bool,
+    /// Maximum queued pushes; `1` keeps only the latest value.
+    pub max_entries: usize,
+    /// Queued pushes older than this are discarded; `0` disables expiry.
+    pub ttl_secs: u64,
+    pub check_interval_ms: u64,
+}
+
+impl Default for OfflineQueueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: 1,
+            ttl_secs: 3600,
+            check_interval_ms: 15_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OriginConfig {
+    pub enabled: bool,
+    pub label: Option<String>,
+}
+
+impl Default for OriginConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            label: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HotkeyConfig {
     pub push: String,
     pub pull: String,
+    /// Optional bindings for the Linux primary selection; unbound by default.
+    #[serde(default)]
+    pub push_primary: Option<String>,
+    #[serde(default)]
+    pub pull_primary: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -68,8 +141,21 @@ pub fn default_agent_config() -> AgentConfig {
         timeout_ms: 7000,
         resync_frames: default_resync_frames(),
         resync_max_bytes: default_resync_max_bytes(),
-        hotkeys: HotkeyConfig { push, pull },
+        retry_attempts: default_retry_attempts(),
+        retry_backoff_ms: default_retry_backoff_ms(),
+        hotkeys: HotkeyConfig {
+            push,
+            pull,
+            push_primary: None,
+            pull_primary: None,
+        },
         autostart_enabled: false,
+        offline_queue: OfflineQueueConfig::default(),
+        clipboard: ClipboardBackendConfig::default(),
+        image: ImageTranscode::default(),
+        transforms: TransformConfig::default(),
+        secrets: SecretGuardConfig::default(),
+        origin: OriginConfig::default(),
     }
 }
 
@@ -109,10 +195,33 @@ pub fn validate_config(config: &AgentConfig) -> Result<()> {
     if config.resync_max_bytes == 0 {
         return Err(eyre!("resync_max_bytes must be > 0"));
     }
+    if config.retry_attempts == 0 {
+        return Err(eyre!("retry_attempts must be > 0"));
+    }
+    if config.offline_queue.max_entries == 0 {
+        return Err(eyre!("offline_queue.max_entries must be > 0"));
+    }
+    if config.offline_queue.check_interval_ms == 0 {
+        return Err(eyre!("offline_queue.check_interval_ms must be > 0"));
+    }
+    config
+        .clipboard
+        .validate()
+        .wrap_err("invalid clipboard backend")?;
+    config.image.validate().wrap_err("invalid image settings")?;
+    SecretGuard::load(&config.secrets).wrap_err("invalid secrets settings")?;
     crate::agent::hotkey::parse_hotkey(&config.hotkeys.push)
         .wrap_err("invalid push hotkey binding")?;
     crate::agent::hotkey::parse_hotkey(&config.hotkeys.pull)
         .wrap_err("invalid pull hotkey binding")?;
+    if let Some(binding) = &config.hotkeys.push_primary {
+        crate::agent::hotkey::parse_hotkey(binding)
+            .wrap_err("invalid push_primary hotkey binding")?;
+    }
+    if let Some(binding) = &config.hotkeys.pull_primary {
+        crate::agent::hotkey::parse_hotkey(binding)
+            .wrap_err("invalid pull_primary hotkey binding")?;
+    }
     Ok(())
 }
 
@@ -131,33 +240,81 @@ pub fn client_config_from_agent(config: &AgentConfig) -> ClientConfig {
         timeout_ms: config.timeout_ms,
         resync_frames: config.resync_frames,
         resync_max_bytes: config.resync_max_bytes,
+        retry: RetryPolicy::with_attempts(config.retry_attempts, config.retry_backoff_ms),
     }
 }
 
-pub async fn agent_push(config: &AgentConfig) -> Result<()> {
-    let value = crate::client_actions::build_clipboard_value_from_clipboard(config.max_size)
-        .map_err(|err| eyre!(err.message))?;
-    let response = send_request(
+/// Reads `selection` and applies the configured image encoding, push transforms, and
+/// secret guard. A blocked push fails with a `SecretsBlocked` error.
+/// `confirmed` is the digest of a previously blocked text the user chose to send.
+pub fn build_push_value(
+    config: &AgentConfig,
+    selection: Selection,
+    confirmed: Option<u64>,
+) -> Result<ClipboardValue> {
+    let guard = SecretGuard::load(&config.secrets)?.confirming(confirmed);
+    let GuardedValue {
+        mut value,
+        findings,
+    } = crate::client_actions::build_clipboard_value_from_clipboard(
+        &config.clipboard,
+        selection,
+        &config.image,
+        &config.transforms.push,
+        &guard,
+        config.max_size,
+    )
+    .map_err(|err| match err.blocked {
+        Some(blocked) => blocked.into(),
+        None => eyre!(err.message),
+    })?;
+    if let Some(note) = secrets::describe_findings(guard.action(), &findings) {
+        tracing::warn!("{note}");
+    }
+    if value.data.len() > config.max_size {
+        return Err(eyre!("payload too large"));
+    }
+    if config.origin.enabled {
+        value.origin = Some(crate::client_actions::local_origin(
+            config.origin.label.clone(),
+        ));
+    }
+    Ok(value)
+}
+
+/// Pushes the local `selection` and returns how many retries the transport needed.
+pub async fn agent_push(config: &AgentConfig, selection: Selection) -> Result<u32> {
+    let value = build_push_value(config, selection, None)?;
+    let outcome = send_request_with_retry(
         &client_config_from_agent(config),
         make_request(RequestKind::Set { value }),
     )
     .await?;
-    match response.kind {
-        ResponseKind::Ok => Ok(()),
+    match outcome.response.kind {
+        ResponseKind::Ok => Ok(outcome.retries),
         ResponseKind::Error { code: _, message } => Err(eyre!(message)),
         other => Err(eyre!("unexpected response: {other:?}")),
     }
 }
 
-pub async fn agent_pull(config: &AgentConfig) -> Result<()> {
-    let response = send_request(
+/// Pulls into the local `target` selection(s) and returns how many retries the
+/// transport needed.
+pub async fn agent_pull(config: &AgentConfig, target: PullSelection) -> Result<u32> {
+    let outcome = send_request_with_retry(
         &client_config_from_agent(config),
         make_request(RequestKind::Get),
     )
     .await?;
-    crate::client_actions::apply_pull_response_to_clipboard(response, config.max_size)
-        .wrap_err("pull failed")?;
-    Ok(())
+    let mut response = outcome.response;
+    transform::apply_to_response(&mut response, &config.transforms.pull);
+    crate::client_actions::apply_pull_response_to_clipboard(
+        response,
+        config.max_size,
+        &config.clipboard,
+        target,
+    )
+    .wrap_err("pull failed")?;
+    Ok(outcome.retries)
 }
 
 pub async fn agent_peek(config: &AgentConfig) -> Result<String> {
@@ -171,10 +328,14 @@ pub async fn agent_peek(config: &AgentConfig) -> Result<String> {
             content_type,
             size,
             created_at,
+            author,
+            origin,
         } => Ok(crate::cli::format_peek_output(
             &content_type,
             size,
             created_at,
+            author.as_deref(),
+            origin.as_ref(),
         )),
         ResponseKind::Empty => Ok("no clipboard value set".to_string()),
         ResponseKind::Error { code: _, message } => Err(eyre!(message)),
@@ -185,6 +346,7 @@ pub async fn agent_peek(config: &AgentConfig) -> Result<String> {
 pub mod autostart;
 pub mod hotkey;
 pub mod notify;
+pub mod queue;
 pub mod run;
 
 pub use hotkey::parse_hotkey;
@@ -196,3 +358,11 @@ fn default_resync_frames() -> bool {
 fn default_resync_max_bytes() -> usize {
     8192
 }
+
+fn default_retry_attempts() -> u32 {
+    DEFAULT_RETRY_ATTEMPTS
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    DEFAULT_RETRY_BACKOFF_MS
+}
@@ -0,0 +1,467 @@
+use crate::agent::{AgentConfig, OfflineQueueConfig, build_push_value, client_config_from_agent};
+use crate::client::clipboard::Selection;
+use crate::client::transport::{
+    RetryPolicy, new_request_id, send_request, send_request_with_retry, transport_error,
+};
+use crate::framing::{decode_message, encode_message};
+use crate::protocol::{ClipboardValue, Request, RequestKind, ResponseKind};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use eyre::{Result, WrapErr, eyre};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use wincode::{SchemaRead, SchemaWrite};
+
+/// Held while a `Set` is in flight, so a queued push never lands after a newer
+/// direct push. A user push waits for at most one queued entry.
+static SEND_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+const SPOOL_MAGIC: [u8; 4] = *b"SCQ1";
+const KEYRING_SERVICE: &str = "ssh_clipboard";
+const KEYRING_USER: &str = "offline-queue";
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+#[derive(Debug, Clone, SchemaWrite, SchemaRead)]
+pub struct QueuedPush {
+    pub request_id: u64,
+    pub queued_at: i64,
+    pub value: ClipboardValue,
+}
+
+#[derive(Debug, Clone, SchemaWrite, SchemaRead)]
+struct SpoolContents {
+    entries: Vec<QueuedPush>,
+}
+
+#[derive(Debug, Default)]
+pub struct PushQueue {
+    entries: VecDeque<QueuedPush>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushResult {
+    Sent { retries: u32 },
+    Queued { pending: usize },
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FlushReport {
+    pub sent: usize,
+    pub dropped: Vec<String>,
+    pub pending: usize,
+}
+
+impl PushQueue {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds an entry, evicting the oldest ones so at most `max_entries` remain
+    /// (`max_entries = 1` is latest-wins).
+    pub fn enqueue(&mut self, entry: QueuedPush, max_entries: usize) {
+        let max_entries = max_entries.max(1);
+        while self.entries.len() >= max_entries {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Drops entries that expired or no longer fit `max_size`; returns reasons for
+    /// each dropped entry.
+    pub fn prune(
+        &mut self,
+        now_ms: i64,
+        config: &OfflineQueueConfig,
+        max_size: usize,
+    ) -> Vec<String> {
+        let mut dropped = Vec::new();
+        self.entries.retain(|entry| {
+            if config.ttl_secs > 0
+                && now_ms.saturating_sub(entry.queued_at) > (config.ttl_secs as i64) * 1000
+            {
+                dropped.push("queued push expired".to_string());
+                return false;
+            }
+            if entry.value.data.len() > max_size {
+                dropped.push("queued push exceeds max_size".to_string());
+                return false;
+            }
+            true
+        });
+        dropped
+    }
+
+    pub fn front(&self) -> Option<&QueuedPush> {
+        self.entries.front()
+    }
+
+    pub fn pop_front(&mut self) -> Option<QueuedPush> {
+        self.entries.pop_front()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn load_from(spool: &Path, key: &Key) -> Result<Self> {
+        if !spool.exists() {
+            return Ok(Self::default());
+        }
+        let bytes = std::fs::read(spool).wrap_err("failed to read queue spool")?;
+        let plaintext = decrypt(key, &bytes)?;
+        let contents: SpoolContents = decode_message(&plaintext)?;
+        Ok(Self {
+            entries: contents.entries.into(),
+        })
+    }
+
+    /// `key` is only asked for when there is something to write.
+    pub fn store_to(&self, spool: &Path, key: impl FnOnce() -> Result<Key>) -> Result<()> {
+        if self.entries.is_empty() {
+            if spool.exists() {
+                std::fs::remove_file(spool).wrap_err("failed to remove queue spool")?;
+            }
+            return Ok(());
+        }
+        let key = key()?;
+        let contents = SpoolContents {
+            entries: self.entries.iter().cloned().collect(),
+        };
+        let ciphertext = encrypt(&key, &encode_message(&contents)?)?;
+        write_private_file(spool, &ciphertext).wrap_err("failed to write queue spool")
+    }
+}
+
+pub fn spool_path() -> Result<PathBuf> {
+    let dir = crate::agent::config_path()?
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| eyre!("config path has no parent directory"))?;
+    Ok(dir.join("queue.spool"))
+}
+
+/// A spool whose key is gone (the Linux kernel keyring does not survive a reboot)
+/// cannot be read back and is discarded.
+pub fn load_queue() -> Result<PushQueue> {
+    let spool = spool_path()?;
+    if !spool.exists() {
+        return Ok(PushQueue::default());
+    }
+    match stored_key()? {
+        Some(key) => PushQueue::load_from(&spool, &key),
+        None => {
+            tracing::warn!(
+                "offline queue key is no longer in the OS keyring; discarding queued pushes"
+            );
+            std::fs::remove_file(&spool).wrap_err("failed to remove queue spool")?;
+            Ok(PushQueue::default())
+        }
+    }
+}
+
+pub fn store_queue(queue: &PushQueue) -> Result<()> {
+    queue.store_to(&spool_path()?, || match stored_key()? {
+        Some(key) => Ok(key),
+        None => create_key(),
+    })
+}
+
+/// Pushes the local `selection`; if the server is unreachable and the offline queue is
+/// enabled, the value is spooled for a later retry instead of failing.
+pub async fn push_or_queue(
+    config: &AgentConfig,
+    queue: &Mutex<PushQueue>,
+    selection: Selection,
+    confirmed: Option<u64>,
+) -> Result<PushResult> {
+    let value = build_push_value(config, selection, confirmed)?;
+    let request_id = new_request_id();
+    let request = Request {
+        request_id,
+        ssh_connection: None,
+        kind: RequestKind::Set {
+            value: value.clone(),
+        },
+    };
+
+    let sent = {
+        let _sending = SEND_LOCK.lock().await;
+        send_request_with_retry(&client_config_from_agent(config), request).await
+    };
+    match sent {
+        Ok(outcome) => match outcome.response.kind {
+            ResponseKind::Ok => {
+                // Anything still queued is older than what was just pushed.
+                let mut queue = queue.lock().unwrap();
+                if !queue.is_empty() {
+                    queue.clear();
+                    store_queue(&queue)?;
+                }
+                Ok(PushResult::Sent {
+                    retries: outcome.retries,
+                })
+            }
+            ResponseKind::Error { code: _, message } => Err(eyre!(message)),
+            other => Err(eyre!("unexpected response: {other:?}")),
+        },
+        Err(err) => {
+            let offline = transport_error(&err).is_some_and(|err| err.is_connectivity());
+            if !config.offline_queue.enabled || !offline {
+                return Err(err);
+            }
+            let mut queue = queue.lock().unwrap();
+            queue.enqueue(
+                QueuedPush {
+                    request_id,
+                    queued_at: value.created_at,
+                    value,
+                },
+                config.offline_queue.max_entries,
+            );
+            store_queue(&queue)?;
+            Ok(PushResult::Queued {
+                pending: queue.len(),
+            })
+        }
+    }
+}
+
+/// Probes the server with a cheap `PeekMeta` and, if it answers, sends queued pushes
+/// oldest-first. Stops at the first connectivity failure.
+pub async fn flush_queue(config: &AgentConfig, queue: &Mutex<PushQueue>) -> Result<FlushReport> {
+    let mut report = FlushReport::default();
+    {
+        let mut queue = queue.lock().unwrap();
+        report.dropped = queue.prune(now_epoch_millis(), &config.offline_queue, config.max_size);
+        if !report.dropped.is_empty() {
+            store_queue(&queue)?;
+        }
+        if queue.is_empty() {
+            return Ok(report);
+        }
+    }
+
+    let mut client_config = client_config_from_agent(config);
+    client_config.retry = RetryPolicy::none();
+
+    let probe = send_request(
+        &client_config,
+        crate::client::transport::make_request(RequestKind::PeekMeta),
+    )
+    .await;
+    if probe.is_err() {
+        report.pending = queue.lock().unwrap().len();
+        return Ok(report);
+    }
+
+    loop {
+        let _sending = SEND_LOCK.lock().await;
+        // Re-read under the lock: a direct push may have cleared the queue.
+        let Some(entry) = queue.lock().unwrap().front().cloned() else {
+            break;
+        };
+        let request_id = entry.request_id;
+        let request = Request {
+            request_id,
+            ssh_connection: None,
+            kind: RequestKind::Set { value: entry.value },
+        };
+        match send_request(&client_config, request).await {
+            Ok(response) => {
+                if let ResponseKind::Error { message, .. } = response.kind {
+                    report.dropped.push(message);
+                } else {
+                    report.sent += 1;
+                }
+                let mut queue = queue.lock().unwrap();
+                if queue
+                    .front()
+                    .is_some_and(|front| front.request_id == request_id)
+                {
+                    queue.pop_front();
+                    store_queue(&queue)?;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    report.pending = queue.lock().unwrap().len();
+    Ok(report)
+}
+
+/// The spool key is kept in the OS credential store (Keychain on macOS, Credential
+/// Manager on Windows, the kernel keyring on Linux), never next to the spool.
+fn keyring_entry() -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .map_err(|err| eyre!("failed to open the OS keyring: {err}"))
+}
+
+fn stored_key() -> Result<Option<Key>> {
+    match keyring_entry()?.get_secret() {
+        Ok(bytes) if bytes.len() == KEY_LEN => Ok(Some(*Key::from_slice(&bytes))),
+        Ok(_) => Err(eyre!("queue key in the OS keyring is corrupt")),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => Err(eyre!("failed to read queue key from the OS keyring: {err}")),
+    }
+}
+
+fn create_key() -> Result<Key> {
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    keyring_entry()?
+        .set_secret(key.as_slice())
+        .map_err(|err| eyre!("failed to store queue key in the OS keyring: {err}"))?;
+    Ok(key)
+}
+
+fn encrypt(key: &Key, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = ChaCha20Poly1305::new(key);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| eyre!("queue spool encryption failed"))?;
+    let mut out = Vec::with_capacity(SPOOL_MAGIC.len() + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(&SPOOL_MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn decrypt(key: &Key, bytes: &[u8]) -> Result<Vec<u8>> {
+    let header = SPOOL_MAGIC.len() + NONCE_LEN;
+    if bytes.len() < header || bytes[..SPOOL_MAGIC.len()] != SPOOL_MAGIC {
+        return Err(eyre!("queue spool has an invalid header"));
+    }
+    let cipher = ChaCha20Poly1305::new(key);
+    cipher
+        .decrypt(
+            Nonce::from_slice(&bytes[SPOOL_MAGIC.len()..header]),
+            &bytes[header..],
+        )
+        .map_err(|_| eyre!("queue spool could not be decrypted"))
+}
+
+fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    Ok(())
+}
+
+fn now_epoch_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::CONTENT_TYPE_TEXT;
+
+    fn entry(request_id: u64, queued_at: i64, text: &str) -> QueuedPush {
+        QueuedPush {
+            request_id,
+            queued_at,
+            value: ClipboardValue {
+                content_type: CONTENT_TYPE_TEXT.to_string(),
+                data: text.as_bytes().to_vec(),
+                created_at: queued_at,
+                origin: None,
+            },
+        }
+    }
+
+    #[test]
+    fn enqueue_latest_wins_keeps_only_newest() {
+        let mut queue = PushQueue::default();
+        queue.enqueue(entry(1, 0, "a"), 1);
+        queue.enqueue(entry(2, 0, "b"), 1);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.front().unwrap().request_id, 2);
+    }
+
+    #[test]
+    fn enqueue_bounded_evicts_oldest() {
+        let mut queue = PushQueue::default();
+        for id in 1..=4 {
+            queue.enqueue(entry(id, 0, "x"), 3);
+        }
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.front().unwrap().request_id, 2);
+    }
+
+    #[test]
+    fn prune_drops_expired_and_oversize_entries() {
+        let config = OfflineQueueConfig {
+            ttl_secs: 10,
+            ..OfflineQueueConfig::default()
+        };
+        let mut queue = PushQueue::default();
+        queue.enqueue(entry(1, 0, "old"), 8);
+        queue.enqueue(entry(2, 50_000, "too large"), 8);
+        queue.enqueue(entry(3, 55_000, "ok"), 8);
+
+        let dropped = queue.prune(60_000, &config, 4);
+        assert_eq!(dropped.len(), 2);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.front().unwrap().request_id, 3);
+    }
+
+    #[test]
+    fn spool_round_trips_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = dir.path().join("queue.spool");
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+
+        let mut queue = PushQueue::default();
+        queue.enqueue(entry(7, 123, "secret text"), 1);
+        queue.store_to(&spool, || Ok(key)).unwrap();
+
+        let raw = std::fs::read(&spool).unwrap();
+        assert!(!raw.windows(11).any(|window| window == b"secret text"));
+
+        let loaded = PushQueue::load_from(&spool, &key).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.front().unwrap().value.data, b"secret text");
+
+        let other = ChaCha20Poly1305::generate_key(&mut OsRng);
+        assert!(PushQueue::load_from(&spool, &other).is_err());
+    }
+
+    #[test]
+    fn storing_empty_queue_removes_spool() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = dir.path().join("queue.spool");
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+
+        let mut queue = PushQueue::default();
+        queue.enqueue(entry(1, 0, "a"), 1);
+        queue.store_to(&spool, || Ok(key)).unwrap();
+        assert!(spool.exists());
+
+        queue.clear();
+        queue
+            .store_to(&spool, || Err(eyre!("no key needed")))
+            .unwrap();
+        assert!(!spool.exists());
+    }
+}
@@ -1,18 +1,26 @@
+use crate::agent::queue::{self, FlushReport, PushQueue, PushResult};
 use crate::agent::{
     AgentConfig, autostart, default_agent_config, load_config, store_config, validate_config,
 };
-use crate::agent::{agent_peek, agent_pull, agent_push};
+use crate::agent::{agent_peek, agent_pull};
 use crate::agent::{hotkey, notify};
+use crate::client::clipboard::{PullSelection, Selection};
+use crate::client_actions::secrets::SecretsBlocked;
 use eyre::{Result, WrapErr, eyre};
 use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tao::event::{Event, StartCause};
 use tao::event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy};
 use tokio::runtime::Runtime;
+use tokio::time::Duration;
 use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuId, MenuItem};
 use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
 
+/// How long a push blocked by the secret guard can be confirmed by pushing again.
+const SECRET_CONFIRM_WINDOW: Duration = Duration::from_secs(15);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum HotkeyMode {
     Enabled,
@@ -31,9 +39,40 @@ enum LinuxSession {
 #[derive(Debug, Clone)]
 enum UserEvent {
     Menu(MenuId),
-    Hotkey { id: u32, state: HotKeyState },
-    OperationOk(&'static str),
+    Hotkey {
+        id: u32,
+        state: HotKeyState,
+    },
+    OperationOk(&'static str, OperationStatus),
     OperationErr(&'static str, String),
+    QueueChanged {
+        pending: usize,
+        note: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OperationStatus {
+    Done { retries: u32 },
+    Queued { pending: usize },
+}
+
+/// A push the secret guard blocked; repeating it within `SECRET_CONFIRM_WINDOW`
+/// sends it anyway, as long as the clipboard still holds the same text.
+#[derive(Debug, Clone, Copy)]
+struct BlockedPush {
+    selection: Selection,
+    digest: u64,
+    at: Instant,
+}
+
+impl From<PushResult> for OperationStatus {
+    fn from(result: PushResult) -> Self {
+        match result {
+            PushResult::Sent { retries } => Self::Done { retries },
+            PushResult::Queued { pending } => Self::Queued { pending },
+        }
+    }
 }
 
 pub fn run_agent(no_tray: bool, no_hotkeys: bool, autostart: bool) -> Result<()> {
@@ -54,6 +93,11 @@ pub fn run_agent(no_tray: bool, no_hotkeys: bool, autostart: bool) -> Result<()>
     let runtime = Runtime::new().wrap_err("failed to create tokio runtime")?;
     let config = Arc::new(Mutex::new(config));
     let operation_running = Arc::new(AtomicBool::new(false));
+    let blocked_push = Arc::new(Mutex::new(None));
+    let push_queue = Arc::new(Mutex::new(queue::load_queue().unwrap_or_else(|err| {
+        tracing::warn!("failed to load offline push queue: {err}");
+        PushQueue::default()
+    })));
 
     let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build();
     let proxy = event_loop.create_proxy();
@@ -91,11 +135,21 @@ pub fn run_agent(no_tray: bool, no_hotkeys: bool, autostart: bool) -> Result<()>
 
                 if !no_tray {
                     match build_tray(config.clone()) {
-                        Ok(state) => tray_state = Some(state),
+                        Ok(state) => {
+                            state.set_queue_pending(push_queue.lock().unwrap().len());
+                            tray_state = Some(state);
+                        }
                         Err(err) => notify::notify("ssh_clipboard", &format!("tray failed: {err}")),
                     }
                 }
 
+                spawn_queue_flusher(
+                    &runtime,
+                    proxy.clone(),
+                    config.clone(),
+                    push_queue.clone(),
+                );
+
                 if hotkey_mode == HotkeyMode::DisabledForWayland {
                     notify::notify(
                         "ssh_clipboard",
@@ -123,6 +177,8 @@ pub fn run_agent(no_tray: bool, no_hotkeys: bool, autostart: bool) -> Result<()>
                         runtime: &runtime,
                         proxy: proxy.clone(),
                         config: config.clone(),
+                        queue: push_queue.clone(),
+                        blocked_push: blocked_push.clone(),
                         running: operation_running.clone(),
                         control_flow,
                     };
@@ -134,39 +190,44 @@ pub fn run_agent(no_tray: bool, no_hotkeys: bool, autostart: bool) -> Result<()>
                 if state != HotKeyState::Pressed {
                     return;
                 }
-                if let Some(hk) = &hotkeys {
-                    if id == hk.push_id {
-                        start_operation(
-                            "push",
-                            &runtime,
-                            proxy.clone(),
-                            config.clone(),
-                            operation_running.clone(),
-                            |cfg| async move { agent_push(&cfg).await },
-                        );
-                    } else if id == hk.pull_id {
-                        start_operation(
-                            "pull",
-                            &runtime,
-                            proxy.clone(),
-                            config.clone(),
-                            operation_running.clone(),
-                            |cfg| async move { agent_pull(&cfg).await },
-                        );
-                    }
+                let Some(action) = hotkeys.as_ref().and_then(|hk| hk.action_for(id)) else {
+                    return;
+                };
+                let ctx = OperationContext {
+                    runtime: &runtime,
+                    proxy: proxy.clone(),
+                    config: config.clone(),
+                    queue: push_queue.clone(),
+                    blocked_push: blocked_push.clone(),
+                    running: operation_running.clone(),
+                };
+                match action {
+                    HotkeyAction::Push(selection) => start_push(ctx, selection),
+                    HotkeyAction::Pull(target) => start_pull(ctx, target),
                 }
             }
 
-            Event::UserEvent(UserEvent::OperationOk(name)) => {
-                if name != "peek" {
-                    notify::notify("ssh_clipboard", &format!("{name}: ok"));
+            Event::UserEvent(UserEvent::OperationOk(name, status)) if name != "peek" => {
+                // A direct push also empties the queue, so refresh on every success.
+                if let Some(state) = &tray_state {
+                    state.set_queue_pending(push_queue.lock().unwrap().len());
                 }
+                notify::notify("ssh_clipboard", &format_operation_ok(name, status));
             }
 
             Event::UserEvent(UserEvent::OperationErr(name, message)) => {
                 notify::notify("ssh_clipboard error", &format!("{name}: {message}"));
             }
 
+            Event::UserEvent(UserEvent::QueueChanged { pending, note }) => {
+                if let Some(state) = &tray_state {
+                    state.set_queue_pending(pending);
+                }
+                if let Some(note) = note {
+                    notify::notify("ssh_clipboard", &note);
+                }
+            }
+
             _ => {}
         }
     });
@@ -217,15 +278,34 @@ where
 }
 
 struct TrayState {
-    _tray: TrayIcon,
+    tray: TrayIcon,
     menu_ids: MenuIds,
     autostart: CheckMenuItem,
+    discard_queue: MenuItem,
+}
+
+impl TrayState {
+    fn set_queue_pending(&self, pending: usize) {
+        if let Err(err) = self.tray.set_tooltip(Some(queue_tooltip(pending))) {
+            tracing::warn!("failed to update tray tooltip: {err}");
+        }
+        self.discard_queue.set_enabled(pending > 0);
+    }
+}
+
+fn queue_tooltip(pending: usize) -> String {
+    match pending {
+        0 => "ssh_clipboard".to_string(),
+        1 => "ssh_clipboard (1 push queued)".to_string(),
+        n => format!("ssh_clipboard ({n} pushes queued)"),
+    }
 }
 
 struct MenuIds {
     push: MenuId,
     pull: MenuId,
     peek: MenuId,
+    discard_queue: MenuId,
     autostart: MenuId,
     restore_defaults: MenuId,
     show_config: MenuId,
@@ -237,6 +317,7 @@ fn build_tray(config: Arc<Mutex<AgentConfig>>) -> Result<TrayState> {
     let push = MenuItem::new("Push", true, None);
     let pull = MenuItem::new("Pull", true, None);
     let peek = MenuItem::new("Peek", true, None);
+    let discard_queue = MenuItem::new("Discard queued push", false, None);
 
     let enabled = config.lock().unwrap().autostart_enabled;
     let autostart = CheckMenuItem::new("Start at login", true, enabled, None);
@@ -249,6 +330,7 @@ fn build_tray(config: Arc<Mutex<AgentConfig>>) -> Result<TrayState> {
         &push,
         &pull,
         &peek,
+        &discard_queue,
         &autostart,
         &restore_defaults,
         &show_config,
@@ -265,17 +347,19 @@ fn build_tray(config: Arc<Mutex<AgentConfig>>) -> Result<TrayState> {
 
     let autostart_id = autostart.id().clone();
     Ok(TrayState {
-        _tray: tray,
+        tray,
         autostart,
         menu_ids: MenuIds {
             push: push.id().clone(),
             pull: pull.id().clone(),
             peek: peek.id().clone(),
+            discard_queue: discard_queue.id().clone(),
             autostart: autostart_id,
             restore_defaults: restore_defaults.id().clone(),
             show_config: show_config.id().clone(),
             quit: quit.id().clone(),
         },
+        discard_queue,
     })
 }
 
@@ -285,10 +369,25 @@ struct MenuContext<'a> {
     runtime: &'a Runtime,
     proxy: EventLoopProxy<UserEvent>,
     config: Arc<Mutex<AgentConfig>>,
+    queue: Arc<Mutex<PushQueue>>,
+    blocked_push: Arc<Mutex<Option<BlockedPush>>>,
     running: Arc<AtomicBool>,
     control_flow: &'a mut ControlFlow,
 }
 
+impl MenuContext<'_> {
+    fn operation(&self) -> OperationContext<'_> {
+        OperationContext {
+            runtime: self.runtime,
+            proxy: self.proxy.clone(),
+            config: self.config.clone(),
+            queue: self.queue.clone(),
+            blocked_push: self.blocked_push.clone(),
+            running: self.running.clone(),
+        }
+    }
+}
+
 fn handle_menu(id: MenuId, ctx: MenuContext) {
     if id == ctx.tray.menu_ids.quit {
         *ctx.control_flow = ControlFlow::Exit;
@@ -303,6 +402,17 @@ fn handle_menu(id: MenuId, ctx: MenuContext) {
         return;
     }
 
+    if id == ctx.tray.menu_ids.discard_queue {
+        let mut push_queue = ctx.queue.lock().unwrap();
+        push_queue.clear();
+        if let Err(err) = queue::store_queue(&push_queue) {
+            notify::notify("ssh_clipboard", &format!("queue update failed: {err}"));
+        }
+        ctx.tray.set_queue_pending(0);
+        notify::notify("ssh_clipboard", "discarded queued push");
+        return;
+    }
+
     if id == ctx.tray.menu_ids.restore_defaults {
         let mut cfg = ctx.config.lock().unwrap();
         let preserved_target = cfg.target.clone();
@@ -359,25 +469,11 @@ fn handle_menu(id: MenuId, ctx: MenuContext) {
     }
 
     if id == ctx.tray.menu_ids.push {
-        start_operation(
-            "push",
-            ctx.runtime,
-            ctx.proxy.clone(),
-            ctx.config.clone(),
-            ctx.running.clone(),
-            |cfg| async move { agent_push(&cfg).await },
-        );
+        start_push(ctx.operation(), Selection::Clipboard);
         return;
     }
     if id == ctx.tray.menu_ids.pull {
-        start_operation(
-            "pull",
-            ctx.runtime,
-            ctx.proxy.clone(),
-            ctx.config.clone(),
-            ctx.running.clone(),
-            |cfg| async move { agent_pull(&cfg).await },
-        );
+        start_pull(ctx.operation(), PullSelection::Clipboard);
         return;
     }
     if id == ctx.tray.menu_ids.peek {
@@ -390,62 +486,165 @@ fn handle_menu(id: MenuId, ctx: MenuContext) {
             |cfg| async move {
                 let result = agent_peek(&cfg).await?;
                 notify::notify("ssh_clipboard peek", &result);
-                Ok(())
+                Ok(OperationStatus::Done { retries: 0 })
             },
         );
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HotkeyAction {
+    Push(Selection),
+    Pull(PullSelection),
+}
+
 struct Hotkeys {
     _manager: GlobalHotKeyManager,
-    push: global_hotkey::hotkey::HotKey,
-    pull: global_hotkey::hotkey::HotKey,
-    push_id: u32,
-    pull_id: u32,
+    bindings: Vec<(global_hotkey::hotkey::HotKey, HotkeyAction)>,
+}
+
+fn hotkey_bindings(
+    cfg: &AgentConfig,
+) -> Result<Vec<(global_hotkey::hotkey::HotKey, HotkeyAction)>> {
+    let mut bindings = vec![
+        (
+            hotkey::parse_hotkey(&cfg.hotkeys.push)?,
+            HotkeyAction::Push(Selection::Clipboard),
+        ),
+        (
+            hotkey::parse_hotkey(&cfg.hotkeys.pull)?,
+            HotkeyAction::Pull(PullSelection::Clipboard),
+        ),
+    ];
+    if let Some(binding) = &cfg.hotkeys.push_primary {
+        bindings.push((
+            hotkey::parse_hotkey(binding)?,
+            HotkeyAction::Push(Selection::Primary),
+        ));
+    }
+    if let Some(binding) = &cfg.hotkeys.pull_primary {
+        bindings.push((
+            hotkey::parse_hotkey(binding)?,
+            HotkeyAction::Pull(PullSelection::Primary),
+        ));
+    }
+    Ok(bindings)
 }
 
 fn register_hotkeys(config: Arc<Mutex<AgentConfig>>) -> Result<Hotkeys> {
     let manager = GlobalHotKeyManager::new().map_err(|err| eyre!(err.to_string()))?;
     let cfg = config.lock().unwrap().clone();
 
-    let push = hotkey::parse_hotkey(&cfg.hotkeys.push)?;
-    let pull = hotkey::parse_hotkey(&cfg.hotkeys.pull)?;
-    let push_id = push.id();
-    let pull_id = pull.id();
-
+    let bindings = hotkey_bindings(&cfg)?;
+    let keys: Vec<_> = bindings.iter().map(|(key, _)| *key).collect();
     manager
-        .register_all(&[push, pull])
+        .register_all(&keys)
         .map_err(|err| eyre!(err.to_string()))?;
 
     Ok(Hotkeys {
         _manager: manager,
-        push,
-        pull,
-        push_id,
-        pull_id,
+        bindings,
     })
 }
 
 impl Hotkeys {
+    fn action_for(&self, id: u32) -> Option<HotkeyAction> {
+        self.bindings
+            .iter()
+            .find(|(key, _)| key.id() == id)
+            .map(|(_, action)| *action)
+    }
+
     fn update_from_config(&mut self, cfg: &AgentConfig) -> Result<()> {
-        let new_push = hotkey::parse_hotkey(&cfg.hotkeys.push)?;
-        let new_pull = hotkey::parse_hotkey(&cfg.hotkeys.pull)?;
+        let new_bindings = hotkey_bindings(cfg)?;
+        let old_keys: Vec<_> = self.bindings.iter().map(|(key, _)| *key).collect();
+        let new_keys: Vec<_> = new_bindings.iter().map(|(key, _)| *key).collect();
 
         self._manager
-            .unregister_all(&[self.push, self.pull])
+            .unregister_all(&old_keys)
             .map_err(|err| eyre!(err.to_string()))?;
         self._manager
-            .register_all(&[new_push, new_pull])
+            .register_all(&new_keys)
             .map_err(|err| eyre!(err.to_string()))?;
 
-        self.push = new_push;
-        self.pull = new_pull;
-        self.push_id = new_push.id();
-        self.pull_id = new_pull.id();
+        self.bindings = new_bindings;
         Ok(())
     }
 }
 
+struct OperationContext<'a> {
+    runtime: &'a Runtime,
+    proxy: EventLoopProxy<UserEvent>,
+    config: Arc<Mutex<AgentConfig>>,
+    queue: Arc<Mutex<PushQueue>>,
+    blocked_push: Arc<Mutex<Option<BlockedPush>>>,
+    running: Arc<AtomicBool>,
+}
+
+fn start_push(ctx: OperationContext, selection: Selection) {
+    let name = match selection {
+        Selection::Clipboard => "push",
+        Selection::Primary => "push primary",
+    };
+    let confirmed = ctx
+        .blocked_push
+        .lock()
+        .unwrap()
+        .take()
+        .filter(|blocked| {
+            blocked.selection == selection && blocked.at.elapsed() < SECRET_CONFIRM_WINDOW
+        })
+        .map(|blocked| blocked.digest);
+    let push_queue = ctx.queue;
+    let blocked_push = ctx.blocked_push;
+    start_operation(
+        name,
+        ctx.runtime,
+        ctx.proxy,
+        ctx.config,
+        ctx.running,
+        move |cfg| async move {
+            match queue::push_or_queue(&cfg, &push_queue, selection, confirmed).await {
+                Ok(result) => Ok(OperationStatus::from(result)),
+                Err(err) => match err.downcast_ref::<SecretsBlocked>() {
+                    Some(blocked) => {
+                        *blocked_push.lock().unwrap() = Some(BlockedPush {
+                            selection,
+                            digest: blocked.digest,
+                            at: Instant::now(),
+                        });
+                        Err(eyre!(
+                            "blocked, clipboard looks like it contains a secret ({}); {name} again within {}s to send anyway",
+                            blocked.rules.join(", "),
+                            SECRET_CONFIRM_WINDOW.as_secs()
+                        ))
+                    }
+                    None => Err(err),
+                },
+            }
+        },
+    );
+}
+
+fn start_pull(ctx: OperationContext, target: PullSelection) {
+    let name = match target {
+        PullSelection::Clipboard => "pull",
+        PullSelection::Primary => "pull primary",
+        PullSelection::Both => "pull both",
+    };
+    start_operation(
+        name,
+        ctx.runtime,
+        ctx.proxy,
+        ctx.config,
+        ctx.running,
+        move |cfg| async move {
+            let retries = agent_pull(&cfg, target).await?;
+            Ok(OperationStatus::Done { retries })
+        },
+    );
+}
+
 fn start_operation<F, Fut>(
     name: &'static str,
     runtime: &Runtime,
@@ -455,7 +654,7 @@ fn start_operation<F, Fut>(
     f: F,
 ) where
     F: FnOnce(AgentConfig) -> Fut + Send + 'static,
-    Fut: std::future::Future<Output = Result<()>> + Send + 'static,
+    Fut: std::future::Future<Output = Result<OperationStatus>> + Send + 'static,
 {
     if running.swap(true, Ordering::SeqCst) {
         let _ = proxy.send_event(UserEvent::OperationErr(name, "already running".to_string()));
@@ -467,8 +666,8 @@ fn start_operation<F, Fut>(
         let result = f(cfg).await;
         running.store(false, Ordering::SeqCst);
         match result {
-            Ok(()) => {
-                let _ = proxy.send_event(UserEvent::OperationOk(name));
+            Ok(status) => {
+                let _ = proxy.send_event(UserEvent::OperationOk(name, status));
             }
             Err(err) => {
                 let _ = proxy.send_event(UserEvent::OperationErr(name, err.to_string()));
@@ -477,6 +676,67 @@ fn start_operation<F, Fut>(
     });
 }
 
+/// Periodically retries queued pushes once the server answers a `PeekMeta` probe.
+/// Flushes run one at a time on this task and never block user operations.
+fn spawn_queue_flusher(
+    runtime: &Runtime,
+    proxy: EventLoopProxy<UserEvent>,
+    config: Arc<Mutex<AgentConfig>>,
+    push_queue: Arc<Mutex<PushQueue>>,
+) {
+    runtime.spawn(async move {
+        loop {
+            let cfg = config.lock().unwrap().clone();
+            tokio::time::sleep(Duration::from_millis(cfg.offline_queue.check_interval_ms)).await;
+            let empty = push_queue.lock().unwrap().is_empty();
+            if empty {
+                continue;
+            }
+            let result = queue::flush_queue(&cfg, &push_queue).await;
+            match result {
+                Ok(report) => {
+                    if let Some(note) = format_flush_report(&report) {
+                        let _ = proxy.send_event(UserEvent::QueueChanged {
+                            pending: report.pending,
+                            note: Some(note),
+                        });
+                    }
+                }
+                Err(err) => tracing::warn!("offline queue flush failed: {err}"),
+            }
+        }
+    });
+}
+
+fn format_flush_report(report: &FlushReport) -> Option<String> {
+    if report.sent == 0 && report.dropped.is_empty() {
+        return None;
+    }
+    let mut parts = Vec::new();
+    if report.sent > 0 {
+        parts.push(format!("sent {} queued push(es)", report.sent));
+    }
+    if !report.dropped.is_empty() {
+        parts.push(format!(
+            "dropped {} queued push(es): {}",
+            report.dropped.len(),
+            report.dropped.join("; ")
+        ));
+    }
+    Some(parts.join(", "))
+}
+
+fn format_operation_ok(name: &str, status: OperationStatus) -> String {
+    match status {
+        OperationStatus::Done { retries: 0 } => format!("{name}: ok"),
+        OperationStatus::Done { retries: 1 } => format!("{name}: ok (after 1 retry)"),
+        OperationStatus::Done { retries } => format!("{name}: ok (after {retries} retries)"),
+        OperationStatus::Queued { pending } => {
+            format!("{name}: server unreachable; queued for retry ({pending} pending)")
+        }
+    }
+}
+
 fn load_tray_icon() -> Result<Icon> {
     static ICON_PNG: &[u8] =
         include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/icon.png"));
@@ -566,6 +826,58 @@ mod tests {
         assert_eq!(linux_session_for(&[]), LinuxSession::Unknown);
     }
 
+    #[test]
+    fn hotkey_bindings_add_primary_only_when_configured() {
+        let mut cfg = default_agent_config();
+        assert_eq!(hotkey_bindings(&cfg).unwrap().len(), 2);
+
+        cfg.hotkeys.pull_primary = Some("Ctrl+Alt+KeyB".to_string());
+        let bindings = hotkey_bindings(&cfg).unwrap();
+        assert_eq!(bindings.len(), 3);
+        assert_eq!(bindings[2].1, HotkeyAction::Pull(PullSelection::Primary));
+    }
+
+    #[test]
+    fn format_operation_ok_reports_retries() {
+        let done = |retries| OperationStatus::Done { retries };
+        assert_eq!(format_operation_ok("push", done(0)), "push: ok");
+        assert_eq!(
+            format_operation_ok("push", done(1)),
+            "push: ok (after 1 retry)"
+        );
+        assert_eq!(
+            format_operation_ok("pull", done(2)),
+            "pull: ok (after 2 retries)"
+        );
+    }
+
+    #[test]
+    fn format_operation_ok_reports_queued_push() {
+        let message = format_operation_ok("push", OperationStatus::Queued { pending: 1 });
+        assert!(message.contains("queued for retry (1 pending)"));
+    }
+
+    #[test]
+    fn queue_tooltip_reflects_pending_count() {
+        assert_eq!(queue_tooltip(0), "ssh_clipboard");
+        assert_eq!(queue_tooltip(1), "ssh_clipboard (1 push queued)");
+        assert_eq!(queue_tooltip(3), "ssh_clipboard (3 pushes queued)");
+    }
+
+    #[test]
+    fn format_flush_report_skips_noop() {
+        assert!(format_flush_report(&FlushReport::default()).is_none());
+        let report = FlushReport {
+            sent: 1,
+            dropped: Vec::new(),
+            pending: 0,
+        };
+        assert_eq!(
+            format_flush_report(&report).as_deref(),
+            Some("sent 1 queued push(es)")
+        );
+    }
+
     #[test]
     fn decide_hotkey_mode_user_disable_wins() {
         assert_eq!(decide_hotkey_mode(true), HotkeyMode::DisabledByUser);
@@ -159,6 +159,15 @@ pub(crate) fn apply_config_set(config: &mut crate::agent::AgentConfig, args: &Co
     if let Some(resync_max_bytes) = args.resync_max_bytes {
         config.resync_max_bytes = resync_max_bytes;
     }
+    if let Some(retry_attempts) = args.retry_attempts {
+        config.retry_attempts = retry_attempts;
+    }
+    if let Some(retry_backoff_ms) = args.retry_backoff_ms {
+        config.retry_backoff_ms = retry_backoff_ms;
+    }
+    if let Some(backend) = args.clipboard_backend {
+        config.clipboard.backend = backend;
+    }
     if args.clear_ssh_options {
         config.ssh_options.clear();
     }
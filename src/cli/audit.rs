@@ -0,0 +1,161 @@
+use eyre::{Result, eyre};
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use tokio::time::{Duration, sleep};
+
+use crate::cli::{AuditArgs, AuditCommands, AuditTailArgs};
+use crate::daemon::{AuditRecord, default_audit_path, rotated_path};
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub async fn run(args: AuditArgs) -> Result<()> {
+    match args.command {
+        AuditCommands::Tail(args) => tail(args).await,
+    }
+}
+
+async fn tail(args: AuditTailArgs) -> Result<()> {
+    let path = match args.path {
+        Some(path) => path,
+        None => default_audit_path()?,
+    };
+    if !path.exists() {
+        return Err(eyre!(
+            "no audit log at {} (run the daemon with --audit or --audit-path)",
+            path.display()
+        ));
+    }
+
+    let contents = std::fs::read_to_string(&path)?;
+    // Reach into the last rotated file when the active one is short.
+    let mut text = String::new();
+    if contents.lines().count() < args.lines
+        && let Ok(rotated) = std::fs::read_to_string(rotated_path(&path, 1))
+    {
+        text = rotated;
+    }
+    text.push_str(&contents);
+    let lines: Vec<&str> = text.lines().collect();
+    let skip = lines.len().saturating_sub(args.lines);
+    for line in &lines[skip..] {
+        print_line(line, args.json);
+    }
+    if !args.follow {
+        return Ok(());
+    }
+
+    let mut inode = std::fs::metadata(&path)?.ino();
+    let mut offset = contents.len() as u64;
+    let mut pending = String::new();
+    loop {
+        sleep(FOLLOW_POLL_INTERVAL).await;
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            continue;
+        };
+        // The daemon renamed the log away and started a new one.
+        if metadata.ino() != inode || metadata.len() < offset {
+            inode = metadata.ino();
+            offset = 0;
+            pending.clear();
+        }
+        if metadata.len() == offset {
+            continue;
+        }
+        offset += read_from(&path, offset, &mut pending)?;
+        while let Some(end) = pending.find('\n') {
+            print_line(&pending[..end], args.json);
+            pending.drain(..=end);
+        }
+    }
+}
+
+fn read_from(path: &Path, offset: u64, out: &mut String) -> Result<u64> {
+    let mut file = std::fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    out.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(bytes.len() as u64)
+}
+
+fn print_line(line: &str, json: bool) {
+    if line.trim().is_empty() {
+        return;
+    }
+    match serde_json::from_str::<AuditRecord>(line) {
+        Ok(record) if !json => println!("{}", format_record(&record)),
+        _ => println!("{line}"),
+    }
+}
+
+fn format_record(record: &AuditRecord) -> String {
+    let json_name = |value: serde_json::Value| match value {
+        serde_json::Value::String(name) => name,
+        serde_json::Value::Object(map) => map.keys().next().cloned().unwrap_or_default(),
+        other => other.to_string(),
+    };
+    let operation = record
+        .operation
+        .map(|operation| json_name(serde_json::to_value(operation).unwrap_or_default()))
+        .unwrap_or_else(|| "-".to_string());
+    let mut result = json_name(serde_json::to_value(record.result).unwrap_or_default());
+    if let Some(code) = &record.error {
+        result = json_name(serde_json::to_value(code).unwrap_or_default());
+    }
+
+    let mut out = format!("{} {operation:<4} {result}", record.timestamp);
+    if let Some(uid) = record.peer_uid {
+        out.push_str(&format!(" uid={uid}"));
+    }
+    if let Some(pid) = record.peer_pid {
+        out.push_str(&format!(" pid={pid}"));
+    }
+    if let Some(ssh) = &record.reported_ssh_connection {
+        match ssh.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [ip, port, ..] => out.push_str(&format!(" reported_from={ip}:{port}")),
+            _ => out.push_str(&format!(" reported_ssh={ssh:?}")),
+        }
+    }
+    if let Some(content_type) = &record.content_type {
+        let essence = content_type.split(';').next().unwrap_or_default();
+        out.push_str(&format!(" {essence}"));
+    }
+    if let Some(size) = record.size {
+        out.push_str(&format!(" {size}B"));
+    }
+    if record.request_id != 0 {
+        out.push_str(&format!(" id={}", record.request_id));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_records_for_humans() {
+        let record: AuditRecord = serde_json::from_str(
+            r#"{"timestamp":"2026-10-18T09:30:00Z","request_id":11,"operation":"set",
+            "peer_uid":1000,"peer_pid":42,"reported_ssh_connection":"10.0.0.2 51234 10.0.0.1 22",
+            "content_type":"text/plain; charset=utf-8","size":6,"result":"ok","error":null}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            format_record(&record),
+            "2026-10-18T09:30:00Z set  ok uid=1000 pid=42 reported_from=10.0.0.2:51234 text/plain 6B id=11"
+        );
+
+        let rejected: AuditRecord = serde_json::from_str(
+            r#"{"timestamp":"2026-10-18T09:31:00Z","request_id":0,"operation":null,
+            "peer_uid":1001,"peer_pid":null,"reported_ssh_connection":null,"content_type":null,
+            "size":null,"result":"error","error":"forbidden"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            format_record(&rejected),
+            "2026-10-18T09:31:00Z -    forbidden uid=1001"
+        );
+    }
+}
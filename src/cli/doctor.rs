@@ -1,7 +1,10 @@
 use crate::cli::DoctorArgs;
-use crate::client::ssh::{SshConfig, resolve_target_and_port};
-use crate::client::transport::{ClientConfig, make_request, send_request};
-use crate::protocol::{DEFAULT_MAX_SIZE, ErrorCode, RequestKind, ResponseKind};
+use crate::client::defaults::ClientDefaults;
+use crate::client::ssh::{
+    EffectiveSshConfig, SshConfig, effective_config, resolve_target_and_port,
+};
+use crate::client::transport::{ClientConfig, RetryPolicy, make_request, send_request};
+use crate::protocol::{DEFAULT_MAX_SIZE, ErrorCode, RequestKind, Response, ResponseKind};
 use eyre::Result;
 use std::path::PathBuf;
 use tokio::process::Command;
@@ -86,11 +89,24 @@ pub async fn run(args: DoctorArgs) -> Result<()> {
         ssh_options: args.ssh_option,
         ssh_bin: args.ssh_bin,
     };
+    let target_from_flags = !ssh.target.trim().is_empty() || ssh.host.is_some();
+    let client_defaults = ClientDefaults::load();
+    if let Ok(defaults) = &client_defaults {
+        defaults.apply(&mut ssh);
+    }
     let agent_info = maybe_apply_agent_config_defaults(&mut ssh);
 
     let (target, port) = resolve_target_and_port(&ssh);
     let mut checks = Vec::new();
 
+    if let Err(err) = client_defaults {
+        checks.push(CheckOutcome::warn(
+            "client config",
+            format!("could not load client defaults: {err:#}"),
+            "fix or remove the client config file, or the SSH_CLIPBOARD_* variable named above",
+        ));
+    }
+
     if let Some(err) = agent_info.load_error {
         checks.push(CheckOutcome::warn(
             "agent config",
@@ -120,14 +136,26 @@ pub async fn run(args: DoctorArgs) -> Result<()> {
     } else {
         let source = if agent_info.used_for_target {
             "agent config"
-        } else {
+        } else if target_from_flags {
             "CLI args"
+        } else {
+            "client config"
         };
         let detail = match port {
             Some(port) => format!("using `{target}` on port {port} ({source})"),
             None => format!("using `{target}` ({source})"),
         };
         checks.push(CheckOutcome::ok("target", detail));
+        if checks.iter().all(|c| c.status != CheckStatus::Fail) {
+            match effective_config(&ssh, timeout_ms).await {
+                Ok(effective) => checks.extend(ssh_config_checks(&target, &effective)),
+                Err(err) => checks.push(CheckOutcome::warn(
+                    "ssh config",
+                    format!("could not read the effective ssh config: {err}"),
+                    "`ssh -G` needs OpenSSH 6.8 or newer; the other checks use the target as given",
+                )),
+            }
+        }
     }
 
     let mut auth_ok = false;
@@ -175,15 +203,31 @@ pub async fn run(args: DoctorArgs) -> Result<()> {
             timeout_ms,
             resync_frames: true,
             resync_max_bytes: DEFAULT_RESYNC_MAX_BYTES,
+            retry: RetryPolicy::none(),
         };
+        let mut roundtrip_ok = false;
         match send_request(&client_config, make_request(RequestKind::PeekMeta)).await {
             Ok(response) => match response.kind {
                 ResponseKind::Meta { .. } | ResponseKind::Empty => {
+                    roundtrip_ok = true;
                     checks.push(CheckOutcome::ok(
                         "protocol roundtrip",
                         "framing/protocol exchange with proxy succeeded",
                     ));
                 }
+                ResponseKind::Error {
+                    code: ErrorCode::VersionMismatch,
+                    message,
+                } => {
+                    checks.push(CheckOutcome::fail(
+                        "protocol roundtrip",
+                        format!(
+                            "client speaks protocol {} but the server does not: {message}",
+                            crate::protocol::VERSION
+                        ),
+                        "install the same ssh_clipboard release on the client and the server",
+                    ));
+                }
                 ResponseKind::Error {
                     code: ErrorCode::DaemonNotRunning,
                     message,
@@ -213,6 +257,19 @@ pub async fn run(args: DoctorArgs) -> Result<()> {
                 "verify SSH, proxy, and daemon setup; then retry",
             )),
         }
+
+        if roundtrip_ok {
+            let check = match send_request(&client_config, make_request(RequestKind::Status)).await
+            {
+                Ok(response) => version_check(response),
+                Err(err) => CheckOutcome::warn(
+                    "server version",
+                    format!("status request failed: {err}"),
+                    "run `ssh_clipboard status --target ...` for details",
+                ),
+            };
+            checks.push(check);
+        }
     }
 
     print_report(&checks);
@@ -223,13 +280,45 @@ pub async fn run(args: DoctorArgs) -> Result<()> {
         .count();
     if fail_count > 0 {
         return crate::cli::exit::exit_with_code(
-            2,
+            crate::cli::exit::EXIT_COMMAND_FAILED,
             &format!("doctor found {fail_count} failing check(s)"),
         );
     }
     Ok(())
 }
 
+/// Compares the daemon's release with this client's, from a `Status` response.
+fn version_check(response: Response) -> CheckOutcome {
+    let client = env!("CARGO_PKG_VERSION");
+    match response.kind {
+        ResponseKind::Status { status } if status.version == client => CheckOutcome::ok(
+            "server version",
+            format!(
+                "daemon {} (protocol {})",
+                status.version, status.protocol_version
+            ),
+        ),
+        ResponseKind::Status { status } => CheckOutcome::warn(
+            "server version",
+            format!("daemon is {} but this client is {client}", status.version),
+            "install the same ssh_clipboard release on the client and the server",
+        ),
+        ResponseKind::Error {
+            code: ErrorCode::Forbidden,
+            ..
+        } => CheckOutcome::warn(
+            "server version",
+            "status is not allowed for this key",
+            "add `status` to the proxy's `--allow` list to let doctor compare versions",
+        ),
+        _ => CheckOutcome::warn(
+            "server version",
+            "daemon did not answer a status request; it is likely older than this client",
+            "install the same ssh_clipboard release on the client and the server",
+        ),
+    }
+}
+
 #[cfg(target_os = "linux")]
 fn maybe_linux_hotkey_check() -> Option<CheckOutcome> {
     match detect_linux_session() {
@@ -398,6 +487,88 @@ fn print_report(checks: &[CheckOutcome]) {
     println!("summary: {ok} ok, {warn} warning(s), {fail} failure(s)");
 }
 
+/// What `ssh -G` resolved the target to, plus settings that break the framed
+/// protocol on stdin/stdout.
+fn ssh_config_checks(target: &str, config: &EffectiveSshConfig) -> Vec<CheckOutcome> {
+    let mut resolved = config.hostname.clone();
+    if let Some(user) = &config.user {
+        resolved = format!("{user}@{resolved}");
+    }
+    if let Some(port) = config.port {
+        resolved = format!("{resolved}:{port}");
+    }
+    let mut detail = format!("`{target}` resolves to {resolved}");
+    if let Some(jump) = &config.proxy_jump {
+        detail.push_str(&format!(", ProxyJump {jump}"));
+    }
+    if let Some(command) = &config.proxy_command {
+        detail.push_str(&format!(", ProxyCommand `{command}`"));
+    }
+    let identities = config
+        .identity_files
+        .iter()
+        .filter(|path| expand_home(path).exists())
+        .cloned()
+        .collect::<Vec<_>>();
+    if identities.is_empty() {
+        detail.push_str(", no identity files found (agent keys only)");
+    } else {
+        detail.push_str(&format!(", identity files {}", identities.join(" ")));
+    }
+    for (name, value) in [
+        ("ControlMaster", &config.control_master),
+        ("RequestTTY", &config.request_tty),
+    ] {
+        if let Some(value) = value
+            .as_deref()
+            .filter(|value| !matches!(*value, "false" | "no"))
+        {
+            detail.push_str(&format!(", {name} {value}"));
+        }
+    }
+    let mut checks = vec![CheckOutcome::ok("ssh config", detail)];
+
+    if let Some(command) = &config.remote_command {
+        checks.push(CheckOutcome::fail(
+            "ssh config",
+            format!("RemoteCommand `{command}` is set, so ssh refuses to run `ssh_clipboard proxy`"),
+            "pass `--ssh-option RemoteCommand=none`, or move RemoteCommand to a separate Host alias",
+        ));
+    }
+    if matches!(
+        config.request_tty.as_deref(),
+        Some("force" | "true" | "yes")
+    ) {
+        checks.push(CheckOutcome::warn(
+            "ssh config",
+            "RequestTTY is on; ssh_clipboard passes `-T`, which overrides it, but anything running `ssh <host> ssh_clipboard proxy` without `-T` gets a TTY that rewrites binary frames",
+            "set RequestTTY only for interactive aliases, or use a separate Host alias for ssh_clipboard",
+        ));
+    }
+    if let Some(level) = config
+        .log_level
+        .as_deref()
+        .filter(|level| level.to_ascii_uppercase().starts_with("DEBUG"))
+    {
+        checks.push(CheckOutcome::warn(
+            "ssh config",
+            format!(
+                "LogLevel {level} prints debug lines on every connection; they go to stderr, but bury ssh_clipboard's own errors and slow each request"
+            ),
+            "pass `--ssh-option LogLevel=ERROR`, or lower LogLevel for this host",
+        ));
+    }
+    checks
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
 #[cfg(all(
     feature = "agent",
     any(target_os = "windows", target_os = "macos", target_os = "linux")
@@ -442,3 +613,81 @@ fn maybe_apply_agent_config_defaults(_ssh: &mut SshConfig) -> AgentConfigInfo {
         load_error: None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{DaemonStatus, VERSION};
+
+    fn status_response(version: &str) -> Response {
+        Response {
+            request_id: 1,
+            kind: ResponseKind::Status {
+                status: DaemonStatus {
+                    version: version.to_string(),
+                    protocol_version: VERSION,
+                    pid: 1,
+                    uptime_secs: 0,
+                    socket_path: String::new(),
+                    max_size: 0,
+                    io_timeout_ms: 0,
+                    stored_values: 0,
+                    stored_bytes: 0,
+                    rss_bytes: None,
+                    requests: Vec::new(),
+                    errors: Vec::new(),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn version_check_warns_on_mismatch() {
+        let check = version_check(status_response(env!("CARGO_PKG_VERSION")));
+        assert!(check.status == CheckStatus::Ok);
+
+        let check = version_check(status_response("0.0.1"));
+        assert!(check.status == CheckStatus::Warn);
+        assert!(check.detail.contains("daemon is 0.0.1"));
+
+        let older = Response {
+            request_id: 1,
+            kind: ResponseKind::Error {
+                code: ErrorCode::InvalidRequest,
+                message: "decode error".to_string(),
+            },
+        };
+        assert!(
+            version_check(older)
+                .detail
+                .contains("older than this client")
+        );
+    }
+
+    #[test]
+    fn ssh_config_checks_flag_protocol_breaking_options() {
+        let mut config = crate::client::ssh::parse_effective_config(
+            "hostname 10.0.0.5\nuser alice\nport 2200\nproxyjump bastion\n\
+             identityfile /nonexistent/id_ed25519\nrequesttty auto\nloglevel INFO\n",
+        )
+        .unwrap();
+        let checks = ssh_config_checks("devbox", &config);
+        assert_eq!(checks.len(), 1);
+        assert!(checks[0].status == CheckStatus::Ok);
+        assert!(
+            checks[0]
+                .detail
+                .starts_with("`devbox` resolves to alice@10.0.0.5:2200, ProxyJump bastion")
+        );
+        assert!(checks[0].detail.contains("no identity files found"));
+
+        config.remote_command = Some("tmux".to_string());
+        config.request_tty = Some("force".to_string());
+        config.log_level = Some("DEBUG3".to_string());
+        let statuses = ssh_config_checks("devbox", &config)
+            .iter()
+            .map(|check| check.status.label())
+            .collect::<Vec<_>>();
+        assert_eq!(statuses, ["ok", "fail", "warn", "warn"]);
+    }
+}
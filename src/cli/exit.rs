@@ -1,5 +1,61 @@
+use crate::protocol::ErrorCode;
 use eyre::Result;
 
+// Codes the client commands and `proxy` share.
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_INVALID_REQUEST: i32 = 2;
+pub const EXIT_PAYLOAD_TOO_LARGE: i32 = 3;
+pub const EXIT_DAEMON_NOT_RUNNING: i32 = 4;
+
+/// A local command such as `install-client` or `doctor` did not succeed.
+pub const EXIT_COMMAND_FAILED: i32 = 2;
+
+// From `5` on the two sides differ: the client uses `5` for SSH failures, `6` for
+// clipboard errors and `7` for pushes blocked by secret detection, while `proxy`
+// uses `5` for daemon internal errors.
+pub const EXIT_POLICY_DENIED: i32 = 8;
+pub const EXIT_FORBIDDEN: i32 = 9;
+#[cfg(target_os = "linux")]
+pub const PROXY_EXIT_INTERNAL: i32 = 5;
+#[cfg(target_os = "linux")]
+pub const PROXY_EXIT_POLICY_DENIED: i32 = 6;
+#[cfg(target_os = "linux")]
+pub const PROXY_EXIT_FORBIDDEN: i32 = 7;
+
+/// Exit code of a client command for a daemon error. `internal` maps to `2`
+/// because `5` already means the SSH connection failed.
+pub fn client_exit_code(code: &ErrorCode) -> i32 {
+    match code {
+        ErrorCode::InvalidRequest
+        | ErrorCode::InvalidUtf8
+        | ErrorCode::VersionMismatch
+        | ErrorCode::Internal => EXIT_INVALID_REQUEST,
+        ErrorCode::PayloadTooLarge => EXIT_PAYLOAD_TOO_LARGE,
+        ErrorCode::DaemonNotRunning => EXIT_DAEMON_NOT_RUNNING,
+        ErrorCode::ContentTypeNotAllowed { .. }
+        | ErrorCode::PolicyLimitExceeded { .. }
+        | ErrorCode::ContentDenied { .. } => EXIT_POLICY_DENIED,
+        ErrorCode::Forbidden => EXIT_FORBIDDEN,
+    }
+}
+
+/// Exit code of `proxy` for a daemon error.
+#[cfg(target_os = "linux")]
+pub fn proxy_exit_code(code: &ErrorCode) -> i32 {
+    match code {
+        ErrorCode::InvalidRequest | ErrorCode::InvalidUtf8 | ErrorCode::VersionMismatch => {
+            EXIT_INVALID_REQUEST
+        }
+        ErrorCode::PayloadTooLarge => EXIT_PAYLOAD_TOO_LARGE,
+        ErrorCode::DaemonNotRunning => EXIT_DAEMON_NOT_RUNNING,
+        ErrorCode::Internal => PROXY_EXIT_INTERNAL,
+        ErrorCode::ContentTypeNotAllowed { .. }
+        | ErrorCode::PolicyLimitExceeded { .. }
+        | ErrorCode::ContentDenied { .. } => PROXY_EXIT_POLICY_DENIED,
+        ErrorCode::Forbidden => PROXY_EXIT_FORBIDDEN,
+    }
+}
+
 pub fn exit_with_code(code: i32, message: &str) -> Result<()> {
     eprintln!("{message}");
     std::process::exit(code);
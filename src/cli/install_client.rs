@@ -80,7 +80,10 @@ pub fn run_install(args: InstallClientArgs) -> Result<()> {
     }
     print_report("install-client", &checks);
     if result.is_err() {
-        return crate::cli::exit::exit_with_code(2, "install-client failed");
+        return crate::cli::exit::exit_with_code(
+            crate::cli::exit::EXIT_COMMAND_FAILED,
+            "install-client failed",
+        );
     }
     Ok(())
 }
@@ -97,7 +100,10 @@ pub fn run_uninstall(args: UninstallClientArgs) -> Result<()> {
     }
     print_report("uninstall-client", &checks);
     if result.is_err() {
-        return crate::cli::exit::exit_with_code(2, "uninstall-client failed");
+        return crate::cli::exit::exit_with_code(
+            crate::cli::exit::EXIT_COMMAND_FAILED,
+            "uninstall-client failed",
+        );
     }
     Ok(())
 }
@@ -905,7 +911,7 @@ fn default_install_dir_for(
     }
 }
 
-fn home_dir() -> Option<PathBuf> {
+pub(super) fn home_dir() -> Option<PathBuf> {
     #[cfg(target_os = "windows")]
     {
         env::var_os("USERPROFILE").map(PathBuf::from).or_else(|| {
@@ -1,10 +1,20 @@
 use crate::cli::{InstallDaemonArgs, UninstallDaemonArgs};
 use crate::protocol::DEFAULT_MAX_SIZE;
 use eyre::{Result, WrapErr, eyre};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 
+mod init;
+mod system;
+
+pub use init::InitSystem;
+
+const SERVICE_UNIT: &str = "ssh_clipboard.service";
+const SOCKET_UNIT: &str = "ssh_clipboard.socket";
+pub const DEFAULT_BIN_LINK: &str = "/usr/local/bin/ssh_clipboard";
+
 pub async fn run(args: InstallDaemonArgs) -> Result<()> {
     let exe = std::env::current_exe().wrap_err("failed to resolve current executable")?;
     let exe_dir = exe
@@ -14,34 +24,129 @@ pub async fn run(args: InstallDaemonArgs) -> Result<()> {
 
     ensure_executable(&exe)?;
 
-    let unit_source = exe_dir.join("ssh_clipboard.service");
-    let unit_link = user_unit_link_path()?;
-    let bin_link = PathBuf::from("/usr/local/bin/ssh_clipboard");
-
-    let max_size = if args.max_size == 0 {
-        DEFAULT_MAX_SIZE
-    } else {
-        args.max_size
-    };
-
-    let unit_contents = render_unit_file(
-        &bin_link,
+    let unit_source = exe_dir.join(SERVICE_UNIT);
+    let unit_link = user_unit_link_path(SERVICE_UNIT)?;
+    let socket_source = exe_dir.join(SOCKET_UNIT);
+    let socket_link = user_unit_link_path(SOCKET_UNIT)?;
+    let bin_link = std::path::absolute(&args.bin_link).wrap_err("failed to resolve --bin-link")?;
+
+    let max_size = args
+        .max_size
+        .map(|size| if size == 0 { DEFAULT_MAX_SIZE } else { size });
+
+    let policy = args
+        .policy
+        .as_deref()
+        .map(std::path::absolute)
+        .transpose()
+        .wrap_err("failed to resolve policy path")?;
+    // A system install passes the policy through /etc/ssh_clipboard/config.
+    let daemon_args = daemon_args(
         args.socket_path.as_deref(),
+        policy.as_deref().filter(|_| !args.system),
         max_size,
         args.io_timeout_ms,
+        args.idle_timeout_secs,
     );
 
+    let init = match args.init {
+        InitSystem::Auto if args.system => InitSystem::Systemd,
+        InitSystem::Auto => init::detect(),
+        init => init,
+    };
+    if args.system {
+        if init != InitSystem::Systemd {
+            return Err(eyre!("--system requires --init systemd"));
+        }
+        let install = system::SystemInstall {
+            unit_contents: render_unit_file(&bin_link, &daemon_args, args.socket_activation),
+            socket_contents: args
+                .socket_activation
+                .then(|| render_socket_file(args.socket_path.as_deref())),
+            bin_path: bin_link,
+            policy,
+        };
+        if args.dry_run {
+            system::print_dry_run(&exe, &install)?;
+            return Ok(());
+        }
+        system::require_root()?;
+        system::install(&exe, &install, args.force)?;
+        system::print_success(&install);
+        return Ok(());
+    }
+    if init != InitSystem::Systemd {
+        if args.socket_activation {
+            return Err(eyre!("--socket-activation requires --init systemd"));
+        }
+        let service = init::render(
+            init,
+            &bin_link,
+            &daemon_args,
+            &current_user()?,
+            std::env::var_os("HOME").as_deref().map(Path::new),
+            &init::Layout::detect()?,
+        );
+        if args.dry_run {
+            println!(
+                "dry-run: would link {} -> {}",
+                bin_link.display(),
+                exe.display()
+            );
+            init::print_dry_run(&service);
+            return Ok(());
+        }
+        install_symlink(&exe, &bin_link, args.no_sudo, args.force)?;
+        init::install(&service, args.no_sudo, args.force)?;
+        init::print_success(&bin_link, &service);
+        return Ok(());
+    }
+
+    let unit_contents = render_unit_file(&bin_link, &daemon_args, args.socket_activation);
+    let socket_contents = args
+        .socket_activation
+        .then(|| render_socket_file(args.socket_path.as_deref()));
+
     if args.dry_run {
         print_dry_run(&exe, &bin_link, &unit_source, &unit_link, &unit_contents)?;
+        if let Some(contents) = &socket_contents {
+            println!(
+                "dry-run: would write socket unit to {} and link {}",
+                socket_source.display(),
+                socket_link.display()
+            );
+            println!("dry-run: would run `systemctl --user disable --now {SERVICE_UNIT}`");
+            println!("dry-run: would run `systemctl --user enable --now {SOCKET_UNIT}`");
+            println!();
+            println!("socket unit contents:\n{contents}");
+        }
         return Ok(());
     }
 
     install_symlink(&exe, &bin_link, args.no_sudo, args.force)?;
     write_unit_file(&unit_source, &unit_contents, args.force)?;
     link_unit_file(&unit_source, &unit_link, args.force)?;
-    reload_and_start_service()?;
-    verify_service_active()?;
-    print_success(&bin_link, &unit_source, &unit_link)?;
+    match &socket_contents {
+        Some(contents) => {
+            write_unit_file(&socket_source, contents, args.force)?;
+            link_unit_file(&socket_source, &socket_link, args.force)?;
+            // The socket unit starts the service on demand; a running daemon would
+            // hold the socket path.
+            run_systemctl_user_allow_failure(&["disable", "--now", SERVICE_UNIT])?;
+            reload_and_start(SOCKET_UNIT)?;
+            verify_unit_active(SOCKET_UNIT)?;
+        }
+        None => {
+            reload_and_start(SERVICE_UNIT)?;
+            verify_unit_active(SERVICE_UNIT)?;
+        }
+    }
+    print_success(
+        &bin_link,
+        &unit_source,
+        &unit_link,
+        args.socket_activation.then_some(socket_link.as_path()),
+    )?;
     Ok(())
 }
 
@@ -63,35 +168,91 @@ fn ensure_executable(path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn render_unit_file(
-    bin_path: &Path,
+/// The `daemon` subcommand and its flags, shared by every init system's renderer.
+/// Settings left out come from the server config files at each start.
+fn daemon_args(
     socket_path: Option<&Path>,
-    max_size: usize,
-    io_timeout_ms: u64,
-) -> String {
-    let mut exec = format!(
-        "{} daemon --io-timeout-ms {} --max-size {}",
-        bin_path.display(),
-        io_timeout_ms,
-        max_size
-    );
+    policy: Option<&Path>,
+    max_size: Option<usize>,
+    io_timeout_ms: Option<u64>,
+    idle_timeout_secs: Option<u64>,
+) -> Vec<String> {
+    let mut args = vec!["daemon".to_string()];
+    if let Some(ms) = io_timeout_ms {
+        args.push("--io-timeout-ms".to_string());
+        args.push(ms.to_string());
+    }
+    if let Some(size) = max_size {
+        args.push("--max-size".to_string());
+        args.push(size.to_string());
+    }
     if let Some(path) = socket_path {
-        let quoted = systemd_quote_arg(&path.to_string_lossy());
-        exec.push_str(&format!(" --socket-path {quoted}"));
+        args.push("--socket-path".to_string());
+        args.push(path.to_string_lossy().into_owned());
     }
+    if let Some(path) = policy {
+        args.push("--policy".to_string());
+        args.push(path.to_string_lossy().into_owned());
+    }
+    if let Some(secs) = idle_timeout_secs {
+        args.push("--idle-timeout-secs".to_string());
+        args.push(secs.to_string());
+    }
+    args
+}
 
+fn render_unit_file(bin_path: &Path, daemon_args: &[String], socket_activation: bool) -> String {
+    let exec = std::iter::once(bin_path.display().to_string())
+        .chain(daemon_args.iter().map(|arg| systemd_quote_arg(arg)))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // Socket-activated services are started by the socket unit, not at login.
+    let (requires, install) = if socket_activation {
+        (
+            format!("Requires={SOCKET_UNIT}\nAfter={SOCKET_UNIT}\n"),
+            String::new(),
+        )
+    } else {
+        (
+            String::new(),
+            "\n[Install]\nWantedBy=default.target\n".to_string(),
+        )
+    };
     format!(
         "[Unit]\n\
 Description=SSH Clipboard Daemon\n\
 After=network.target\n\
+{requires}\
 \n\
 [Service]\n\
+Type=notify\n\
 ExecStart={exec}\n\
+ExecReload=/bin/kill -HUP $MAINPID\n\
+WatchdogSec=30\n\
 Restart=on-failure\n\
 RestartSec=1\n\
+{install}"
+    )
+}
+
+/// `ListenStream` defaults to the daemon's default socket under `$XDG_RUNTIME_DIR`
+/// (`%t` in a user unit).
+fn render_socket_file(socket_path: Option<&Path>) -> String {
+    let listen = socket_path
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "%t/ssh_clipboard/daemon.sock".to_string());
+    format!(
+        "[Unit]\n\
+Description=SSH Clipboard Daemon Socket\n\
+\n\
+[Socket]\n\
+ListenStream={listen}\n\
+SocketMode=0600\n\
+DirectoryMode=0700\n\
 \n\
 [Install]\n\
-WantedBy=default.target\n"
+WantedBy=sockets.target\n"
     )
 }
 
@@ -116,13 +277,13 @@ fn systemd_quote_arg(value: &str) -> String {
     out
 }
 
-fn user_unit_link_path() -> Result<PathBuf> {
+fn user_unit_link_path(unit: &str) -> Result<PathBuf> {
     let home = std::env::var("HOME").wrap_err("HOME is not set")?;
     Ok(Path::new(&home)
         .join(".config")
         .join("systemd")
         .join("user")
-        .join("ssh_clipboard.service"))
+        .join(unit))
 }
 
 fn print_dry_run(
@@ -147,13 +308,31 @@ fn print_dry_run(
         unit_source.display()
     );
     println!("dry-run: would run `systemctl --user daemon-reload`");
-    println!("dry-run: would run `systemctl --user enable --now ssh_clipboard.service`");
+    println!("dry-run: would run `systemctl --user enable --now {SERVICE_UNIT}`");
     println!();
     println!("unit file contents:\n{unit_contents}");
     Ok(())
 }
 
+/// `--bin-link` may name the executable itself (as `install-server` does), in
+/// which case there is nothing to link.
+fn is_executable_itself(exe: &Path, bin_link: &Path) -> bool {
+    std::fs::symlink_metadata(bin_link).is_ok_and(|meta| !meta.file_type().is_symlink())
+        && std::fs::canonicalize(bin_link).is_ok_and(|path| path == exe)
+}
+
+/// Whether the current user can create entries in `dir` without sudo.
+fn can_write_dir(dir: &Path) -> bool {
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
 fn install_symlink(exe: &Path, bin_link: &Path, no_sudo: bool, force: bool) -> Result<()> {
+    if is_executable_itself(exe, bin_link) {
+        return Ok(());
+    }
     if bin_link.exists() {
         let meta = std::fs::symlink_metadata(bin_link)?;
         if !meta.file_type().is_symlink() && !force {
@@ -164,12 +343,12 @@ fn install_symlink(exe: &Path, bin_link: &Path, no_sudo: bool, force: bool) -> R
         }
     }
 
-    if is_root() {
+    if is_root() || bin_link.parent().is_some_and(can_write_dir) {
         if bin_link.exists() {
             let _ = std::fs::remove_file(bin_link);
         }
         std::os::unix::fs::symlink(exe, bin_link)
-            .wrap_err("failed to create /usr/local/bin symlink")?;
+            .wrap_err_with(|| format!("failed to create {}", bin_link.display()))?;
         return Ok(());
     }
 
@@ -221,20 +400,20 @@ fn link_unit_file(source: &Path, link: &Path, force: bool) -> Result<()> {
     Ok(())
 }
 
-fn reload_and_start_service() -> Result<()> {
+fn reload_and_start(unit: &str) -> Result<()> {
     run_systemctl_user(&["daemon-reload"])?;
-    run_systemctl_user(&["enable", "--now", "ssh_clipboard.service"])?;
+    run_systemctl_user(&["enable", "--now", unit])?;
     Ok(())
 }
 
-fn verify_service_active() -> Result<()> {
-    let output = run_systemctl_user_allow_failure(&["is-active", "ssh_clipboard.service"])
+fn verify_unit_active(unit: &str) -> Result<()> {
+    let output = run_systemctl_user_allow_failure(&["is-active", unit])
         .wrap_err("failed to run systemctl is-active")?;
     if !output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
         let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(eyre!(
-            "service did not start ({}{}); try: systemctl --user status ssh_clipboard.service",
+            "{unit} did not start ({}{}); try: systemctl --user status {unit}",
             stdout.trim(),
             if stderr.trim().is_empty() {
                 ""
@@ -246,13 +425,24 @@ fn verify_service_active() -> Result<()> {
     Ok(())
 }
 
-fn print_success(bin_link: &Path, unit_source: &Path, unit_link: &Path) -> Result<()> {
+fn print_success(
+    bin_link: &Path,
+    unit_source: &Path,
+    unit_link: &Path,
+    socket_link: Option<&Path>,
+) -> Result<()> {
     println!("installed:");
     println!("- binary link: {}", bin_link.display());
     println!("- unit source: {}", unit_source.display());
     println!("- unit link: {}", unit_link.display());
+    if let Some(link) = socket_link {
+        println!("- socket unit link: {}", link.display());
+    }
     println!();
     println!("status:");
+    if socket_link.is_some() {
+        println!("  systemctl --user status {SOCKET_UNIT}");
+    }
     println!("  systemctl --user status ssh_clipboard.service");
     println!("  journalctl --user -u ssh_clipboard.service -f");
     println!();
@@ -311,6 +501,14 @@ fn is_root() -> bool {
     unsafe { libc::geteuid() == 0 }
 }
 
+/// Who the daemon runs as under the system-wide init systems.
+fn current_user() -> Result<String> {
+    ["USER", "LOGNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|user| !user.is_empty()))
+        .ok_or_else(|| eyre!("could not determine the current user; set USER"))
+}
+
 pub async fn run_uninstall(args: UninstallDaemonArgs) -> Result<()> {
     let exe = std::env::current_exe().wrap_err("failed to resolve current executable")?;
     let exe_dir = exe
@@ -320,12 +518,51 @@ pub async fn run_uninstall(args: UninstallDaemonArgs) -> Result<()> {
 
     ensure_executable(&exe)?;
 
-    let unit_source = exe_dir.join("ssh_clipboard.service");
-    let unit_link = user_unit_link_path()?;
-    let bin_link = PathBuf::from("/usr/local/bin/ssh_clipboard");
+    let bin_link = std::path::absolute(&args.bin_link).wrap_err("failed to resolve --bin-link")?;
+    if args.system {
+        if args.dry_run {
+            system::print_dry_run_uninstall(&bin_link);
+            return Ok(());
+        }
+        system::require_root()?;
+        system::uninstall(&exe, &bin_link)?;
+        system::print_uninstall_success(&bin_link);
+        return Ok(());
+    }
+    let layout = init::Layout::detect()?;
+    let init = match args.init {
+        InitSystem::Auto => init::detect_installed(&layout).unwrap_or(InitSystem::Systemd),
+        init => init,
+    };
+    if init != InitSystem::Systemd {
+        let service = init::render(init, &bin_link, &[], "", None, &layout);
+        if args.dry_run {
+            init::print_dry_run_uninstall(&service);
+            println!("dry-run: would remove binary link {}", bin_link.display());
+            return Ok(());
+        }
+        init::uninstall(&service, args.no_sudo)?;
+        remove_bin_link_if_matches(&exe, &bin_link, args.no_sudo)?;
+        init::print_uninstall_success(&service);
+        println!("- binary link: {}", bin_link.display());
+        return Ok(());
+    }
+
+    let unit_source = exe_dir.join(SERVICE_UNIT);
+    let unit_link = user_unit_link_path(SERVICE_UNIT)?;
+    let socket_source = exe_dir.join(SOCKET_UNIT);
+    let socket_link = user_unit_link_path(SOCKET_UNIT)?;
+    let socket_installed = socket_link.exists() || socket_source.exists();
 
     if args.dry_run {
-        println!("dry-run: would run `systemctl --user disable --now ssh_clipboard.service`");
+        if socket_installed {
+            println!("dry-run: would run `systemctl --user disable --now {SOCKET_UNIT}`");
+            println!(
+                "dry-run: would remove socket unit {}",
+                socket_link.display()
+            );
+        }
+        println!("dry-run: would run `systemctl --user disable --now {SERVICE_UNIT}`");
         println!("dry-run: would remove unit link {}", unit_link.display());
         println!(
             "dry-run: would remove unit source {}",
@@ -335,7 +572,12 @@ pub async fn run_uninstall(args: UninstallDaemonArgs) -> Result<()> {
         return Ok(());
     }
 
-    disable_service_if_present()?;
+    if socket_installed {
+        disable_unit_if_present(SOCKET_UNIT)?;
+        remove_unit_link(&socket_link)?;
+        remove_unit_source(&socket_source)?;
+    }
+    disable_unit_if_present(SERVICE_UNIT)?;
     remove_unit_link(&unit_link)?;
     remove_unit_source(&unit_source)?;
     remove_bin_link_if_matches(&exe, &bin_link, args.no_sudo)?;
@@ -343,9 +585,9 @@ pub async fn run_uninstall(args: UninstallDaemonArgs) -> Result<()> {
     Ok(())
 }
 
-fn disable_service_if_present() -> Result<()> {
+fn disable_unit_if_present(unit: &str) -> Result<()> {
     let output = Command::new("systemctl")
-        .args(["--user", "disable", "--now", "ssh_clipboard.service"])
+        .args(["--user", "disable", "--now", unit])
         .output()
         .wrap_err("failed to run systemctl disable")?;
     if output.status.success() {
@@ -386,7 +628,7 @@ fn remove_unit_source(path: &Path) -> Result<()> {
 }
 
 fn remove_bin_link_if_matches(exe: &Path, link: &Path, no_sudo: bool) -> Result<()> {
-    if !link.exists() {
+    if !link.exists() || is_executable_itself(exe, link) {
         return Ok(());
     }
     let meta = std::fs::symlink_metadata(link)?;
@@ -405,7 +647,7 @@ fn remove_bin_link_if_matches(exe: &Path, link: &Path, no_sudo: bool) -> Result<
         ));
     }
 
-    if is_root() {
+    if is_root() || link.parent().is_some_and(can_write_dir) {
         std::fs::remove_file(link).wrap_err("failed to remove binary link")?;
         return Ok(());
     }
@@ -436,20 +678,55 @@ mod tests {
 
     #[test]
     fn unit_render_contains_execstart() {
-        let contents = render_unit_file(Path::new("/usr/local/bin/ssh_clipboard"), None, 10, 7000);
+        let contents = render_unit_file(
+            Path::new("/usr/local/bin/ssh_clipboard"),
+            &daemon_args(
+                None,
+                Some(Path::new("/etc/ssh_clipboard/policy.toml")),
+                Some(10),
+                Some(7000),
+                None,
+            ),
+            false,
+        );
         assert!(contents.contains("ExecStart=/usr/local/bin/ssh_clipboard daemon"));
+        assert!(contents.contains("\nType=notify\n"));
+        assert!(contents.contains("\nExecReload=/bin/kill -HUP $MAINPID\n"));
         assert!(contents.contains("--max-size 10"));
         assert!(contents.contains("--io-timeout-ms 7000"));
+        assert!(contents.contains("--policy /etc/ssh_clipboard/policy.toml"));
     }
 
     #[test]
     fn unit_render_quotes_socket_path() {
         let contents = render_unit_file(
             Path::new("/usr/local/bin/ssh_clipboard"),
-            Some(Path::new("/run/user/1000/ssh clipboard.sock")),
-            10,
-            7000,
+            &daemon_args(
+                Some(Path::new("/run/user/1000/ssh clipboard.sock")),
+                None,
+                Some(10),
+                Some(7000),
+                None,
+            ),
+            false,
         );
         assert!(contents.contains("--socket-path \"/run/user/1000/ssh clipboard.sock\""));
     }
+
+    #[test]
+    fn socket_activation_units_start_on_demand() {
+        let service = render_unit_file(
+            Path::new("/usr/local/bin/ssh_clipboard"),
+            &daemon_args(None, None, None, None, Some(600)),
+            true,
+        );
+        assert!(service.contains("\nRequires=ssh_clipboard.socket\n"));
+        assert!(service.contains("--idle-timeout-secs 600"));
+        assert!(!service.contains("[Install]"));
+
+        let socket = render_socket_file(None);
+        assert!(socket.contains("\nListenStream=%t/ssh_clipboard/daemon.sock\n"));
+        assert!(socket.contains("\nSocketMode=0600\n"));
+        assert!(socket.contains("\nWantedBy=sockets.target\n"));
+    }
 }
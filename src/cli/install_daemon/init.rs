@@ -0,0 +1,687 @@
+use super::{is_root, run_command, run_sudo};
+use clap::ValueEnum;
+use eyre::{Result, WrapErr, eyre};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+const SERVICE_NAME: &str = "ssh_clipboard";
+const OPENRC_SCRIPT: &str = "/etc/init.d/ssh_clipboard";
+const RUNIT_SERVICE: &str = "/etc/sv/ssh_clipboard";
+
+/// Service manager `install-daemon` writes files for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum InitSystem {
+    /// Pick one from what is running on this machine.
+    #[default]
+    Auto,
+    Systemd,
+    Openrc,
+    Runit,
+    Supervisord,
+    XdgAutostart,
+    /// Install the binary only; start the daemon yourself.
+    None,
+}
+
+/// Picks the init system from markers on this machine. systemd wins when it is
+/// PID 1; desktop autostart is only chosen inside a desktop session.
+pub fn detect() -> InitSystem {
+    let desktop = ["XDG_CURRENT_DESKTOP", "DESKTOP_SESSION"]
+        .iter()
+        .any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()));
+    detect_with(marker_present, desktop)
+}
+
+/// A marker is a path when it starts with `/`, otherwise a command on `PATH`.
+fn marker_present(marker: &str) -> bool {
+    if marker.starts_with('/') {
+        return Path::new(marker).exists();
+    }
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(marker).is_file()))
+}
+
+fn detect_with(present: impl Fn(&str) -> bool, desktop: bool) -> InitSystem {
+    if present("/run/systemd/system") {
+        InitSystem::Systemd
+    } else if present("/run/openrc") {
+        InitSystem::Openrc
+    } else if present("/run/runit") || (present("runsvdir") && present("/etc/service")) {
+        InitSystem::Runit
+    } else if present("supervisorctl") {
+        InitSystem::Supervisord
+    } else if desktop {
+        InitSystem::XdgAutostart
+    } else {
+        InitSystem::None
+    }
+}
+
+/// Where the service files go on this machine.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    runit_service_dir: PathBuf,
+    supervisor_conf: PathBuf,
+    autostart_dir: PathBuf,
+}
+
+impl Layout {
+    pub fn detect() -> Result<Self> {
+        let first_existing = |candidates: &[&str]| {
+            candidates
+                .iter()
+                .map(PathBuf::from)
+                .find(|path| path.is_dir())
+                .unwrap_or_else(|| PathBuf::from(candidates[0]))
+        };
+        let supervisor_conf = if Path::new("/etc/supervisord.d").is_dir()
+            && !Path::new("/etc/supervisor/conf.d").is_dir()
+        {
+            PathBuf::from("/etc/supervisord.d/ssh_clipboard.ini")
+        } else {
+            PathBuf::from("/etc/supervisor/conf.d/ssh_clipboard.conf")
+        };
+        let config_home = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(&std::env::var("HOME").wrap_err("HOME is not set")?).join(".config"),
+        };
+        Ok(Self {
+            runit_service_dir: first_existing(&[
+                "/var/service",
+                "/etc/service",
+                "/etc/runit/runsvdir/default",
+            ]),
+            supervisor_conf,
+            autostart_dir: config_home.join("autostart"),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceFile {
+    path: PathBuf,
+    contents: String,
+    mode: u32,
+}
+
+/// Everything needed to install or remove the daemon under one init system.
+#[derive(Debug, Clone)]
+pub struct Service {
+    init: InitSystem,
+    /// The daemon command line, for `--init none` and the success message.
+    command: String,
+    files: Vec<ServiceFile>,
+    start: Vec<Vec<String>>,
+    stop: Vec<Vec<String>>,
+    /// Run after the files are removed (e.g. to make supervisord forget the program).
+    after_remove: Vec<Vec<String>>,
+    /// Files and commands need root.
+    system: bool,
+}
+
+fn command(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// Renders the service for `init`, which must not be `Auto` or `Systemd` (the
+/// systemd unit is rendered by `render_unit_file`). `user` is who the daemon runs as
+/// under the system-wide init systems, and `home` their home directory; runit and
+/// supervisord switch users without setting `HOME`, which the daemon needs to find
+/// its user config.
+pub fn render(
+    init: InitSystem,
+    bin_path: &Path,
+    daemon_args: &[String],
+    user: &str,
+    home: Option<&Path>,
+    layout: &Layout,
+) -> Service {
+    let argv: Vec<String> = std::iter::once(bin_path.display().to_string())
+        .chain(daemon_args.iter().cloned())
+        .collect();
+    let command_line = argv
+        .iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut service = Service {
+        init,
+        command: command_line.clone(),
+        files: Vec::new(),
+        start: Vec::new(),
+        stop: Vec::new(),
+        after_remove: Vec::new(),
+        system: true,
+    };
+    match init {
+        InitSystem::Openrc => {
+            service.files.push(ServiceFile {
+                path: PathBuf::from(OPENRC_SCRIPT),
+                contents: render_openrc(bin_path, daemon_args, user),
+                mode: 0o755,
+            });
+            service.start = vec![
+                command(&["rc-update", "add", SERVICE_NAME, "default"]),
+                command(&["rc-service", SERVICE_NAME, "restart"]),
+            ];
+            service.stop = vec![
+                command(&["rc-service", SERVICE_NAME, "stop"]),
+                command(&["rc-update", "del", SERVICE_NAME, "default"]),
+            ];
+        }
+        InitSystem::Runit => {
+            let link = layout.runit_service_dir.join(SERVICE_NAME);
+            service.files.push(ServiceFile {
+                path: Path::new(RUNIT_SERVICE).join("run"),
+                contents: render_runit(&command_line, user, home),
+                mode: 0o755,
+            });
+            service.start = vec![command(&[
+                "ln",
+                "-sfn",
+                RUNIT_SERVICE,
+                &link.display().to_string(),
+            ])];
+            service.stop = vec![command(&["rm", "-f", &link.display().to_string()])];
+            service.after_remove = vec![command(&["rm", "-rf", RUNIT_SERVICE])];
+        }
+        InitSystem::Supervisord => {
+            service.files.push(ServiceFile {
+                path: layout.supervisor_conf.clone(),
+                contents: render_supervisord(&command_line, user, home),
+                mode: 0o644,
+            });
+            service.start = vec![
+                command(&["supervisorctl", "reread"]),
+                command(&["supervisorctl", "update", SERVICE_NAME]),
+            ];
+            service.stop = vec![command(&["supervisorctl", "stop", SERVICE_NAME])];
+            service.after_remove = vec![
+                command(&["supervisorctl", "reread"]),
+                command(&["supervisorctl", "update"]),
+            ];
+        }
+        InitSystem::XdgAutostart => {
+            service.system = false;
+            service.files.push(ServiceFile {
+                path: layout.autostart_dir.join("ssh_clipboard.desktop"),
+                contents: render_xdg_autostart(&argv),
+                mode: 0o644,
+            });
+        }
+        InitSystem::None => service.system = false,
+        InitSystem::Auto | InitSystem::Systemd => {
+            unreachable!("systemd units are rendered by render_unit_file")
+        }
+    }
+    service
+}
+
+/// The init system whose service file is on disk, for `uninstall-daemon --init auto`.
+pub fn detect_installed(layout: &Layout) -> Option<InitSystem> {
+    [
+        InitSystem::Openrc,
+        InitSystem::Runit,
+        InitSystem::Supervisord,
+        InitSystem::XdgAutostart,
+    ]
+    .into_iter()
+    .find(|init| {
+        render(*init, Path::new(""), &[], "", None, layout)
+            .files
+            .iter()
+            .any(|file| file.path.exists())
+    })
+}
+
+fn render_openrc(bin_path: &Path, daemon_args: &[String], user: &str) -> String {
+    let args = daemon_args
+        .iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "#!/sbin/openrc-run\n\
+# Generated by ssh_clipboard install-daemon.\n\
+\n\
+name=\"ssh_clipboard\"\n\
+description=\"SSH Clipboard Daemon\"\n\
+command={command}\n\
+command_args={args}\n\
+command_user={user}\n\
+command_background=true\n\
+pidfile=\"/run/${{RC_SVCNAME}}.pid\"\n\
+retry=\"TERM/10/KILL/5\"\n\
+extra_started_commands=\"reload\"\n\
+\n\
+depend() {{\n\
+\tneed localmount\n\
+\tafter net\n\
+}}\n\
+\n\
+reload() {{\n\
+\tebegin \"Reloading ${{RC_SVCNAME}}\"\n\
+\tstart-stop-daemon --signal HUP --pidfile \"${{pidfile}}\"\n\
+\teend $?\n\
+}}\n",
+        command = shell_quote(&bin_path.display().to_string()),
+        // OpenRC evals `command_args`, so the quoted words are quoted once more.
+        args = shell_quote(&args),
+        user = shell_quote(user),
+    )
+}
+
+fn render_runit(command_line: &str, user: &str, home: Option<&Path>) -> String {
+    let export_home = home
+        .map(|home| format!("export HOME={}\n", shell_quote(&home.display().to_string())))
+        .unwrap_or_default();
+    format!(
+        "#!/bin/sh\n\
+# Generated by ssh_clipboard install-daemon.\n\
+exec 2>&1\n\
+{export_home}\
+exec chpst -u {user} {command_line}\n",
+        user = shell_quote(user),
+    )
+}
+
+fn render_supervisord(command_line: &str, user: &str, home: Option<&Path>) -> String {
+    // supervisord expands `%(name)s`, so literal percent signs are doubled.
+    let environment = home
+        .map(|home| {
+            let home = home.display().to_string();
+            format!(
+                "environment=HOME=\"{}\"\n",
+                home.replace('%', "%%").replace('"', "\\\"")
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "; Generated by ssh_clipboard install-daemon.\n\
+[program:ssh_clipboard]\n\
+command={command}\n\
+user={user}\n\
+{environment}\
+autostart=true\n\
+autorestart=true\n\
+stopsignal=TERM\n\
+stopwaitsecs=10\n\
+redirect_stderr=true\n",
+        command = command_line.replace('%', "%%"),
+    )
+}
+
+fn render_xdg_autostart(argv: &[String]) -> String {
+    let exec = argv
+        .iter()
+        .map(|arg| desktop_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "[Desktop Entry]\n\
+Type=Application\n\
+Name=SSH Clipboard Daemon\n\
+Comment=Stores the clipboard for ssh_clipboard proxy connections\n\
+Exec={exec}\n\
+Terminal=false\n\
+NoDisplay=true\n\
+X-GNOME-Autostart-enabled=true\n"
+    )
+}
+
+fn shell_quote(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Quoting for `Exec=` in desktop entries: reserved characters need double quotes,
+/// and `%` is a field code.
+fn desktop_quote(value: &str) -> String {
+    let value = value.replace('%', "%%");
+    if !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c))
+    {
+        return value;
+    }
+    let mut out = String::from("\"");
+    for ch in value.chars() {
+        if matches!(ch, '"' | '`' | '$' | '\\') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out.push('"');
+    out
+}
+
+pub fn print_dry_run(service: &Service) {
+    let prefix = if service.system && !is_root() {
+        "sudo "
+    } else {
+        ""
+    };
+    for file in &service.files {
+        println!(
+            "dry-run: would write {} (mode {:o})",
+            file.path.display(),
+            file.mode
+        );
+    }
+    for args in &service.start {
+        println!("dry-run: would run `{prefix}{}`", args.join(" "));
+    }
+    for file in &service.files {
+        println!();
+        println!("{} contents:\n{}", file.path.display(), file.contents);
+    }
+    if service.init == InitSystem::None {
+        println!(
+            "dry-run: no init system; the daemon command would be:\n  {}",
+            service.command
+        );
+    }
+}
+
+pub fn print_dry_run_uninstall(service: &Service) {
+    let prefix = if service.system && !is_root() {
+        "sudo "
+    } else {
+        ""
+    };
+    for args in &service.stop {
+        println!("dry-run: would run `{prefix}{}`", args.join(" "));
+    }
+    for file in &service.files {
+        println!("dry-run: would remove {}", file.path.display());
+    }
+    for args in &service.after_remove {
+        println!("dry-run: would run `{prefix}{}`", args.join(" "));
+    }
+}
+
+pub fn install(service: &Service, no_sudo: bool, force: bool) -> Result<()> {
+    for file in &service.files {
+        if file.path.exists() && !force {
+            return Err(eyre!(
+                "{} already exists; use --force to overwrite",
+                file.path.display()
+            ));
+        }
+        write_file(service, file, no_sudo)?;
+    }
+    for args in &service.start {
+        run(service, args, no_sudo)?;
+    }
+    Ok(())
+}
+
+pub fn uninstall(service: &Service, no_sudo: bool) -> Result<()> {
+    // Stopping fails when the service is not running; removal still proceeds.
+    for args in &service.stop {
+        if let Err(err) = run(service, args, no_sudo) {
+            eprintln!("warning: {err}");
+        }
+    }
+    for file in &service.files {
+        if !file.path.exists() {
+            continue;
+        }
+        let path = file.path.display().to_string();
+        if service.system && !is_root() {
+            require_sudo(no_sudo, &format!("rm -f {path}"))?;
+            run_sudo(&["rm", "-f", &path])?;
+        } else {
+            std::fs::remove_file(&file.path)
+                .wrap_err_with(|| format!("failed to remove {path}"))?;
+        }
+    }
+    for args in &service.after_remove {
+        run(service, args, no_sudo)?;
+    }
+    Ok(())
+}
+
+fn require_sudo(no_sudo: bool, instead: &str) -> Result<()> {
+    if no_sudo {
+        return Err(eyre!(
+            "root permissions required; run as root or: sudo {instead}"
+        ));
+    }
+    Ok(())
+}
+
+fn run(service: &Service, args: &[String], no_sudo: bool) -> Result<()> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    if service.system && !is_root() {
+        require_sudo(no_sudo, &args.join(" "))?;
+        run_sudo(&args)
+    } else {
+        run_command(args[0], &args[1..])
+    }
+}
+
+fn write_file(service: &Service, file: &ServiceFile, no_sudo: bool) -> Result<()> {
+    let path = &file.path;
+    if !service.system || is_root() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("failed to create {}", parent.display()))?;
+        }
+        std::fs::write(path, &file.contents)
+            .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(file.mode))?;
+        return Ok(());
+    }
+
+    // Stage the file where we can write, then let `install` put it in place as root.
+    let staged = std::env::temp_dir().join(format!(
+        "ssh_clipboard-{}-{}",
+        std::process::id(),
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("service")
+    ));
+    let mode = format!("{:o}", file.mode);
+    require_sudo(
+        no_sudo,
+        &format!("install -D -m {mode} <file> {}", path.display()),
+    )?;
+    std::fs::write(&staged, &file.contents)
+        .wrap_err_with(|| format!("failed to write {}", staged.display()))?;
+    let result = run_sudo(&[
+        "install",
+        "-D",
+        "-m",
+        &mode,
+        &staged.display().to_string(),
+        &path.display().to_string(),
+    ]);
+    let _ = std::fs::remove_file(&staged);
+    result
+}
+
+pub fn print_success(bin_link: &Path, service: &Service) {
+    println!("installed:");
+    println!("- binary link: {}", bin_link.display());
+    for file in &service.files {
+        println!("- {}", file.path.display());
+    }
+    println!();
+    match service.init {
+        InitSystem::Openrc => {
+            println!("status:");
+            println!("  rc-service ssh_clipboard status");
+        }
+        InitSystem::Runit => {
+            println!("status:");
+            println!("  sv status ssh_clipboard");
+        }
+        InitSystem::Supervisord => {
+            println!("status:");
+            println!("  supervisorctl status ssh_clipboard");
+        }
+        InitSystem::XdgAutostart => {
+            println!("the daemon starts with your next desktop login; to start it now:");
+            println!("  {} &", service.command);
+        }
+        InitSystem::None => {
+            println!("no init system configured; start the daemon from your entrypoint:");
+            println!("  {}", service.command);
+            println!("or let the proxy start it: ssh_clipboard proxy --autostart-daemon");
+        }
+        InitSystem::Auto | InitSystem::Systemd => {}
+    }
+    println!();
+    println!("test over SSH:");
+    println!("  ssh -T user@server ssh_clipboard proxy");
+}
+
+pub fn print_uninstall_success(service: &Service) {
+    println!("removed:");
+    for file in &service.files {
+        println!("- {}", file.path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> Layout {
+        Layout {
+            runit_service_dir: PathBuf::from("/var/service"),
+            supervisor_conf: PathBuf::from("/etc/supervisor/conf.d/ssh_clipboard.conf"),
+            autostart_dir: PathBuf::from("/home/alice/.config/autostart"),
+        }
+    }
+
+    fn render_for(init: InitSystem) -> Service {
+        let args = [
+            "daemon",
+            "--io-timeout-ms",
+            "7000",
+            "--socket-path",
+            "/srv/clip board/daemon.sock",
+        ]
+        .map(String::from);
+        render(
+            init,
+            Path::new("/usr/local/bin/ssh_clipboard"),
+            &args,
+            "alice",
+            Some(Path::new("/home/alice")),
+            &layout(),
+        )
+    }
+
+    #[test]
+    fn openrc_script_runs_as_the_user() {
+        let service = render_for(InitSystem::Openrc);
+        let script = &service.files[0];
+        assert_eq!(script.path, Path::new("/etc/init.d/ssh_clipboard"));
+        assert_eq!(script.mode, 0o755);
+        assert!(script.contents.starts_with("#!/sbin/openrc-run\n"));
+        assert!(
+            script
+                .contents
+                .contains("\ncommand=/usr/local/bin/ssh_clipboard\n")
+        );
+        assert!(script.contents.contains(
+            "\ncommand_args='daemon --io-timeout-ms 7000 --socket-path '\\''/srv/clip board/daemon.sock'\\'''\n"
+        ));
+        assert!(script.contents.contains("\ncommand_user=alice\n"));
+        assert!(script.contents.contains("--signal HUP"));
+        assert_eq!(
+            service.start[0],
+            ["rc-update", "add", "ssh_clipboard", "default"]
+        );
+        assert!(service.system);
+    }
+
+    #[test]
+    fn runit_run_script_and_link() {
+        let service = render_for(InitSystem::Runit);
+        let run = &service.files[0];
+        assert_eq!(run.path, Path::new("/etc/sv/ssh_clipboard/run"));
+        assert!(run.contents.contains("\nexport HOME=/home/alice\n"));
+        assert!(run.contents.contains(
+            "\nexec chpst -u alice /usr/local/bin/ssh_clipboard daemon --io-timeout-ms 7000 --socket-path '/srv/clip board/daemon.sock'\n"
+        ));
+        assert_eq!(
+            service.start[0],
+            [
+                "ln",
+                "-sfn",
+                "/etc/sv/ssh_clipboard",
+                "/var/service/ssh_clipboard"
+            ]
+        );
+        assert_eq!(service.stop[0], ["rm", "-f", "/var/service/ssh_clipboard"]);
+    }
+
+    #[test]
+    fn supervisord_program_section() {
+        let service = render_for(InitSystem::Supervisord);
+        let conf = &service.files[0].contents;
+        assert!(conf.contains("\n[program:ssh_clipboard]\n"));
+        assert!(conf.contains(
+            "\ncommand=/usr/local/bin/ssh_clipboard daemon --io-timeout-ms 7000 --socket-path '/srv/clip board/daemon.sock'\n"
+        ));
+        assert!(conf.contains("\nuser=alice\nenvironment=HOME=\"/home/alice\"\n"));
+        assert_eq!(
+            service.after_remove.last().unwrap(),
+            &["supervisorctl", "update"]
+        );
+    }
+
+    #[test]
+    fn xdg_autostart_entry_is_per_user() {
+        let service = render_for(InitSystem::XdgAutostart);
+        let entry = &service.files[0];
+        assert_eq!(
+            entry.path,
+            Path::new("/home/alice/.config/autostart/ssh_clipboard.desktop")
+        );
+        assert!(entry.contents.contains(
+            "\nExec=/usr/local/bin/ssh_clipboard daemon --io-timeout-ms 7000 --socket-path \"/srv/clip board/daemon.sock\"\n"
+        ));
+        assert!(!service.system);
+        assert!(service.start.is_empty());
+
+        let none = render_for(InitSystem::None);
+        assert!(none.files.is_empty());
+        assert!(
+            none.command
+                .ends_with("--socket-path '/srv/clip board/daemon.sock'")
+        );
+    }
+
+    #[test]
+    fn detection_prefers_the_running_init() {
+        let with = |markers: &'static [&'static str]| move |marker: &str| markers.contains(&marker);
+        assert_eq!(
+            detect_with(with(&["/run/systemd/system", "supervisorctl"]), true),
+            InitSystem::Systemd
+        );
+        assert_eq!(
+            detect_with(with(&["/run/openrc"]), false),
+            InitSystem::Openrc
+        );
+        assert_eq!(detect_with(with(&["/run/runit"]), false), InitSystem::Runit);
+        assert_eq!(
+            detect_with(with(&["runsvdir", "/etc/service"]), false),
+            InitSystem::Runit
+        );
+        assert_eq!(
+            detect_with(with(&["supervisorctl"]), false),
+            InitSystem::Supervisord
+        );
+        assert_eq!(detect_with(with(&[]), true), InitSystem::XdgAutostart);
+        assert_eq!(detect_with(with(&[]), false), InitSystem::None);
+    }
+}
@@ -0,0 +1,261 @@
+use super::{SERVICE_UNIT, SOCKET_UNIT};
+use crate::server_config::{SYSTEM_CONFIG_PATH, ServerConfig};
+use eyre::{Result, WrapErr, eyre};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// User units shared by every account. Enabled with `systemctl --global`, each
+/// user's systemd instance starts its own daemon at login, under that user's
+/// `$XDG_RUNTIME_DIR`, where `proxy` looks for the socket.
+const GLOBAL_UNIT_DIR: &str = "/etc/systemd/user";
+
+pub(super) struct SystemInstall {
+    pub bin_path: PathBuf,
+    pub unit_contents: String,
+    pub socket_contents: Option<String>,
+    pub policy: Option<PathBuf>,
+}
+
+impl SystemInstall {
+    fn unit_path(&self) -> PathBuf {
+        Path::new(GLOBAL_UNIT_DIR).join(SERVICE_UNIT)
+    }
+
+    fn socket_path(&self) -> PathBuf {
+        Path::new(GLOBAL_UNIT_DIR).join(SOCKET_UNIT)
+    }
+
+    /// The unit users' managers start: the socket when socket-activated.
+    fn enabled_unit(&self) -> &'static str {
+        if self.socket_contents.is_some() {
+            SOCKET_UNIT
+        } else {
+            SERVICE_UNIT
+        }
+    }
+}
+
+pub(super) fn require_root() -> Result<()> {
+    if !super::is_root() {
+        return Err(eyre!(
+            "--system writes to /etc and /usr/local/bin; run it as root (sudo ssh_clipboard install-daemon --system)"
+        ));
+    }
+    Ok(())
+}
+
+pub(super) fn print_dry_run(exe: &Path, install: &SystemInstall) -> Result<()> {
+    println!(
+        "dry-run: would copy {} to {}",
+        exe.display(),
+        install.bin_path.display()
+    );
+    println!(
+        "dry-run: would write unit file to {}",
+        install.unit_path().display()
+    );
+    if install.socket_contents.is_some() {
+        println!(
+            "dry-run: would write socket unit to {}",
+            install.socket_path().display()
+        );
+    }
+    println!("dry-run: would write {SYSTEM_CONFIG_PATH} if it is missing or --policy changes it");
+    println!(
+        "dry-run: would run `systemctl --global enable {}`",
+        install.enabled_unit()
+    );
+    println!();
+    println!("unit file contents:\n{}", install.unit_contents);
+    if let Some(contents) = &install.socket_contents {
+        println!("socket unit contents:\n{contents}");
+    }
+    let config = ServerConfig {
+        policy: install.policy.clone(),
+        ..ServerConfig::default()
+    };
+    println!("{SYSTEM_CONFIG_PATH} contents:\n{}", config.render()?);
+    Ok(())
+}
+
+pub(super) fn install(exe: &Path, install: &SystemInstall, force: bool) -> Result<()> {
+    install_binary(exe, &install.bin_path)?;
+    write_file(&install.unit_path(), &install.unit_contents, force)?;
+    if let Some(contents) = &install.socket_contents {
+        write_file(&install.socket_path(), contents, force)?;
+        // Users' managers must not also start the service at login; it would hold
+        // the socket path.
+        run_systemctl_global(&["disable", SERVICE_UNIT])?;
+    }
+    write_config(install.policy.as_deref())?;
+    run_systemctl_global(&["enable", install.enabled_unit()])?;
+    Ok(())
+}
+
+/// Copies rather than links: the extracted release may sit in a home directory
+/// other users cannot read.
+fn install_binary(exe: &Path, bin_path: &Path) -> Result<()> {
+    if super::is_executable_itself(exe, bin_path) {
+        return Ok(());
+    }
+    if let Some(parent) = bin_path.parent() {
+        std::fs::create_dir_all(parent)
+            .wrap_err_with(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut staged = bin_path.as_os_str().to_owned();
+    staged.push(".new");
+    let staged = PathBuf::from(staged);
+    std::fs::copy(exe, &staged)
+        .wrap_err_with(|| format!("failed to copy to {}", staged.display()))?;
+    std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::rename(&staged, bin_path)
+        .wrap_err_with(|| format!("failed to install {}", bin_path.display()))?;
+    Ok(())
+}
+
+fn write_file(path: &Path, contents: &str, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        return Err(eyre!(
+            "{} already exists; use --force to overwrite",
+            path.display()
+        ));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .wrap_err_with(|| format!("failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, contents)
+        .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o644))?;
+    Ok(())
+}
+
+/// Keeps an admin's other settings; only `policy` changes, and only when
+/// `--policy` asks for it.
+fn write_config(policy: Option<&Path>) -> Result<()> {
+    let path = Path::new(SYSTEM_CONFIG_PATH);
+    let mut config = ServerConfig::load_from(path)?;
+    if path.exists() && (policy.is_none() || config.policy.as_deref() == policy) {
+        return Ok(());
+    }
+    if let Some(policy) = policy {
+        config.policy = Some(policy.to_path_buf());
+    }
+    write_file(path, &config.render()?, true)
+}
+
+fn run_systemctl_global(args: &[&str]) -> Result<()> {
+    let output = Command::new("systemctl")
+        .arg("--global")
+        .args(args)
+        .output()
+        .wrap_err("failed to spawn systemctl")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if args.first() == Some(&"disable")
+            && (stderr.contains("not loaded") || stderr.contains("does not exist"))
+        {
+            return Ok(());
+        }
+        return Err(eyre!("systemctl --global failed: {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+pub(super) fn print_success(install: &SystemInstall) {
+    println!("installed for all users:");
+    println!("- binary: {}", install.bin_path.display());
+    println!("- unit: {}", install.unit_path().display());
+    if install.socket_contents.is_some() {
+        println!("- socket unit: {}", install.socket_path().display());
+    }
+    println!("- defaults: {SYSTEM_CONFIG_PATH}");
+    println!();
+    println!("each user's daemon starts at their next login. Users already logged in can run:");
+    println!(
+        "  systemctl --user daemon-reload && systemctl --user start {}",
+        install.enabled_unit()
+    );
+    println!(
+        "to opt out: systemctl --user mask {}",
+        install.enabled_unit()
+    );
+    println!("services outside a login session need: loginctl enable-linger <user>");
+}
+
+pub(super) fn print_dry_run_uninstall(bin_path: &Path) {
+    for unit in [SOCKET_UNIT, SERVICE_UNIT] {
+        println!("dry-run: would run `systemctl --global disable {unit}`");
+        println!(
+            "dry-run: would remove {}",
+            Path::new(GLOBAL_UNIT_DIR).join(unit).display()
+        );
+    }
+    println!(
+        "dry-run: would remove {} if it is this binary",
+        bin_path.display()
+    );
+    println!("dry-run: would keep {SYSTEM_CONFIG_PATH}");
+}
+
+pub(super) fn uninstall(exe: &Path, bin_path: &Path) -> Result<()> {
+    for unit in [SOCKET_UNIT, SERVICE_UNIT] {
+        let path = Path::new(GLOBAL_UNIT_DIR).join(unit);
+        if path.exists() {
+            run_systemctl_global(&["disable", unit])?;
+            std::fs::remove_file(&path)
+                .wrap_err_with(|| format!("failed to remove {}", path.display()))?;
+        }
+    }
+    if is_copy_of(exe, bin_path) {
+        std::fs::remove_file(bin_path)
+            .wrap_err_with(|| format!("failed to remove {}", bin_path.display()))?;
+    }
+    Ok(())
+}
+
+/// Whether `path` is the running executable or holds the same bytes, i.e. was
+/// installed from it.
+fn is_copy_of(exe: &Path, path: &Path) -> bool {
+    if super::is_executable_itself(exe, path) {
+        return true;
+    }
+    match (std::fs::read(exe), std::fs::read(path)) {
+        (Ok(exe), Ok(installed)) => exe == installed,
+        _ => false,
+    }
+}
+
+pub(super) fn print_uninstall_success(bin_path: &Path) {
+    println!("removed:");
+    println!("- units: {GLOBAL_UNIT_DIR}/{SERVICE_UNIT}, {GLOBAL_UNIT_DIR}/{SOCKET_UNIT}");
+    println!("- binary: {}", bin_path.display());
+    println!();
+    println!("kept {SYSTEM_CONFIG_PATH}; running daemons stop at each user's logout, or now with:");
+    println!("  systemctl --user stop {SERVICE_UNIT}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn installed_copy_is_recognized() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("ssh_clipboard");
+        std::fs::write(&exe, b"binary").unwrap();
+        std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let exe = std::fs::canonicalize(exe).unwrap();
+        let bin_path = dir.path().join("bin").join("ssh_clipboard");
+
+        install_binary(&exe, &bin_path).unwrap();
+        let mode = std::fs::metadata(&bin_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert!(is_copy_of(&exe, &bin_path));
+        assert!(is_copy_of(&exe, &exe));
+
+        std::fs::write(&bin_path, b"other").unwrap();
+        assert!(!is_copy_of(&exe, &bin_path));
+    }
+}
@@ -0,0 +1,349 @@
+use crate::cli::{DoctorArgs, InstallServerArgs};
+use crate::client::ssh::{SshConfig, resolve_target_and_port};
+use eyre::{Result, WrapErr, eyre};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::{Duration, timeout};
+
+/// Where the binary goes on the server, expanded by the remote shell.
+const REMOTE_BIN: &str = "$HOME/.local/bin/ssh_clipboard";
+const REMOTE_UPLOAD: &str = "$HOME/.local/bin/.ssh_clipboard.upload";
+
+/// The server's OS and CPU, normalized to Rust's `std::env::consts` names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RemotePlatform {
+    os: String,
+    arch: String,
+    installed: bool,
+}
+
+pub async fn run(args: InstallServerArgs) -> Result<()> {
+    let timeout_ms = args.timeout_ms.max(1);
+    let ssh = SshConfig {
+        target: args.target.unwrap_or_default(),
+        port: args.port,
+        user: args.user,
+        host: args.host,
+        identity_file: args.identity_file,
+        ssh_options: args.ssh_option,
+        ssh_bin: args.ssh_bin,
+    };
+    let (target, _) = resolve_target_and_port(&ssh);
+    if target.trim().is_empty() {
+        return crate::cli::exit::exit_with_code(2, "missing SSH target (use --target or --host)");
+    }
+
+    let probe = match ssh_output(&ssh, &probe_script(), timeout_ms).await {
+        Ok(output) => output,
+        Err(err) => return crate::cli::exit::exit_with_code(5, &format!("{err:#}")),
+    };
+    let remote = parse_probe(&probe)?;
+    if remote.os != "linux" {
+        return Err(eyre!(
+            "{target} runs {}; the server side of ssh_clipboard is Linux-only",
+            remote.os
+        ));
+    }
+    if remote.installed && !args.force {
+        return Err(eyre!(
+            "{target} already has ~/.local/bin/ssh_clipboard; use --force to replace it"
+        ));
+    }
+
+    let binary = match args.binary {
+        Some(path) => path,
+        None => std::env::current_exe().wrap_err("failed to resolve current executable")?,
+    };
+    let contents =
+        std::fs::read(&binary).wrap_err_with(|| format!("failed to read {}", binary.display()))?;
+    check_binary_arch(&binary, &contents, &remote.arch)?;
+
+    let install = install_daemon_script(args.force);
+    if args.dry_run {
+        println!("dry-run: {target} is {}/{}", remote.os, remote.arch);
+        println!(
+            "dry-run: would upload {} ({}) to {target}:~/.local/bin/ssh_clipboard",
+            binary.display(),
+            super::humanize_bytes(contents.len() as u64)
+        );
+        println!("dry-run: would run `{}` on {target}", install);
+        println!("dry-run: would run `ssh_clipboard doctor --target {target}`");
+        return Ok(());
+    }
+
+    println!(
+        "uploading {} ({}) to {target}:~/.local/bin/ssh_clipboard",
+        binary.display(),
+        super::humanize_bytes(contents.len() as u64)
+    );
+    if let Err(err) = ssh_write(&ssh, &upload_script(), &contents).await {
+        return crate::cli::exit::exit_with_code(5, &format!("{err:#}"));
+    }
+
+    println!("running install-daemon on {target}");
+    let status = ssh_command(&ssh, &install)
+        .status()
+        .await
+        .wrap_err("failed to run ssh")?;
+    if !status.success() {
+        return Err(eyre!("remote install-daemon exited with {status}"));
+    }
+
+    // `ssh host ssh_clipboard proxy` resolves the binary through the remote PATH,
+    // which often lacks ~/.local/bin outside login shells.
+    match ssh_output(&ssh, "command -v ssh_clipboard", timeout_ms).await {
+        Ok(found) if !found.trim().is_empty() => {}
+        _ => print_path_hint(&target),
+    }
+
+    println!();
+    super::doctor::run(DoctorArgs {
+        target: Some(ssh.target),
+        host: ssh.host,
+        user: ssh.user,
+        port: ssh.port,
+        identity_file: ssh.identity_file,
+        ssh_option: ssh.ssh_options,
+        ssh_bin: ssh.ssh_bin,
+        timeout_ms,
+    })
+    .await
+}
+
+/// Remote commands run under `sh -c` so the server's login shell does not matter.
+pub(super) fn sh(script: &str) -> String {
+    format!("sh -c '{script}'")
+}
+
+fn probe_script() -> String {
+    sh(&format!(
+        "uname -sm && if [ -e \"{REMOTE_BIN}\" ]; then echo installed; fi"
+    ))
+}
+
+/// Streams the binary into a temporary file and renames it into place, so a
+/// running daemon keeps its executable until it restarts.
+fn upload_script() -> String {
+    sh(&format!(
+        "mkdir -p \"$HOME/.local/bin\" && cat > \"{REMOTE_UPLOAD}\" && chmod 755 \"{REMOTE_UPLOAD}\" && mv -f \"{REMOTE_UPLOAD}\" \"{REMOTE_BIN}\""
+    ))
+}
+
+fn install_daemon_script(force: bool) -> String {
+    let force = if force { " --force" } else { "" };
+    sh(&format!(
+        "\"{REMOTE_BIN}\" install-daemon --no-sudo --bin-link \"{REMOTE_BIN}\"{force}"
+    ))
+}
+
+fn parse_probe(output: &str) -> Result<RemotePlatform> {
+    let mut lines = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    let uname = lines
+        .next()
+        .ok_or_else(|| eyre!("`uname -sm` printed nothing on the server"))?;
+    let (os, arch) = uname
+        .split_once(' ')
+        .ok_or_else(|| eyre!("unexpected `uname -sm` output: {uname}"))?;
+    Ok(RemotePlatform {
+        os: normalize_os(os),
+        arch: normalize_arch(arch.trim()),
+        installed: lines.any(|line| line == "installed"),
+    })
+}
+
+fn normalize_os(uname_s: &str) -> String {
+    match uname_s {
+        "Darwin" => "macos".to_string(),
+        other => other.to_ascii_lowercase(),
+    }
+}
+
+fn normalize_arch(uname_m: &str) -> String {
+    match uname_m {
+        "x86_64" | "amd64" => "x86_64",
+        "aarch64" | "arm64" => "aarch64",
+        "armv6l" | "armv7l" | "armhf" => "arm",
+        "i386" | "i486" | "i586" | "i686" => "x86",
+        "ppc64" | "ppc64le" => "powerpc64",
+        other => other,
+    }
+    .to_string()
+}
+
+/// The CPU an ELF executable was built for, or `None` if `contents` is not ELF.
+fn elf_arch(contents: &[u8]) -> Option<&'static str> {
+    if contents.len() < 20 || !contents.starts_with(b"\x7fELF") {
+        return None;
+    }
+    let machine = match contents[5] {
+        2 => u16::from_be_bytes([contents[18], contents[19]]),
+        _ => u16::from_le_bytes([contents[18], contents[19]]),
+    };
+    Some(match machine {
+        3 => "x86",
+        21 => "powerpc64",
+        22 => "s390x",
+        40 => "arm",
+        62 => "x86_64",
+        183 => "aarch64",
+        243 => "riscv64",
+        _ => "unknown",
+    })
+}
+
+fn check_binary_arch(path: &Path, contents: &[u8], remote_arch: &str) -> Result<()> {
+    let hint = format!(
+        "pass `--binary <path>` to a Linux {remote_arch} ssh_clipboard build (the linux-server release artifact)"
+    );
+    match elf_arch(contents) {
+        Some(arch) if arch == remote_arch => Ok(()),
+        Some(arch) => Err(eyre!(
+            "{} is built for {arch} but the server is {remote_arch}; {hint}",
+            path.display()
+        )),
+        None => Err(eyre!(
+            "{} is not a Linux executable; {hint}",
+            path.display()
+        )),
+    }
+}
+
+fn ssh_command(config: &SshConfig, remote: &str) -> Command {
+    let (target, port) = resolve_target_and_port(config);
+    let ssh_bin = config
+        .ssh_bin
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("ssh"));
+    let mut cmd = Command::new(ssh_bin);
+    cmd.arg("-T");
+    if let Some(port) = port {
+        cmd.arg("-p").arg(port.to_string());
+    }
+    if let Some(identity_file) = &config.identity_file {
+        cmd.arg("-i").arg(identity_file);
+    }
+    for opt in &config.ssh_options {
+        cmd.arg("-o").arg(opt);
+    }
+    cmd.arg(target).arg(remote);
+    cmd
+}
+
+async fn ssh_output(config: &SshConfig, remote: &str, timeout_ms: u64) -> Result<String> {
+    let output = timeout(
+        Duration::from_millis(timeout_ms),
+        ssh_command(config, remote).stdin(Stdio::null()).output(),
+    )
+    .await
+    .map_err(|_| eyre!("ssh timed out after {timeout_ms}ms"))?
+    .map_err(|err| eyre!("failed to run ssh: {err}"))?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "ssh exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs `remote` with `contents` on its stdin.
+async fn ssh_write(config: &SshConfig, remote: &str, contents: &[u8]) -> Result<()> {
+    ssh_exchange(config, remote, contents).await.map(drop)
+}
+
+/// Runs `remote` with `contents` on its stdin and returns its stdout.
+pub(super) async fn ssh_exchange(
+    config: &SshConfig,
+    remote: &str,
+    contents: &[u8],
+) -> Result<String> {
+    let mut child = ssh_command(config, remote)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| eyre!("failed to spawn ssh: {err}"))?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| eyre!("ssh stdin unavailable"))?;
+    let write = stdin.write_all(contents).await;
+    drop(stdin);
+    let output = child
+        .wait_with_output()
+        .await
+        .wrap_err("failed to wait for ssh")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "ssh exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    write.wrap_err("failed to write to ssh stdin")?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn print_path_hint(target: &str) {
+    println!();
+    println!("note:");
+    println!("  `ssh_clipboard` is not on PATH for non-interactive SSH sessions on {target}.");
+    println!("  add this near the top of ~/.bashrc there (before any interactive-only return):");
+    println!("    export PATH=\"$HOME/.local/bin:$PATH\"");
+    println!(
+        "  or, with sudo: sudo ln -sf ~/.local/bin/ssh_clipboard /usr/local/bin/ssh_clipboard"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_output_is_normalized() {
+        let remote = parse_probe("Linux aarch64\n").unwrap();
+        assert_eq!(
+            remote,
+            RemotePlatform {
+                os: "linux".to_string(),
+                arch: "aarch64".to_string(),
+                installed: false,
+            }
+        );
+        let remote = parse_probe("Linux amd64\ninstalled\n").unwrap();
+        assert_eq!(remote.arch, "x86_64");
+        assert!(remote.installed);
+        assert_eq!(parse_probe("Darwin arm64").unwrap().os, "macos");
+        assert!(parse_probe("").is_err());
+    }
+
+    #[test]
+    fn binary_arch_must_match_the_server() {
+        let mut header = vec![0u8; 64];
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[5] = 1;
+        header[18..20].copy_from_slice(&183u16.to_le_bytes());
+        assert_eq!(elf_arch(&header), Some("aarch64"));
+        assert!(check_binary_arch(Path::new("bin"), &header, "aarch64").is_ok());
+
+        let err = check_binary_arch(Path::new("bin"), &header, "x86_64").unwrap_err();
+        assert!(err.to_string().contains("built for aarch64"));
+        assert!(check_binary_arch(Path::new("bin"), b"MZ\x90\x00", "x86_64").is_err());
+    }
+
+    #[test]
+    fn remote_scripts_stay_inside_home() {
+        assert!(upload_script().starts_with("sh -c '"));
+        assert!(upload_script().ends_with("\"$HOME/.local/bin/ssh_clipboard\"'"));
+        assert_eq!(
+            install_daemon_script(true),
+            "sh -c '\"$HOME/.local/bin/ssh_clipboard\" install-daemon --no-sudo --bin-link \"$HOME/.local/bin/ssh_clipboard\" --force'"
+        );
+    }
+}
@@ -5,13 +5,23 @@ use eyre::WrapErr;
 use std::path::PathBuf;
 use tracing_subscriber::EnvFilter;
 
+use crate::client::backend::BackendKind;
+use crate::client::clipboard::{PullSelection, Selection};
+use crate::client::defaults::ClientDefaults;
+use crate::client::image::{DEFAULT_JPEG_QUALITY, ImageEncoding};
 use crate::client::ssh::SshConfig;
-use crate::client::transport::ClientConfig;
-use crate::protocol::{DEFAULT_MAX_SIZE, ErrorCode, Response, ResponseKind};
+use crate::client::transport::{
+    ClientConfig, DEFAULT_RETRY_ATTEMPTS, DEFAULT_RETRY_BACKOFF_MS, DEFAULT_TIMEOUT_MS, RetryPolicy,
+};
+use crate::client_actions::secrets::{DEFAULT_ENTROPY_THRESHOLD, SecretAction};
+use crate::client_actions::transform::{NewlineMode, Transform};
+use crate::protocol::{DEFAULT_MAX_SIZE, Origin, Response, ResponseKind};
 use time::{Duration, OffsetDateTime};
 
+#[cfg(target_os = "linux")]
+mod audit;
 mod doctor;
-mod exit;
+pub(crate) mod exit;
 #[cfg(all(
     feature = "agent",
     any(target_os = "windows", target_os = "macos", target_os = "linux")
@@ -19,14 +29,23 @@ mod exit;
 mod install_client;
 #[cfg(target_os = "linux")]
 mod install_daemon;
+mod install_server;
 mod peek;
 mod pull;
 mod push;
+#[cfg(target_os = "linux")]
+mod server_config;
 #[cfg(all(
     feature = "agent",
     any(target_os = "windows", target_os = "macos", target_os = "linux")
 ))]
 mod setup_agent;
+#[cfg(all(
+    feature = "agent",
+    any(target_os = "windows", target_os = "macos", target_os = "linux")
+))]
+mod setup_key;
+mod status;
 
 #[cfg(all(
     feature = "agent",
@@ -46,7 +65,9 @@ enum Commands {
     Push(PushArgs),
     Pull(PullArgs),
     Peek(PeekArgs),
+    Status(StatusArgs),
     Doctor(DoctorArgs),
+    InstallServer(InstallServerArgs),
     #[cfg(target_os = "linux")]
     Daemon(DaemonArgs),
     #[cfg(target_os = "linux")]
@@ -55,6 +76,10 @@ enum Commands {
     InstallDaemon(InstallDaemonArgs),
     #[cfg(target_os = "linux")]
     UninstallDaemon(UninstallDaemonArgs),
+    #[cfg(target_os = "linux")]
+    Audit(AuditArgs),
+    #[cfg(target_os = "linux")]
+    ServerConfig(ServerConfigArgs),
     #[cfg(all(
         feature = "agent",
         any(target_os = "windows", target_os = "macos", target_os = "linux")
@@ -85,6 +110,11 @@ enum Commands {
         any(target_os = "windows", target_os = "macos", target_os = "linux")
     ))]
     SetupAgent(SetupAgentArgs),
+    #[cfg(all(
+        feature = "agent",
+        any(target_os = "windows", target_os = "macos", target_os = "linux")
+    ))]
+    SetupKey(SetupKeyArgs),
 }
 
 #[derive(Args, Clone)]
@@ -105,14 +135,50 @@ pub struct PushArgs {
     pub ssh_bin: Option<PathBuf>,
     #[arg(long, default_value_t = DEFAULT_MAX_SIZE)]
     pub max_size: usize,
-    #[arg(long, default_value_t = 7000)]
-    pub timeout_ms: u64,
     #[arg(long)]
+    pub timeout_ms: Option<u64>,
+    #[arg(long, conflicts_with = "image")]
     pub stdin: bool,
     #[arg(long)]
+    pub image: Option<PathBuf>,
+    #[arg(long)]
     pub strict_frames: bool,
     #[arg(long, default_value_t = 8192)]
     pub resync_max_bytes: usize,
+    #[arg(long, default_value_t = DEFAULT_RETRY_ATTEMPTS)]
+    pub retry_attempts: u32,
+    #[arg(long, default_value_t = DEFAULT_RETRY_BACKOFF_MS)]
+    pub retry_backoff_ms: u64,
+    #[arg(long, value_enum, default_value_t = BackendKind::Auto)]
+    pub clipboard_backend: BackendKind,
+    #[arg(long)]
+    pub clipboard_read_cmd: Option<String>,
+    #[arg(long)]
+    pub clipboard_read_image_cmd: Option<String>,
+    #[arg(long, value_enum, default_value_t = Selection::Clipboard)]
+    pub selection: Selection,
+    #[arg(long, value_enum, default_value_t = ImageEncoding::Png)]
+    pub image_format: ImageEncoding,
+    #[arg(long, default_value_t = DEFAULT_JPEG_QUALITY)]
+    pub image_quality: u8,
+    #[arg(long)]
+    pub max_image_dimension: Option<u32>,
+    #[arg(long)]
+    pub transcode_threshold: Option<usize>,
+    #[arg(long)]
+    pub transform: Vec<Transform>,
+    #[arg(long, value_enum, default_value_t = SecretAction::Warn)]
+    pub secrets: SecretAction,
+    #[arg(long)]
+    pub allow_secrets: bool,
+    #[arg(long)]
+    pub secret_rules: Option<PathBuf>,
+    #[arg(long, default_value_t = DEFAULT_ENTROPY_THRESHOLD)]
+    pub secret_entropy: f64,
+    #[arg(long, conflicts_with = "no_origin")]
+    pub label: Option<String>,
+    #[arg(long)]
+    pub no_origin: bool,
 }
 
 #[derive(Args, Clone)]
@@ -133,8 +199,8 @@ pub struct PullArgs {
     pub ssh_bin: Option<PathBuf>,
     #[arg(long, default_value_t = DEFAULT_MAX_SIZE)]
     pub max_size: usize,
-    #[arg(long, default_value_t = 7000)]
-    pub timeout_ms: u64,
+    #[arg(long)]
+    pub timeout_ms: Option<u64>,
     #[arg(long)]
     pub stdout: bool,
     #[arg(long)]
@@ -149,6 +215,26 @@ pub struct PullArgs {
     pub strict_frames: bool,
     #[arg(long, default_value_t = 8192)]
     pub resync_max_bytes: usize,
+    #[arg(long, default_value_t = DEFAULT_RETRY_ATTEMPTS)]
+    pub retry_attempts: u32,
+    #[arg(long, default_value_t = DEFAULT_RETRY_BACKOFF_MS)]
+    pub retry_backoff_ms: u64,
+    #[arg(long, value_enum, default_value_t = BackendKind::Auto)]
+    pub clipboard_backend: BackendKind,
+    #[arg(long)]
+    pub clipboard_write_cmd: Option<String>,
+    #[arg(long)]
+    pub clipboard_write_image_cmd: Option<String>,
+    #[arg(long, value_enum, default_value_t = PullSelection::Clipboard)]
+    pub selection: PullSelection,
+    #[arg(long, value_enum)]
+    pub image_format: Option<ImageEncoding>,
+    #[arg(long, default_value_t = DEFAULT_JPEG_QUALITY)]
+    pub image_quality: u8,
+    #[arg(long)]
+    pub transform: Vec<Transform>,
+    #[arg(long, value_enum, default_value_t = NewlineMode::Always)]
+    pub newline: NewlineMode,
 }
 
 #[derive(Args, Clone)]
@@ -169,14 +255,56 @@ pub struct PeekArgs {
     pub ssh_bin: Option<PathBuf>,
     #[arg(long, default_value_t = DEFAULT_MAX_SIZE)]
     pub max_size: usize,
-    #[arg(long, default_value_t = 7000)]
-    pub timeout_ms: u64,
+    #[arg(long)]
+    pub timeout_ms: Option<u64>,
+    #[arg(long)]
+    pub json: bool,
+    #[arg(long)]
+    pub strict_frames: bool,
+    #[arg(long, default_value_t = 8192)]
+    pub resync_max_bytes: usize,
+    #[arg(long, default_value_t = DEFAULT_RETRY_ATTEMPTS)]
+    pub retry_attempts: u32,
+    #[arg(long, default_value_t = DEFAULT_RETRY_BACKOFF_MS)]
+    pub retry_backoff_ms: u64,
+}
+
+#[derive(Args, Clone)]
+pub struct StatusArgs {
+    #[arg(long)]
+    pub target: Option<String>,
+    #[arg(long)]
+    pub host: Option<String>,
+    #[arg(long)]
+    pub user: Option<String>,
+    #[arg(long)]
+    pub port: Option<u16>,
+    #[arg(long)]
+    pub identity_file: Option<PathBuf>,
+    #[arg(long)]
+    pub ssh_option: Vec<String>,
+    #[arg(long)]
+    pub ssh_bin: Option<PathBuf>,
+    #[arg(long, default_value_t = DEFAULT_MAX_SIZE)]
+    pub max_size: usize,
+    #[arg(long)]
+    pub timeout_ms: Option<u64>,
     #[arg(long)]
     pub json: bool,
     #[arg(long)]
     pub strict_frames: bool,
     #[arg(long, default_value_t = 8192)]
     pub resync_max_bytes: usize,
+    #[arg(long, default_value_t = DEFAULT_RETRY_ATTEMPTS)]
+    pub retry_attempts: u32,
+    #[arg(long, default_value_t = DEFAULT_RETRY_BACKOFF_MS)]
+    pub retry_backoff_ms: u64,
+    #[cfg(target_os = "linux")]
+    #[arg(long, conflicts_with_all = ["target", "host"])]
+    pub local: bool,
+    #[cfg(target_os = "linux")]
+    #[arg(long, requires = "local")]
+    pub socket_path: Option<PathBuf>,
 }
 
 #[derive(Args, Clone)]
@@ -199,15 +327,122 @@ pub struct DoctorArgs {
     pub timeout_ms: u64,
 }
 
+#[derive(Args, Clone)]
+pub struct InstallServerArgs {
+    #[arg(long)]
+    pub target: Option<String>,
+    #[arg(long)]
+    pub host: Option<String>,
+    #[arg(long)]
+    pub user: Option<String>,
+    #[arg(long)]
+    pub port: Option<u16>,
+    #[arg(long)]
+    pub identity_file: Option<PathBuf>,
+    #[arg(long)]
+    pub ssh_option: Vec<String>,
+    #[arg(long)]
+    pub ssh_bin: Option<PathBuf>,
+    #[arg(long)]
+    pub binary: Option<PathBuf>,
+    #[arg(long)]
+    pub dry_run: bool,
+    #[arg(long)]
+    pub force: bool,
+    #[arg(long, default_value_t = 7000)]
+    pub timeout_ms: u64,
+}
+
 #[cfg(target_os = "linux")]
 #[derive(Args, Clone)]
 pub struct DaemonArgs {
     #[arg(long)]
     pub socket_path: Option<PathBuf>,
-    #[arg(long, default_value_t = DEFAULT_MAX_SIZE)]
-    pub max_size: usize,
-    #[arg(long, default_value_t = 7000)]
-    pub io_timeout_ms: u64,
+    #[arg(long)]
+    pub max_size: Option<usize>,
+    #[arg(long)]
+    pub io_timeout_ms: Option<u64>,
+    #[arg(long)]
+    pub policy: Option<PathBuf>,
+    #[arg(long, requires = "socket_path")]
+    pub shared: bool,
+    #[arg(long, requires = "shared")]
+    pub grant: Vec<crate::daemon::Grant>,
+    #[arg(long)]
+    pub audit: bool,
+    #[arg(long)]
+    pub audit_path: Option<PathBuf>,
+    #[arg(long, default_value_t = crate::daemon::DEFAULT_AUDIT_MAX_BYTES)]
+    pub audit_max_bytes: u64,
+    #[arg(long)]
+    pub metrics_listen: Option<crate::daemon::MetricsListen>,
+    #[arg(long, default_value_t = crate::daemon::DEFAULT_DRAIN_TIMEOUT_MS)]
+    pub drain_timeout_ms: u64,
+    #[arg(long)]
+    pub idle_timeout_secs: Option<u64>,
+    #[cfg(feature = "server-clipboard")]
+    #[arg(long)]
+    pub mirror_clipboard: bool,
+    #[cfg(feature = "server-clipboard")]
+    #[arg(long, default_value_t = 500)]
+    pub mirror_interval_ms: u64,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Args, Clone)]
+pub struct AuditArgs {
+    #[command(subcommand)]
+    pub command: AuditCommands,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Subcommand, Clone)]
+pub enum AuditCommands {
+    Tail(AuditTailArgs),
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Args, Clone)]
+pub struct AuditTailArgs {
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+    #[arg(short = 'n', long, default_value_t = 20)]
+    pub lines: usize,
+    #[arg(short, long)]
+    pub follow: bool,
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Args, Clone)]
+pub struct ServerConfigArgs {
+    #[command(subcommand)]
+    pub command: ServerConfigCommands,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Subcommand, Clone)]
+pub enum ServerConfigCommands {
+    Path,
+    Show {
+        #[arg(long)]
+        json: bool,
+    },
+    Validate {
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+    Set(ServerConfigSetArgs),
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Args, Clone)]
+pub struct ServerConfigSetArgs {
+    pub key: String,
+    pub value: Option<String>,
+    #[arg(long)]
+    pub system: bool,
 }
 
 #[cfg(target_os = "linux")]
@@ -215,12 +450,14 @@ pub struct DaemonArgs {
 pub struct ProxyArgs {
     #[arg(long)]
     pub socket_path: Option<PathBuf>,
-    #[arg(long, default_value_t = DEFAULT_MAX_SIZE)]
-    pub max_size: usize,
-    #[arg(long, default_value_t = 7000)]
-    pub io_timeout_ms: u64,
+    #[arg(long)]
+    pub max_size: Option<usize>,
+    #[arg(long)]
+    pub io_timeout_ms: Option<u64>,
     #[arg(long)]
     pub autostart_daemon: bool,
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub allow: Vec<crate::proxy::ProxyOperation>,
 }
 
 #[cfg(target_os = "linux")]
@@ -232,12 +469,24 @@ pub struct InstallDaemonArgs {
     pub force: bool,
     #[arg(long)]
     pub no_sudo: bool,
-    #[arg(long, default_value_t = DEFAULT_MAX_SIZE)]
-    pub max_size: usize,
-    #[arg(long, default_value_t = 7000)]
-    pub io_timeout_ms: u64,
+    #[arg(long)]
+    pub max_size: Option<usize>,
+    #[arg(long)]
+    pub io_timeout_ms: Option<u64>,
     #[arg(long)]
     pub socket_path: Option<PathBuf>,
+    #[arg(long)]
+    pub policy: Option<PathBuf>,
+    #[arg(long)]
+    pub socket_activation: bool,
+    #[arg(long, requires = "socket_activation")]
+    pub idle_timeout_secs: Option<u64>,
+    #[arg(long, value_enum, default_value_t = install_daemon::InitSystem::Auto)]
+    pub init: install_daemon::InitSystem,
+    #[arg(long, default_value = install_daemon::DEFAULT_BIN_LINK)]
+    pub bin_link: PathBuf,
+    #[arg(long, conflicts_with = "no_sudo")]
+    pub system: bool,
 }
 
 #[cfg(target_os = "linux")]
@@ -247,6 +496,12 @@ pub struct UninstallDaemonArgs {
     pub dry_run: bool,
     #[arg(long)]
     pub no_sudo: bool,
+    #[arg(long, value_enum, default_value_t = install_daemon::InitSystem::Auto)]
+    pub init: install_daemon::InitSystem,
+    #[arg(long, default_value = install_daemon::DEFAULT_BIN_LINK)]
+    pub bin_link: PathBuf,
+    #[arg(long, conflicts_with = "no_sudo")]
+    pub system: bool,
 }
 
 #[cfg(all(
@@ -363,6 +618,12 @@ pub struct ConfigSetArgs {
     pub resync_frames: Option<bool>,
     #[arg(long)]
     pub resync_max_bytes: Option<usize>,
+    #[arg(long)]
+    pub retry_attempts: Option<u32>,
+    #[arg(long)]
+    pub retry_backoff_ms: Option<u64>,
+    #[arg(long, value_enum)]
+    pub clipboard_backend: Option<BackendKind>,
 }
 
 #[cfg(all(
@@ -417,6 +678,36 @@ pub struct SetupAgentArgs {
     pub dry_run: bool,
 }
 
+#[cfg(all(
+    feature = "agent",
+    any(target_os = "windows", target_os = "macos", target_os = "linux")
+))]
+#[derive(Args, Clone)]
+pub struct SetupKeyArgs {
+    #[arg(long)]
+    pub target: Option<String>,
+    #[arg(long)]
+    pub port: Option<u16>,
+    #[arg(long)]
+    pub identity_file: Option<PathBuf>,
+    #[arg(long)]
+    pub ssh_option: Vec<String>,
+    #[arg(long)]
+    pub ssh_bin: Option<PathBuf>,
+    #[arg(long)]
+    pub key_path: Option<PathBuf>,
+    #[arg(long, value_delimiter = ',', value_parser = ["get", "set", "peek", "status"])]
+    pub allow: Vec<String>,
+    #[arg(long, default_value = "ssh_clipboard")]
+    pub remote_bin: String,
+    #[arg(long)]
+    pub force: bool,
+    #[arg(long)]
+    pub dry_run: bool,
+    #[arg(long, default_value_t = 7000)]
+    pub timeout_ms: u64,
+}
+
 pub async fn run() -> Result<()> {
     let cli = Cli::parse();
     #[cfg(all(
@@ -436,27 +727,72 @@ pub async fn run() -> Result<()> {
         Commands::Push(args) => push::run(args).await,
         Commands::Pull(args) => pull::run(args).await,
         Commands::Peek(args) => peek::run(args).await,
+        Commands::Status(args) => status::run(args).await,
         Commands::Doctor(args) => doctor::run(args).await,
+        Commands::InstallServer(args) => install_server::run(args).await,
         #[cfg(target_os = "linux")]
         Commands::Daemon(args) => {
-            let socket_path = args
-                .socket_path
-                .unwrap_or(crate::daemon::default_socket_path()?);
-            crate::daemon::run_daemon(socket_path, args.max_size, args.io_timeout_ms)
-                .await
-                .wrap_err("daemon failed")?;
+            let flags = crate::server_config::ServerConfig {
+                socket_path: args.socket_path,
+                max_size: args.max_size,
+                io_timeout_ms: args.io_timeout_ms,
+                policy: args.policy,
+                autostart_daemon: None,
+            };
+            let config = crate::server_config::ServerConfig::load()?.overlay(&flags);
+            let socket_path = match &config.socket_path {
+                Some(path) => path.clone(),
+                None => crate::daemon::default_socket_path()?,
+            };
+            #[cfg(feature = "server-clipboard")]
+            let mirror_interval_ms = args.mirror_clipboard.then_some(args.mirror_interval_ms);
+            #[cfg(not(feature = "server-clipboard"))]
+            let mirror_interval_ms = None;
+            let audit_path = match args.audit_path {
+                Some(path) => Some(path),
+                None if args.audit => Some(crate::daemon::default_audit_path()?),
+                None => None,
+            };
+            crate::daemon::run_daemon(crate::daemon::DaemonConfig {
+                socket_path,
+                max_size: config.effective_max_size(),
+                io_timeout_ms: config.effective_io_timeout_ms(),
+                mirror_interval_ms,
+                policy_path: config.policy.clone(),
+                shared: args.shared,
+                grants: args.grant,
+                audit_path,
+                audit_max_bytes: args.audit_max_bytes,
+                metrics_listen: args.metrics_listen,
+                drain_timeout_ms: args.drain_timeout_ms,
+                idle_timeout_secs: args.idle_timeout_secs,
+                config_flags: Some(flags),
+            })
+            .await
+            .wrap_err("daemon failed")?;
             Ok(())
         }
         #[cfg(target_os = "linux")]
         Commands::Proxy(args) => {
-            let socket_path = args
-                .socket_path
-                .unwrap_or(crate::daemon::default_socket_path()?);
+            let config = crate::server_config::ServerConfig::load()?.overlay(
+                &crate::server_config::ServerConfig {
+                    socket_path: args.socket_path,
+                    max_size: args.max_size,
+                    io_timeout_ms: args.io_timeout_ms,
+                    policy: None,
+                    autostart_daemon: args.autostart_daemon.then_some(true),
+                },
+            );
+            let socket_path = match &config.socket_path {
+                Some(path) => path.clone(),
+                None => crate::daemon::default_socket_path()?,
+            };
             let exit_code = crate::proxy::run_proxy(
                 socket_path,
-                args.max_size,
-                args.io_timeout_ms,
-                args.autostart_daemon,
+                config.effective_max_size(),
+                config.effective_io_timeout_ms(),
+                config.autostart_daemon.unwrap_or(false),
+                &args.allow,
             )
             .await
             .wrap_err("proxy failed")?;
@@ -466,6 +802,10 @@ pub async fn run() -> Result<()> {
         Commands::InstallDaemon(args) => install_daemon::run(args).await,
         #[cfg(target_os = "linux")]
         Commands::UninstallDaemon(args) => install_daemon::run_uninstall(args).await,
+        #[cfg(target_os = "linux")]
+        Commands::Audit(args) => audit::run(args).await,
+        #[cfg(target_os = "linux")]
+        Commands::ServerConfig(args) => server_config::run(args),
         #[cfg(all(
             feature = "agent",
             any(target_os = "windows", target_os = "macos", target_os = "linux")
@@ -496,6 +836,11 @@ pub async fn run() -> Result<()> {
             any(target_os = "windows", target_os = "macos", target_os = "linux")
         ))]
         Commands::SetupAgent(args) => setup_agent::run(args),
+        #[cfg(all(
+            feature = "agent",
+            any(target_os = "windows", target_os = "macos", target_os = "linux")
+        ))]
+        Commands::SetupKey(args) => setup_key::run(args).await,
     }
 }
 
@@ -504,15 +849,12 @@ pub(crate) fn handle_response(response: Response, allow_empty: bool) -> Result<(
         ResponseKind::Ok => Ok(()),
         ResponseKind::Empty if allow_empty => Ok(()),
         ResponseKind::Empty => exit::exit_with_code(2, "no clipboard value set"),
-        ResponseKind::Error { code, message } => match code {
-            ErrorCode::InvalidRequest | ErrorCode::InvalidUtf8 | ErrorCode::VersionMismatch => {
-                exit::exit_with_code(2, &message)
-            }
-            ErrorCode::PayloadTooLarge => exit::exit_with_code(3, &message),
-            ErrorCode::DaemonNotRunning => exit::exit_with_code(4, &message),
-            ErrorCode::Internal => exit::exit_with_code(2, &message),
-        },
-        ResponseKind::Value { .. } | ResponseKind::Meta { .. } => Ok(()),
+        ResponseKind::Error { code, message } => {
+            exit::exit_with_code(exit::client_exit_code(&code), &message)
+        }
+        ResponseKind::Value { .. } | ResponseKind::Meta { .. } | ResponseKind::Status { .. } => {
+            Ok(())
+        }
     }
 }
 
@@ -522,16 +864,29 @@ pub(crate) fn handle_peek_response(response: Response, json: bool) -> Result<()>
             content_type,
             size,
             created_at,
+            author,
+            origin,
         } => {
             if json {
                 let value = serde_json::json!({
                     "content_type": content_type,
                     "size": size,
-                    "created_at": created_at
+                    "created_at": created_at,
+                    "author": author,
+                    "origin": origin
                 });
                 println!("{value}");
             } else {
-                println!("{}", format_peek_output(content_type, *size, *created_at));
+                println!(
+                    "{}",
+                    format_peek_output(
+                        content_type,
+                        *size,
+                        *created_at,
+                        author.as_deref(),
+                        origin.as_ref()
+                    )
+                );
             }
             Ok(())
         }
@@ -540,12 +895,42 @@ pub(crate) fn handle_peek_response(response: Response, json: bool) -> Result<()>
     }
 }
 
-pub(crate) fn format_peek_output(content_type: &str, size: u64, created_at_ms: i64) -> String {
-    format!(
+pub(crate) fn format_peek_output(
+    content_type: &str,
+    size: u64,
+    created_at_ms: i64,
+    author: Option<&str>,
+    origin: Option<&Origin>,
+) -> String {
+    let mut output = format!(
         "Content-Type: {content_type}\nSize: {size} bytes ({human_size})\nCreated: {created}",
         human_size = humanize_bytes(size),
         created = format_created_at(created_at_ms)
-    )
+    );
+    if let Some(author) = author {
+        output.push_str(&format!("\nAuthor: {author}"));
+    }
+    if let Some(origin) = origin {
+        output.push_str(&format!("\nOrigin: {}", format_origin(origin)));
+    }
+    output
+}
+
+/// `alice@laptop (macos) [work]`; parts the client left empty are skipped.
+pub(crate) fn format_origin(origin: &Origin) -> String {
+    let mut out = match (origin.username.is_empty(), origin.hostname.is_empty()) {
+        (false, false) => format!("{}@{}", origin.username, origin.hostname),
+        (true, false) => origin.hostname.clone(),
+        (false, true) => origin.username.clone(),
+        (true, true) => "unknown".to_string(),
+    };
+    if !origin.os.is_empty() {
+        out.push_str(&format!(" ({})", origin.os));
+    }
+    if let Some(label) = &origin.label {
+        out.push_str(&format!(" [{label}]"));
+    }
+    out
 }
 
 fn format_created_at(created_at_ms: i64) -> String {
@@ -627,6 +1012,7 @@ fn humanize_bytes(size: u64) -> String {
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::*;
 
@@ -655,6 +1041,26 @@ mod tests {
         assert!(formatted.contains("in the future"));
     }
 
+    #[test]
+    fn peek_output_shows_origin() {
+        let origin = Origin {
+            hostname: "laptop".to_string(),
+            os: "linux".to_string(),
+            username: "alice".to_string(),
+            label: Some("work".to_string()),
+        };
+        let output = format_peek_output("text/plain", 5, 0, Some("alice"), Some(&origin));
+        assert!(output.ends_with("\nAuthor: alice\nOrigin: alice@laptop (linux) [work]"));
+
+        let bare = Origin {
+            hostname: "laptop".to_string(),
+            os: String::new(),
+            username: String::new(),
+            label: None,
+        };
+        assert_eq!(format_origin(&bare), "laptop");
+    }
+
     #[cfg(all(
         feature = "agent",
         any(target_os = "windows", target_os = "macos", target_os = "linux")
@@ -675,27 +1081,37 @@ pub(crate) struct ClientConfigArgs {
     pub ssh_option: Vec<String>,
     pub ssh_bin: Option<PathBuf>,
     pub max_size: usize,
-    pub timeout_ms: u64,
+    pub timeout_ms: Option<u64>,
     pub strict_frames: bool,
     pub resync_max_bytes: usize,
+    pub retry_attempts: u32,
+    pub retry_backoff_ms: u64,
 }
 
-pub(crate) fn build_client_config(args: ClientConfigArgs) -> ClientConfig {
-    ClientConfig {
-        ssh: SshConfig {
-            target: args.target.unwrap_or_default(),
-            port: args.port,
-            user: args.user,
-            host: args.host,
-            identity_file: args.identity_file,
-            ssh_options: args.ssh_option,
-            ssh_bin: args.ssh_bin,
-        },
+/// Flags first, then the client config file and `SSH_CLIPBOARD_*` variables.
+pub(crate) fn build_client_config(args: ClientConfigArgs) -> Result<ClientConfig> {
+    let defaults = ClientDefaults::load()?;
+    let mut ssh = SshConfig {
+        target: args.target.unwrap_or_default(),
+        port: args.port,
+        user: args.user,
+        host: args.host,
+        identity_file: args.identity_file,
+        ssh_options: args.ssh_option,
+        ssh_bin: args.ssh_bin,
+    };
+    defaults.apply(&mut ssh);
+    Ok(ClientConfig {
+        ssh,
         max_size: args.max_size,
-        timeout_ms: args.timeout_ms,
+        timeout_ms: args
+            .timeout_ms
+            .or(defaults.timeout_ms)
+            .unwrap_or(DEFAULT_TIMEOUT_MS),
         resync_frames: !args.strict_frames,
         resync_max_bytes: args.resync_max_bytes,
-    }
+        retry: RetryPolicy::with_attempts(args.retry_attempts, args.retry_backoff_ms),
+    })
 }
 
 fn init_tracing(agent_mode: bool) -> Result<()> {
@@ -4,9 +4,13 @@ use eyre::Result;
 use std::fs;
 
 use crate::cli::{ClientConfigArgs, PullArgs, build_client_config, handle_peek_response};
+use crate::client::backend::{ClipboardBackendConfig, ClipboardCommands};
+use crate::client::clipboard::{PrimaryHold, PullSelection};
+use crate::client::image;
 use crate::client::transport::{make_request, send_request};
-use crate::client_actions::{PullApplyErrorKind, apply_pull_response_with_system_clipboard};
-use crate::protocol::{CONTENT_TYPE_PNG, CONTENT_TYPE_TEXT, RequestKind, ResponseKind};
+use crate::client_actions::transform;
+use crate::client_actions::{PullApplyErrorKind, apply_pull_response_with_backend};
+use crate::protocol::{CONTENT_TYPE_TEXT, RequestKind, ResponseKind, is_image_content_type};
 
 pub async fn run(args: PullArgs) -> Result<()> {
     if args.stdout && args.output.is_some() {
@@ -15,6 +19,12 @@ pub async fn run(args: PullArgs) -> Result<()> {
     if args.base64 && !args.stdout {
         return crate::cli::exit::exit_with_code(2, "--base64 requires --stdout");
     }
+    if args.image_format.is_some() && args.output.is_none() {
+        return crate::cli::exit::exit_with_code(2, "--image-format requires --output");
+    }
+    if !(1..=100).contains(&args.image_quality) {
+        return crate::cli::exit::exit_with_code(2, "--image-quality must be between 1 and 100");
+    }
 
     let effective_max_size = if args.max_size == 0 {
         crate::protocol::DEFAULT_MAX_SIZE
@@ -22,31 +32,35 @@ pub async fn run(args: PullArgs) -> Result<()> {
         args.max_size
     };
 
+    let config = match build_client_config(client_config_args(&args, effective_max_size)) {
+        Ok(config) => config,
+        Err(err) => return crate::cli::exit::exit_with_code(2, &format!("{err:#}")),
+    };
     if args.peek {
-        let response = match send_request(
-            &build_client_config(client_config_args(&args, effective_max_size)),
-            make_request(RequestKind::PeekMeta),
-        )
-        .await
-        {
+        let response = match send_request(&config, make_request(RequestKind::PeekMeta)).await {
             Ok(response) => response,
             Err(err) => return crate::cli::exit::exit_with_code(5, &err.to_string()),
         };
         return handle_peek_response(response, args.json);
     }
 
-    let response = match send_request(
-        &build_client_config(client_config_args(&args, effective_max_size)),
-        make_request(RequestKind::Get),
-    )
-    .await
-    {
+    let mut response = match send_request(&config, make_request(RequestKind::Get)).await {
         Ok(response) => response,
         Err(err) => return crate::cli::exit::exit_with_code(5, &err.to_string()),
     };
+    transform::apply_to_response(&mut response, &args.transform);
 
     if !args.stdout && args.output.is_none() && !args.base64 {
-        return handle_pull_to_clipboard(response, effective_max_size);
+        let backend = ClipboardBackendConfig {
+            backend: args.clipboard_backend,
+            commands: ClipboardCommands {
+                write_text: args.clipboard_write_cmd.clone(),
+                write_image: args.clipboard_write_image_cmd.clone(),
+                ..ClipboardCommands::default()
+            },
+            primary_hold: PrimaryHold::Wait,
+        };
+        return handle_pull_to_clipboard(response, effective_max_size, &backend, args.selection);
     }
 
     if let ResponseKind::Value { value } = &response.kind {
@@ -58,7 +72,7 @@ pub async fn run(args: PullArgs) -> Result<()> {
                 }
             };
             if args.stdout {
-                println!("{text}");
+                print!("{text}{}", args.newline.terminator(&text));
                 return Ok(());
             }
             if let Some(path) = args.output {
@@ -72,9 +86,22 @@ pub async fn run(args: PullArgs) -> Result<()> {
             }
         }
 
-        if value.content_type == CONTENT_TYPE_PNG {
+        if is_image_content_type(&value.content_type) {
             if let Some(path) = args.output {
-                if let Err(err) = fs::write(&path, &value.data) {
+                let data = match args.image_format {
+                    Some(format) => match image::transcode(
+                        &value.data,
+                        &value.content_type,
+                        format,
+                        args.image_quality,
+                        effective_max_size,
+                    ) {
+                        Ok(data) => data,
+                        Err(err) => return crate::cli::exit::exit_with_code(2, &err.to_string()),
+                    },
+                    None => value.data.clone(),
+                };
+                if let Err(err) = fs::write(&path, data) {
                     return crate::cli::exit::exit_with_code(
                         2,
                         &format!("failed to write output: {err}"),
@@ -121,8 +148,10 @@ pub async fn run(args: PullArgs) -> Result<()> {
 fn handle_pull_to_clipboard(
     response: crate::protocol::Response,
     max_decoded_bytes: usize,
+    backend: &ClipboardBackendConfig,
+    target: PullSelection,
 ) -> Result<()> {
-    match apply_pull_response_with_system_clipboard(response, max_decoded_bytes) {
+    match apply_pull_response_with_backend(response, max_decoded_bytes, backend, target) {
         Ok(()) => Ok(()),
         Err(err) => match err.kind {
             PullApplyErrorKind::Clipboard => crate::cli::exit::exit_with_code(6, &err.message),
@@ -149,5 +178,7 @@ fn client_config_args(args: &PullArgs, max_size: usize) -> ClientConfigArgs {
         timeout_ms: args.timeout_ms,
         strict_frames: args.strict_frames,
         resync_max_bytes: args.resync_max_bytes,
+        retry_attempts: args.retry_attempts,
+        retry_backoff_ms: args.retry_backoff_ms,
     }
 }
@@ -1,10 +1,15 @@
-use eyre::{Result, WrapErr, eyre};
+use eyre::{Result, WrapErr};
+use std::io::Read;
+use std::path::Path;
 use tokio::io::{AsyncReadExt, BufReader};
 
 use crate::cli::{ClientConfigArgs, PushArgs, build_client_config, handle_response};
+use crate::client::backend::{ClipboardBackendConfig, ClipboardCommands};
+use crate::client::image::{self, ImageTranscode};
 use crate::client::transport::{make_request, send_request};
-use crate::client_actions::ClipboardBuildError;
-use crate::protocol::{ClipboardValue, DEFAULT_MAX_SIZE, RequestKind};
+use crate::client_actions::secrets::{self, SecretGuard, SecretGuardConfig};
+use crate::client_actions::{ClipboardBuildError, GuardedValue};
+use crate::protocol::{DEFAULT_MAX_SIZE, RequestKind};
 
 pub async fn run(args: PushArgs) -> Result<()> {
     let effective_max_size = if args.max_size == 0 {
@@ -13,29 +18,55 @@ pub async fn run(args: PushArgs) -> Result<()> {
         args.max_size
     };
 
-    let value = match build_clipboard_value(args.stdin, effective_max_size).await {
-        Ok(value) => value,
+    let action = if args.allow_secrets {
+        args.secrets.allowing()
+    } else {
+        args.secrets
+    };
+    let guard = match SecretGuard::load(&SecretGuardConfig {
+        action,
+        entropy_threshold: args.secret_entropy,
+        rules_file: args.secret_rules.clone(),
+    }) {
+        Ok(guard) => guard,
+        Err(err) => return crate::cli::exit::exit_with_code(2, &format!("{err:#}")),
+    };
+    let GuardedValue {
+        mut value,
+        findings,
+    } = match build_clipboard_value(&args, &guard, effective_max_size).await {
+        Ok(guarded) => guarded,
         Err(err) => return crate::cli::exit::exit_with_code(err.code, &err.message),
     };
+    if let Some(note) = secrets::describe_findings(action, &findings) {
+        eprintln!("warning: {note}");
+    }
+    if value.data.len() > effective_max_size {
+        return crate::cli::exit::exit_with_code(3, "payload too large");
+    }
+    if !args.no_origin {
+        value.origin = Some(crate::client_actions::local_origin(args.label.clone()));
+    }
 
-    let response = match send_request(
-        &build_client_config(ClientConfigArgs {
-            target: args.target,
-            host: args.host,
-            user: args.user,
-            port: args.port,
-            identity_file: args.identity_file,
-            ssh_option: args.ssh_option,
-            ssh_bin: args.ssh_bin,
-            max_size: effective_max_size,
-            timeout_ms: args.timeout_ms,
-            strict_frames: args.strict_frames,
-            resync_max_bytes: args.resync_max_bytes,
-        }),
-        make_request(RequestKind::Set { value }),
-    )
-    .await
-    {
+    let config = match build_client_config(ClientConfigArgs {
+        target: args.target,
+        host: args.host,
+        user: args.user,
+        port: args.port,
+        identity_file: args.identity_file,
+        ssh_option: args.ssh_option,
+        ssh_bin: args.ssh_bin,
+        max_size: effective_max_size,
+        timeout_ms: args.timeout_ms,
+        strict_frames: args.strict_frames,
+        resync_max_bytes: args.resync_max_bytes,
+        retry_attempts: args.retry_attempts,
+        retry_backoff_ms: args.retry_backoff_ms,
+    }) {
+        Ok(config) => config,
+        Err(err) => return crate::cli::exit::exit_with_code(2, &format!("{err:#}")),
+    };
+    let response = match send_request(&config, make_request(RequestKind::Set { value })).await {
         Ok(response) => response,
         Err(err) => return crate::cli::exit::exit_with_code(5, &err.to_string()),
     };
@@ -44,29 +75,111 @@ pub async fn run(args: PushArgs) -> Result<()> {
 }
 
 async fn build_clipboard_value(
-    stdin: bool,
+    args: &PushArgs,
+    guard: &SecretGuard,
     max_size: usize,
-) -> Result<ClipboardValue, ClipboardBuildError> {
-    if stdin {
-        let text = read_stdin_text().await.map_err(|err| ClipboardBuildError {
-            code: 2,
-            message: err.to_string(),
-        })?;
-        return crate::client_actions::build_text_value(text, max_size);
+) -> Result<GuardedValue, ClipboardBuildError> {
+    let transcode = ImageTranscode {
+        format: args.image_format,
+        quality: args.image_quality,
+        max_dimension: args.max_image_dimension,
+        threshold: args.transcode_threshold,
+    };
+    transcode.validate().map_err(|err| ClipboardBuildError {
+        code: 2,
+        message: err.to_string(),
+        blocked: None,
+    })?;
+
+    // Image input may shrink when transcoded, so it is capped at the decode limit
+    // rather than `max_size`.
+    let input_limit = max_size.max(image::MAX_INPUT_DECODED_BYTES);
+
+    if let Some(path) = &args.image {
+        let data = read_image_file(path, input_limit)?;
+        let value = crate::client_actions::build_image_value(data, &transcode, max_size)?;
+        return Ok(GuardedValue {
+            value,
+            findings: Vec::new(),
+        });
+    }
+
+    if args.stdin {
+        let data = read_stdin(input_limit).await?;
+        return crate::client_actions::build_stdin_value(
+            data,
+            &transcode,
+            &args.transform,
+            guard,
+            max_size,
+        );
     }
 
-    crate::client_actions::build_clipboard_value_from_clipboard(max_size)
+    let backend = ClipboardBackendConfig {
+        backend: args.clipboard_backend,
+        commands: ClipboardCommands {
+            read_text: args.clipboard_read_cmd.clone(),
+            read_image: args.clipboard_read_image_cmd.clone(),
+            ..ClipboardCommands::default()
+        },
+        ..ClipboardBackendConfig::default()
+    };
+    crate::client_actions::build_clipboard_value_from_clipboard(
+        &backend,
+        args.selection,
+        &transcode,
+        &args.transform,
+        guard,
+        max_size,
+    )
+}
+
+fn read_image_file(path: &Path, limit: usize) -> Result<Vec<u8>, ClipboardBuildError> {
+    let read_error = |err: std::io::Error| ClipboardBuildError {
+        code: 2,
+        message: format!("failed to read {}: {err}", path.display()),
+        blocked: None,
+    };
+    let file = std::fs::File::open(path).map_err(read_error)?;
+    let mut data = Vec::new();
+    file.take(limit as u64 + 1)
+        .read_to_end(&mut data)
+        .map_err(read_error)?;
+    if data.len() > limit {
+        return Err(too_large_input());
+    }
+    Ok(data)
 }
 
-async fn read_stdin_text() -> Result<String> {
-    let mut reader = BufReader::new(tokio::io::stdin());
-    let mut buffer = String::new();
+async fn read_stdin(limit: usize) -> Result<Vec<u8>, ClipboardBuildError> {
+    let mut reader = BufReader::new(tokio::io::stdin()).take(limit as u64 + 1);
+    let mut buffer = Vec::new();
     reader
-        .read_to_string(&mut buffer)
+        .read_to_end(&mut buffer)
         .await
-        .wrap_err("failed to read stdin")?;
+        .wrap_err("failed to read stdin")
+        .map_err(|err| ClipboardBuildError {
+            code: 2,
+            message: err.to_string(),
+            blocked: None,
+        })?;
     if buffer.is_empty() {
-        return Err(eyre!("stdin was empty"));
+        return Err(ClipboardBuildError {
+            code: 2,
+            message: "stdin was empty".to_string(),
+            blocked: None,
+        });
+    }
+    if buffer.len() > limit {
+        return Err(too_large_input());
     }
     Ok(buffer)
 }
+
+fn too_large_input() -> ClipboardBuildError {
+    ClipboardBuildError {
+        code: 3,
+        message: "input too large".to_string(),
+        blocked: None,
+    }
+}
@@ -0,0 +1,178 @@
+use eyre::{Result, WrapErr, eyre};
+use std::path::{Path, PathBuf};
+
+use crate::cli::{ServerConfigArgs, ServerConfigCommands, ServerConfigSetArgs};
+use crate::daemon::{Policy, default_socket_path};
+use crate::server_config::{
+    ConfigSource, KEYS, SYSTEM_CONFIG_PATH, ServerConfig, user_config_path,
+};
+
+pub fn run(args: ServerConfigArgs) -> Result<()> {
+    match args.command {
+        ServerConfigCommands::Path => {
+            println!("system: {SYSTEM_CONFIG_PATH}");
+            println!("user: {}", user_config_path()?.display());
+            Ok(())
+        }
+        ServerConfigCommands::Show { json } => show(json),
+        ServerConfigCommands::Validate { path } => validate(path),
+        ServerConfigCommands::Set(args) => set(args),
+    }
+}
+
+struct Layers {
+    system: ServerConfig,
+    user: ServerConfig,
+}
+
+impl Layers {
+    fn load() -> Result<Self> {
+        Ok(Self {
+            system: ServerConfig::load_from(Path::new(SYSTEM_CONFIG_PATH))?,
+            user: ServerConfig::load_from(&user_config_path()?)?,
+        })
+    }
+
+    fn source(&self, key: &str) -> ConfigSource {
+        if self.user.is_set(key) {
+            ConfigSource::User
+        } else if self.system.is_set(key) {
+            ConfigSource::System
+        } else {
+            ConfigSource::Default
+        }
+    }
+
+    /// Every key with its value after layering and defaults.
+    fn effective(&self) -> Result<ServerConfig> {
+        let merged = self.system.overlay(&self.user);
+        Ok(ServerConfig {
+            max_size: Some(merged.effective_max_size()),
+            io_timeout_ms: Some(merged.effective_io_timeout_ms()),
+            autostart_daemon: Some(merged.autostart_daemon.unwrap_or(false)),
+            socket_path: Some(match merged.socket_path {
+                Some(path) => path,
+                None => default_socket_path()?,
+            }),
+            policy: merged.policy,
+        })
+    }
+}
+
+fn show(json: bool) -> Result<()> {
+    let layers = Layers::load()?;
+    let effective = layers.effective()?;
+    if json {
+        let sources: serde_json::Map<String, serde_json::Value> = KEYS
+            .iter()
+            .map(|key| (key.to_string(), layers.source(key).to_string().into()))
+            .collect();
+        let value = serde_json::json!({
+            "system_path": SYSTEM_CONFIG_PATH,
+            "user_path": user_config_path()?,
+            "settings": effective,
+            "sources": sources,
+        });
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+    println!("{}", format_show(&layers, &effective)?);
+    Ok(())
+}
+
+fn format_show(layers: &Layers, effective: &ServerConfig) -> Result<String> {
+    let rendered: toml::Table = toml::Table::try_from(effective).map_err(|err| eyre!("{err}"))?;
+    let lines = KEYS
+        .iter()
+        .map(|key| {
+            let value = rendered
+                .get(*key)
+                .map_or_else(|| "(none)".to_string(), |value| value.to_string());
+            format!("{key} = {value}  # {}", layers.source(key))
+        })
+        .collect::<Vec<_>>();
+    Ok(lines.join("\n"))
+}
+
+/// Checks one file, or both layers and the policy they name.
+fn validate(path: Option<PathBuf>) -> Result<()> {
+    let paths = match path {
+        Some(path) => vec![path],
+        None => vec![PathBuf::from(SYSTEM_CONFIG_PATH), user_config_path()?],
+    };
+    let mut merged = ServerConfig::default();
+    for path in &paths {
+        let config = ServerConfig::load_from(path)?;
+        let state = if path.exists() { "ok" } else { "missing" };
+        println!("{}: {state}", path.display());
+        merged = merged.overlay(&config);
+    }
+    if let Some(policy) = &merged.policy {
+        Policy::load(policy)?;
+        println!("{}: ok", policy.display());
+    }
+    Ok(())
+}
+
+fn set(args: ServerConfigSetArgs) -> Result<()> {
+    let path = if args.system {
+        PathBuf::from(SYSTEM_CONFIG_PATH)
+    } else {
+        user_config_path()?
+    };
+    let mut config = ServerConfig::load_from(&path)?;
+    config.set(&args.key, args.value.as_deref())?;
+    if let Some(policy) = config.policy.as_deref().filter(|_| args.key == "policy") {
+        Policy::load(policy)?;
+    }
+    write_config(&path, &config.render()?)?;
+    match &args.value {
+        Some(value) => println!("{}: {} = {value}", path.display(), args.key),
+        None => println!("{}: removed {}", path.display(), args.key),
+    }
+    println!(
+        "reload running daemons with `systemctl --user reload ssh_clipboard.service` (or SIGHUP)"
+    );
+    Ok(())
+}
+
+fn write_config(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .wrap_err_with(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut staged = path.as_os_str().to_owned();
+    staged.push(".new");
+    let staged = PathBuf::from(staged);
+    std::fs::write(&staged, contents)
+        .wrap_err_with(|| format!("failed to write {}", staged.display()))?;
+    std::fs::rename(&staged, path)
+        .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn show_names_the_layer_of_each_key() {
+        let layers = Layers {
+            system: ServerConfig::parse("max_size = 1024\npolicy = \"/etc/p.toml\"\n").unwrap(),
+            user: ServerConfig::parse("max_size = 2048\n").unwrap(),
+        };
+        let effective = ServerConfig {
+            socket_path: Some(PathBuf::from("/run/user/1000/ssh_clipboard/daemon.sock")),
+            ..layers.effective().unwrap()
+        };
+        let output = format_show(&layers, &effective).unwrap();
+        assert_eq!(
+            output,
+            "socket_path = \"/run/user/1000/ssh_clipboard/daemon.sock\"  # default\n\
+             max_size = 2048  # user\n\
+             io_timeout_ms = 7000  # default\n\
+             policy = \"/etc/p.toml\"  # system\n\
+             autostart_daemon = false  # default"
+        );
+    }
+}
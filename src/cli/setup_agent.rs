@@ -29,6 +29,9 @@ pub fn run(args: SetupAgentArgs) -> Result<()> {
         timeout_ms: args.timeout_ms,
         resync_frames: args.resync_frames,
         resync_max_bytes: args.resync_max_bytes,
+        retry_attempts: None,
+        retry_backoff_ms: None,
+        clipboard_backend: None,
     };
     crate::cli::agent::apply_config_set(&mut config, &set_args);
 
@@ -0,0 +1,383 @@
+use crate::cli::SetupKeyArgs;
+use crate::cli::install_client::home_dir;
+use crate::cli::install_server::{sh, ssh_exchange};
+use crate::client::ssh::{SshConfig, resolve_target_and_port};
+use crate::client::transport::{ClientConfig, RetryPolicy, make_request, send_request};
+use crate::protocol::{DEFAULT_MAX_SIZE, ErrorCode, RequestKind, ResponseKind};
+use eyre::{Result, WrapErr, eyre};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const KEY_FILE_NAME: &str = "ssh_clipboard_ed25519";
+const ENTRY_OPTIONS: &str = "no-port-forwarding,no-X11-forwarding,no-agent-forwarding,no-pty";
+const IDENTITIES_ONLY: &str = "IdentitiesOnly=yes";
+const REMOTE_AUTHORIZED_KEYS: &str = "$HOME/.ssh/authorized_keys";
+const REMOTE_STAGED_KEYS: &str = "$HOME/.ssh/authorized_keys.ssh_clipboard";
+/// Printed by `upsert_script` when `authorized_keys` already holds the block.
+const UNCHANGED: &str = "unchanged";
+
+pub async fn run(args: SetupKeyArgs) -> Result<()> {
+    let home = home_dir().ok_or_else(|| eyre!("cannot resolve home directory"))?;
+    let key_path = args.key_path.unwrap_or_else(|| default_key_path(&home));
+
+    let mut config = match crate::agent::load_config() {
+        Ok(config) => config,
+        Err(err) => {
+            let missing = crate::agent::config_path().is_ok_and(|path| !path.exists());
+            if missing {
+                crate::agent::default_agent_config()
+            } else {
+                return Err(err);
+            }
+        }
+    };
+    let (target, port) = match args.target {
+        Some(target) => (target, args.port),
+        None if !config.target.trim().is_empty() => {
+            (config.target.clone(), args.port.or(config.port))
+        }
+        None => {
+            return Err(eyre!(
+                "missing SSH target; pass --target user@server or run setup-agent first"
+            ));
+        }
+    };
+    // Log in with the user's usual credentials to install the new key.
+    let ssh = SshConfig {
+        target,
+        port,
+        user: None,
+        host: None,
+        identity_file: args.identity_file,
+        ssh_options: args.ssh_option,
+        ssh_bin: args.ssh_bin,
+    };
+    let (resolved_target, _) = resolve_target_and_port(&ssh);
+
+    let comment = key_comment();
+    let (begin, end) = markers(&comment);
+    let command = forced_command(&args.remote_bin, &args.allow);
+    let update_config = config.target.trim().is_empty() || config.target == ssh.target;
+
+    if args.dry_run {
+        let public_key = match std::fs::read_to_string(public_key_path(&key_path)) {
+            Ok(public_key) if !args.force => {
+                println!("dry-run: would reuse {}", key_path.display());
+                public_key
+            }
+            _ => {
+                println!(
+                    "dry-run: would run `ssh-keygen -q -t ed25519 -N \"\" -C {comment} -f {}`",
+                    key_path.display()
+                );
+                format!("ssh-ed25519 <new key> {comment}")
+            }
+        };
+        println!("dry-run: would add to {resolved_target}:~/.ssh/authorized_keys:");
+        print!(
+            "{}",
+            render_block(&begin, &end, &render_entry(&command, &public_key))
+        );
+        if update_config {
+            println!(
+                "dry-run: would set agent identity_file = {} (with ssh option {IDENTITIES_ONLY})",
+                key_path.display()
+            );
+        } else {
+            println!(
+                "dry-run: would leave the agent config alone (it targets {})",
+                config.target
+            );
+        }
+        return Ok(());
+    }
+
+    let public_key = ensure_key(&key_path, &comment, args.force)?;
+    let block = render_block(&begin, &end, &render_entry(&command, &public_key));
+
+    let timeout_ms = args.timeout_ms.max(1);
+    let outcome = match ssh_exchange(&ssh, &upsert_script(), block.as_bytes()).await {
+        Ok(outcome) => outcome,
+        Err(err) => return crate::cli::exit::exit_with_code(5, &format!("{err:#}")),
+    };
+    // Login shells may print banners first; the script's answer is the last line.
+    if outcome.lines().last().map(str::trim) == Some(UNCHANGED) {
+        println!("{resolved_target}:~/.ssh/authorized_keys is already up to date");
+    } else {
+        println!("installed restricted key on {resolved_target}: {command}");
+    }
+
+    if update_config {
+        config.target = ssh.target.clone();
+        config.port = ssh.port;
+        config.identity_file = Some(key_path.clone());
+        if !config.ssh_options.iter().any(|opt| opt == IDENTITIES_ONLY) {
+            config.ssh_options.push(IDENTITIES_ONLY.to_string());
+        }
+        crate::agent::validate_config(&config)?;
+        crate::agent::store_config(&config)?;
+        println!("agent identity_file: {}", key_path.display());
+    } else {
+        println!(
+            "agent config targets {}; not changed (use `ssh_clipboard config set --identity-file {}`)",
+            config.target,
+            key_path.display()
+        );
+    }
+
+    let mut key_ssh = ssh.clone();
+    key_ssh.identity_file = Some(key_path);
+    key_ssh.ssh_options.push(IDENTITIES_ONLY.to_string());
+    verify(key_ssh, timeout_ms).await;
+    Ok(())
+}
+
+fn default_key_path(home: &Path) -> PathBuf {
+    home.join(".ssh").join(KEY_FILE_NAME)
+}
+
+fn public_key_path(key_path: &Path) -> PathBuf {
+    let mut name = key_path.as_os_str().to_owned();
+    name.push(".pub");
+    PathBuf::from(name)
+}
+
+/// Identifies this client's key in the server's `authorized_keys`, so each client
+/// manages its own entry.
+fn key_comment() -> String {
+    format!(
+        "ssh_clipboard@{}",
+        gethostname::gethostname().to_string_lossy()
+    )
+}
+
+fn markers(comment: &str) -> (String, String) {
+    (
+        format!("# >>> ssh_clipboard setup-key {comment} >>>"),
+        format!("# <<< ssh_clipboard setup-key {comment} <<<"),
+    )
+}
+
+fn forced_command(remote_bin: &str, allow: &[String]) -> String {
+    let mut command = format!("{remote_bin} proxy");
+    if !allow.is_empty() {
+        command.push_str(" --allow ");
+        command.push_str(&allow.join(","));
+    }
+    command
+}
+
+fn render_entry(command: &str, public_key: &str) -> String {
+    let escaped = command.replace('\\', "\\\\").replace('"', "\\\"");
+    format!(
+        "command=\"{escaped}\",{ENTRY_OPTIONS} {}",
+        public_key.trim()
+    )
+}
+
+fn render_block(begin: &str, end: &str, entry: &str) -> String {
+    format!("{begin}\n{entry}\n{end}\n")
+}
+
+/// Generates an unencrypted ed25519 key at `key_path` unless one exists, and
+/// returns its public half. The agent uses it without a prompt.
+fn ensure_key(key_path: &Path, comment: &str, force: bool) -> Result<String> {
+    let public_path = public_key_path(key_path);
+    if key_path.exists() && !force {
+        return std::fs::read_to_string(&public_path).wrap_err_with(|| {
+            format!(
+                "failed to read {}; use --force to generate a new key",
+                public_path.display()
+            )
+        });
+    }
+    if let Some(parent) = key_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty() && !parent.exists())
+    {
+        std::fs::create_dir_all(parent)
+            .wrap_err_with(|| format!("failed to create {}", parent.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))?;
+        }
+    }
+    for path in [key_path, public_path.as_path()] {
+        if path.exists() {
+            std::fs::remove_file(path)
+                .wrap_err_with(|| format!("failed to remove {}", path.display()))?;
+        }
+    }
+    let output = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C", comment, "-f"])
+        .arg(key_path)
+        .output()
+        .wrap_err("failed to run ssh-keygen (install the OpenSSH client)")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "ssh-keygen failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    std::fs::read_to_string(&public_path)
+        .wrap_err_with(|| format!("failed to read {}", public_path.display()))
+}
+
+/// Reads the managed block on stdin and swaps it into `authorized_keys` on the
+/// server, between the markers on its first and last lines (appending it when they
+/// are absent). Every other line is copied unchanged, so other keys and other
+/// clients' blocks survive. Prints `UNCHANGED` when the file already holds the block.
+fn upsert_script() -> String {
+    sh(&format!(
+        "umask 077 && mkdir -p \"$HOME/.ssh\" && keys=\"{REMOTE_AUTHORIZED_KEYS}\" && staged=\"{REMOTE_STAGED_KEYS}\" \
+         && block=$(cat) && begin=$(printf \"%s\\n\" \"$block\" | head -n 1) && end=$(printf \"%s\\n\" \"$block\" | tail -n 1) \
+         && skip=0 && found=0 && {{ \
+           if [ -e \"$keys\" ]; then while IFS= read -r line || [ -n \"$line\" ]; do \
+             if [ \"$skip\" = 1 ]; then if [ \"$line\" = \"$end\" ]; then skip=0; fi; continue; fi; \
+             if [ \"$line\" = \"$begin\" ]; then if [ \"$found\" = 0 ]; then printf \"%s\\n\" \"$block\"; fi; found=1; skip=1; continue; fi; \
+             printf \"%s\\n\" \"$line\"; \
+           done < \"$keys\"; fi; \
+           if [ \"$found\" = 0 ]; then printf \"%s\\n\" \"$block\"; fi; \
+         }} > \"$staged\" \
+         && if [ \"$skip\" = 1 ]; then rm -f \"$staged\"; echo \"unterminated block $begin in $keys\" >&2; exit 1; fi \
+         && if [ -e \"$keys\" ] && cmp -s \"$keys\" \"$staged\"; then rm -f \"$staged\"; echo {UNCHANGED}; \
+         else mv -f \"$staged\" \"$keys\" && echo updated; fi"
+    ))
+}
+
+/// Connects with the new key; a `forbidden` answer still proves the forced command
+/// runs.
+async fn verify(ssh: SshConfig, timeout_ms: u64) {
+    let config = ClientConfig {
+        ssh,
+        max_size: DEFAULT_MAX_SIZE,
+        timeout_ms,
+        resync_frames: true,
+        resync_max_bytes: 8192,
+        retry: RetryPolicy::none(),
+    };
+    match send_request(&config, make_request(RequestKind::PeekMeta)).await {
+        Ok(response) => match response.kind {
+            ResponseKind::Meta { .. }
+            | ResponseKind::Empty
+            | ResponseKind::Error {
+                code: ErrorCode::Forbidden,
+                ..
+            } => println!("verified: the new key reaches the proxy"),
+            ResponseKind::Error { message, .. } => {
+                println!("warning: the new key reaches the proxy, which answered: {message}")
+            }
+            other => println!("warning: unexpected response with the new key: {other:?}"),
+        },
+        Err(err) => println!(
+            "warning: could not connect with the new key: {err}; run `ssh_clipboard doctor` for details"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_restricts_the_key_to_the_proxy() {
+        let command = forced_command("ssh_clipboard", &["get".to_string(), "peek".to_string()]);
+        assert_eq!(command, "ssh_clipboard proxy --allow get,peek");
+        assert_eq!(
+            render_entry(&command, "ssh-ed25519 AAAAC3 ssh_clipboard@laptop\n"),
+            "command=\"ssh_clipboard proxy --allow get,peek\",no-port-forwarding,no-X11-forwarding,no-agent-forwarding,no-pty ssh-ed25519 AAAAC3 ssh_clipboard@laptop"
+        );
+        assert_eq!(
+            forced_command("/opt/ssh_clipboard", &[]),
+            "/opt/ssh_clipboard proxy"
+        );
+    }
+
+    /// Runs `upsert_script` locally against `home`, as the server's `sh` would.
+    fn upsert(home: &Path, block: &str) -> std::process::Output {
+        use std::io::Write;
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(upsert_script())
+            .env("HOME", home)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(block.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    #[test]
+    fn authorized_keys_block_is_replaced_on_the_server() {
+        let home = tempfile::tempdir().unwrap();
+        let keys = home.path().join(".ssh").join("authorized_keys");
+        let entry = |key: &str| format!("command=\"ssh_clipboard proxy\" ssh-ed25519 {key}");
+        let (begin, end) = markers("ssh_clipboard@laptop");
+        let block = render_block(&begin, &end, &entry("A1"));
+
+        let output = upsert(home.path(), &block);
+        assert!(output.status.success());
+        assert_eq!(std::fs::read_to_string(&keys).unwrap(), block);
+
+        let existing = "ssh-ed25519 AAAAother me@desktop\n# a note\n";
+        std::fs::write(&keys, format!("{existing}{block}")).unwrap();
+        let output = upsert(home.path(), &block);
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), UNCHANGED);
+
+        let (other_begin, other_end) = markers("ssh_clipboard@desktop");
+        let other = render_block(&other_begin, &other_end, &entry("B2"));
+        upsert(home.path(), &other);
+        let rotated = render_block(&begin, &end, &entry("A3"));
+        let output = upsert(home.path(), &rotated);
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "updated");
+        assert_eq!(
+            std::fs::read_to_string(&keys).unwrap(),
+            format!("{existing}{rotated}{other}")
+        );
+
+        std::fs::write(&keys, format!("{existing}{begin}\n{}\n", entry("A1"))).unwrap();
+        assert!(!upsert(home.path(), &block).status.success());
+        assert!(
+            std::fs::read_to_string(&keys)
+                .unwrap()
+                .starts_with(existing)
+        );
+    }
+
+    #[test]
+    fn key_is_generated_once_under_home() {
+        if Command::new("ssh-keygen").arg("-?").output().is_err() {
+            return;
+        }
+        let home = tempfile::tempdir().unwrap();
+        let key_path = default_key_path(home.path());
+        let public_key = ensure_key(&key_path, "ssh_clipboard@test", false).unwrap();
+        assert!(public_key.starts_with("ssh-ed25519 "));
+        assert!(public_key.trim_end().ends_with(" ssh_clipboard@test"));
+        assert_eq!(
+            ensure_key(&key_path, "ssh_clipboard@test", false).unwrap(),
+            public_key
+        );
+        assert_ne!(
+            ensure_key(&key_path, "ssh_clipboard@test", true).unwrap(),
+            public_key
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(home.path().join(".ssh"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+    }
+}
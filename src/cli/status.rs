@@ -0,0 +1,202 @@
+use eyre::Result;
+
+use crate::cli::{ClientConfigArgs, StatusArgs, build_client_config, handle_response};
+use crate::client::transport::{DEFAULT_TIMEOUT_MS, make_request, send_request};
+use crate::protocol::{DaemonStatus, RequestKind, Response, ResponseKind, StatusCounter};
+
+pub async fn run(args: StatusArgs) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    if args.local {
+        let socket_path = match args.socket_path {
+            Some(path) => path,
+            None => match crate::server_config::ServerConfig::load()?.socket_path {
+                Some(path) => path,
+                None => crate::daemon::default_socket_path()?,
+            },
+        };
+        let response = match local::send(
+            &socket_path,
+            args.max_size,
+            args.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS),
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(err) => return crate::cli::exit::exit_with_code(4, &format!("{err:#}")),
+        };
+        return handle_status_response(response, args.json);
+    }
+
+    let config = match build_client_config(ClientConfigArgs {
+        target: args.target,
+        host: args.host,
+        user: args.user,
+        port: args.port,
+        identity_file: args.identity_file,
+        ssh_option: args.ssh_option,
+        ssh_bin: args.ssh_bin,
+        max_size: args.max_size,
+        timeout_ms: args.timeout_ms,
+        strict_frames: args.strict_frames,
+        resync_max_bytes: args.resync_max_bytes,
+        retry_attempts: args.retry_attempts,
+        retry_backoff_ms: args.retry_backoff_ms,
+    }) {
+        Ok(config) => config,
+        Err(err) => return crate::cli::exit::exit_with_code(2, &format!("{err:#}")),
+    };
+    let response = match send_request(&config, make_request(RequestKind::Status)).await {
+        Ok(response) => response,
+        Err(err) => return crate::cli::exit::exit_with_code(5, &err.to_string()),
+    };
+    handle_status_response(response, args.json)
+}
+
+fn handle_status_response(response: Response, json: bool) -> Result<()> {
+    match response.kind {
+        ResponseKind::Status { status } => {
+            if json {
+                println!("{}", serde_json::to_string(&status)?);
+            } else {
+                println!("{}", format_status_output(&status));
+            }
+            Ok(())
+        }
+        ResponseKind::Error { .. } => handle_response(response, false),
+        other => crate::cli::exit::exit_with_code(
+            2,
+            &format!("unexpected response (is the daemon older than this client?): {other:?}"),
+        ),
+    }
+}
+
+fn format_status_output(status: &DaemonStatus) -> String {
+    let counters = |counters: &[StatusCounter]| {
+        if counters.is_empty() {
+            return "none".to_string();
+        }
+        counters
+            .iter()
+            .map(|counter| format!("{}={}", counter.name, counter.count))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let memory = match status.rss_bytes {
+        Some(rss) => format!("{} resident", super::humanize_bytes(rss)),
+        None => "unknown".to_string(),
+    };
+    format!(
+        "Version: {version} (protocol {protocol})\n\
+         PID: {pid}\n\
+         Uptime: {uptime}\n\
+         Socket: {socket}\n\
+         Max size: {max_size} bytes ({human_max})\n\
+         IO timeout: {io_timeout} ms\n\
+         Stored: {values} value(s), {stored} bytes\n\
+         Memory: {memory}\n\
+         Requests: {requests}\n\
+         Errors: {errors}",
+        version = status.version,
+        protocol = status.protocol_version,
+        pid = status.pid,
+        uptime = format_uptime(status.uptime_secs),
+        socket = status.socket_path,
+        max_size = status.max_size,
+        human_max = super::humanize_bytes(status.max_size),
+        io_timeout = status.io_timeout_ms,
+        values = status.stored_values,
+        stored = status.stored_bytes,
+        requests = counters(&status.requests),
+        errors = counters(&status.errors),
+    )
+}
+
+fn format_uptime(secs: u64) -> String {
+    let (days, hours, minutes, seconds) = (
+        secs / 86_400,
+        secs % 86_400 / 3_600,
+        secs % 3_600 / 60,
+        secs % 60,
+    );
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{seconds}s"),
+        (0, 0, _) => format!("{minutes}m {seconds}s"),
+        (0, _, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h"),
+    }
+}
+
+/// Talks to the daemon socket directly, for `status --local` on the server.
+#[cfg(target_os = "linux")]
+mod local {
+    use crate::client::transport::make_request;
+    use crate::framing::{decode_message, encode_message, read_frame_payload, write_frame_payload};
+    use crate::protocol::{RESPONSE_OVERHEAD, RequestKind, Response};
+    use eyre::{Result, WrapErr};
+    use std::path::Path;
+    use tokio::net::UnixStream;
+    use tokio::time::{Duration, timeout};
+
+    pub async fn send(socket_path: &Path, max_size: usize, timeout_ms: u64) -> Result<Response> {
+        let limit = Duration::from_millis(timeout_ms.max(1));
+        let mut stream = timeout(limit, UnixStream::connect(socket_path))
+            .await
+            .wrap_err("daemon connect timed out")?
+            .wrap_err_with(|| {
+                format!(
+                    "daemon not running or socket unavailable at {}",
+                    socket_path.display()
+                )
+            })?;
+        let payload = encode_message(&make_request(RequestKind::Status))?;
+        timeout(limit, write_frame_payload(&mut stream, &payload))
+            .await
+            .wrap_err("write to daemon timed out")??;
+        let payload = timeout(
+            limit,
+            read_frame_payload(&mut stream, max_size + RESPONSE_OVERHEAD),
+        )
+        .await
+        .wrap_err("read from daemon timed out")??;
+        decode_message(&payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_output_is_readable() {
+        let status = DaemonStatus {
+            version: "0.3.0".to_string(),
+            protocol_version: 3,
+            pid: 42,
+            uptime_secs: 3_723,
+            socket_path: "/run/user/1000/ssh_clipboard/daemon.sock".to_string(),
+            max_size: 1024,
+            io_timeout_ms: 7000,
+            stored_values: 1,
+            stored_bytes: 5,
+            rss_bytes: None,
+            requests: vec![
+                StatusCounter {
+                    name: "get".to_string(),
+                    count: 2,
+                },
+                StatusCounter {
+                    name: "set".to_string(),
+                    count: 1,
+                },
+            ],
+            errors: Vec::new(),
+        };
+        let output = format_status_output(&status);
+        assert!(output.starts_with("Version: 0.3.0 (protocol 3)\nPID: 42\nUptime: 1h 2m\n"));
+        assert!(output.contains("\nMax size: 1024 bytes (1.0 KiB)\n"));
+        assert!(output.contains("\nMemory: unknown\n"));
+        assert!(output.ends_with("\nRequests: get=2 set=1\nErrors: none"));
+        assert_eq!(format_uptime(59), "59s");
+        assert_eq!(format_uptime(90_061), "1d 1h");
+    }
+}
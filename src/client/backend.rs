@@ -0,0 +1,506 @@
+use crate::client::clipboard::{PrimaryHold, Selection};
+use crate::client::image;
+use crate::client_actions::{ClipboardAccess, SystemClipboard};
+use arboard::ImageData;
+use clap::ValueEnum;
+use eyre::{Result, WrapErr, eyre};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Which clipboard implementation the client uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// Pick a backend from the environment (see `detect_backend`).
+    #[default]
+    Auto,
+    /// Native clipboard access through `arboard`.
+    Arboard,
+    /// `wl-copy` / `wl-paste`.
+    WlClipboard,
+    Xclip,
+    Xsel,
+    /// `pbcopy` / `pbpaste`.
+    Pbcopy,
+    /// Windows clipboard from inside WSL via `powershell.exe`.
+    Wsl,
+    /// User-supplied commands only.
+    Command,
+}
+
+/// Shell command lines used by command-based backends. Read commands print the
+/// clipboard to stdout; write commands receive the value on stdin. Images are PNG.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardCommands {
+    pub read_text: Option<String>,
+    pub write_text: Option<String>,
+    pub read_image: Option<String>,
+    pub write_image: Option<String>,
+}
+
+impl ClipboardCommands {
+    /// Fills unset commands from `fallback`, keeping explicit overrides.
+    pub fn or(self, fallback: ClipboardCommands) -> ClipboardCommands {
+        ClipboardCommands {
+            read_text: self.read_text.or(fallback.read_text),
+            write_text: self.write_text.or(fallback.write_text),
+            read_image: self.read_image.or(fallback.read_image),
+            write_image: self.write_image.or(fallback.write_image),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read_text.is_none()
+            && self.write_text.is_none()
+            && self.read_image.is_none()
+            && self.write_image.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardBackendConfig {
+    pub backend: BackendKind,
+    /// Overrides for individual commands; also the full command set for `command`.
+    pub commands: ClipboardCommands,
+    /// Set by the caller, not read from config files.
+    #[serde(skip)]
+    pub primary_hold: PrimaryHold,
+}
+
+impl ClipboardBackendConfig {
+    /// Checks the settings without probing the environment.
+    pub fn validate(&self) -> Result<()> {
+        if self.backend == BackendKind::Arboard && !self.commands.is_empty() {
+            return Err(eyre!(
+                "clipboard commands require a command-based backend, not arboard"
+            ));
+        }
+        if self.backend == BackendKind::Command && self.commands.is_empty() {
+            return Err(eyre!(
+                "clipboard backend `command` needs at least one clipboard command"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Resolves `auto` and preset backends to a concrete clipboard for `selection`.
+    /// Custom commands get the selection in `SSH_CLIPBOARD_SELECTION`.
+    pub fn open(&self, max_decoded_bytes: usize, selection: Selection) -> Result<ClipboardBackend> {
+        self.validate()?;
+        let kind = match self.backend {
+            BackendKind::Auto => detect_backend(selection),
+            other => other,
+        };
+        if kind == BackendKind::Arboard {
+            if !self.commands.is_empty() {
+                return Err(eyre!(
+                    "clipboard commands require a command-based backend, not arboard"
+                ));
+            }
+            return Ok(ClipboardBackend::System(SystemClipboard {
+                selection,
+                primary_hold: self.primary_hold,
+            }));
+        }
+        let commands = self.commands.clone().or(preset_commands(kind, selection)?);
+        Ok(ClipboardBackend::Command(CommandClipboard::new(
+            commands,
+            max_decoded_bytes,
+            selection,
+        )))
+    }
+}
+
+pub enum ClipboardBackend {
+    System(SystemClipboard),
+    Command(CommandClipboard),
+}
+
+impl ClipboardAccess for ClipboardBackend {
+    fn read_text(&mut self) -> Result<String> {
+        match self {
+            ClipboardBackend::System(clipboard) => clipboard.read_text(),
+            ClipboardBackend::Command(clipboard) => clipboard.read_text(),
+        }
+    }
+
+    fn read_image(&mut self) -> Result<ImageData<'static>> {
+        match self {
+            ClipboardBackend::System(clipboard) => clipboard.read_image(),
+            ClipboardBackend::Command(clipboard) => clipboard.read_image(),
+        }
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<()> {
+        match self {
+            ClipboardBackend::System(clipboard) => clipboard.write_text(text),
+            ClipboardBackend::Command(clipboard) => clipboard.write_text(text),
+        }
+    }
+
+    fn write_image(&mut self, image: ImageData<'static>) -> Result<()> {
+        match self {
+            ClipboardBackend::System(clipboard) => clipboard.write_image(image),
+            ClipboardBackend::Command(clipboard) => clipboard.write_image(image),
+        }
+    }
+}
+
+/// Environment variable naming the selection (`clipboard` or `primary`) a
+/// clipboard command should act on.
+pub const SELECTION_ENV: &str = "SSH_CLIPBOARD_SELECTION";
+
+/// Clipboard access through external commands run by the platform shell.
+pub struct CommandClipboard {
+    commands: ClipboardCommands,
+    max_decoded_bytes: usize,
+    selection: Selection,
+}
+
+impl CommandClipboard {
+    pub fn new(
+        commands: ClipboardCommands,
+        max_decoded_bytes: usize,
+        selection: Selection,
+    ) -> Self {
+        Self {
+            commands,
+            max_decoded_bytes,
+            selection,
+        }
+    }
+}
+
+impl ClipboardAccess for CommandClipboard {
+    fn read_text(&mut self) -> Result<String> {
+        let command = required(&self.commands.read_text, "text read")?;
+        let output = run_read(command, self.selection)?;
+        if output.is_empty() {
+            return Err(eyre!("clipboard has no text"));
+        }
+        String::from_utf8(output).map_err(|_| eyre!("clipboard text is not valid UTF-8"))
+    }
+
+    fn read_image(&mut self) -> Result<ImageData<'static>> {
+        let command = required(&self.commands.read_image, "image read")?;
+        let output = run_read(command, self.selection)?;
+        if output.is_empty() {
+            return Err(eyre!("clipboard has no image"));
+        }
+        image::decode_png(&output, self.max_decoded_bytes)
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<()> {
+        let command = required(&self.commands.write_text, "text write")?;
+        run_write(command, self.selection, text.as_bytes())
+    }
+
+    fn write_image(&mut self, image: ImageData<'static>) -> Result<()> {
+        let command = required(&self.commands.write_image, "image write")?;
+        let png = image::encode_png(image)?;
+        run_write(command, self.selection, &png)
+    }
+}
+
+/// Picks a backend for `auto`. `arboard` is preferred wherever it works; command
+/// backends cover WSL and Wayland sessions where data-control may be missing.
+/// On X11, `xclip` and then `xsel` take over when `arboard` cannot open the
+/// display, and for the primary selection, which `arboard` only serves while the
+/// writing process lives. On macOS, `pbcopy` takes over when `arboard` cannot
+/// reach the pasteboard.
+pub fn detect_backend(selection: Selection) -> BackendKind {
+    if cfg!(target_os = "macos") {
+        if !arboard_available() && command_exists("pbpaste") {
+            return BackendKind::Pbcopy;
+        }
+        return BackendKind::Arboard;
+    }
+    if !cfg!(target_os = "linux") {
+        return BackendKind::Arboard;
+    }
+    if is_wsl() && command_exists("powershell.exe") {
+        return BackendKind::Wsl;
+    }
+    if env_present("WAYLAND_DISPLAY") && command_exists("wl-paste") {
+        return BackendKind::WlClipboard;
+    }
+    if env_present("DISPLAY")
+        && (selection == Selection::Primary || !arboard_available())
+        && let Some(kind) = [(BackendKind::Xclip, "xclip"), (BackendKind::Xsel, "xsel")]
+            .into_iter()
+            .find_map(|(kind, tool)| command_exists(tool).then_some(kind))
+    {
+        return kind;
+    }
+    BackendKind::Arboard
+}
+
+fn arboard_available() -> bool {
+    arboard::Clipboard::new().is_ok()
+}
+
+/// Built-in commands for a preset backend. `pbcopy` and `wsl` have no primary
+/// selection.
+pub fn preset_commands(kind: BackendKind, selection: Selection) -> Result<ClipboardCommands> {
+    let commands = |read_text: String, write_text: String, images: Option<(String, String)>| {
+        ClipboardCommands {
+            read_text: Some(read_text),
+            write_text: Some(write_text),
+            read_image: images.as_ref().map(|(read, _)| read.clone()),
+            write_image: images.map(|(_, write)| write),
+        }
+    };
+    let primary = selection == Selection::Primary;
+    let commands = match kind {
+        BackendKind::WlClipboard => {
+            let flag = if primary { " --primary" } else { "" };
+            commands(
+                format!("wl-paste{flag} --no-newline --type text"),
+                format!("wl-copy{flag} --type text/plain"),
+                Some((
+                    format!("wl-paste{flag} --type image/png"),
+                    format!("wl-copy{flag} --type image/png"),
+                )),
+            )
+        }
+        BackendKind::Xclip => {
+            let name = if primary { "primary" } else { "clipboard" };
+            commands(
+                format!("xclip -selection {name} -out -target UTF8_STRING"),
+                format!("xclip -selection {name} -in"),
+                Some((
+                    format!("xclip -selection {name} -out -target image/png"),
+                    format!("xclip -selection {name} -in -target image/png"),
+                )),
+            )
+        }
+        BackendKind::Xsel => {
+            let flag = if primary { "--primary" } else { "--clipboard" };
+            commands(
+                format!("xsel {flag} --output"),
+                format!("xsel {flag} --input"),
+                None,
+            )
+        }
+        BackendKind::Pbcopy | BackendKind::Wsl if primary => {
+            return Err(eyre!(
+                "the primary selection is not supported by the {kind:?} backend"
+            ));
+        }
+        BackendKind::Pbcopy => commands("pbpaste".to_string(), "pbcopy".to_string(), None),
+        BackendKind::Wsl => commands(
+            "powershell.exe -NoProfile -Command '[Console]::OutputEncoding=[Text.Encoding]::UTF8; [Console]::Out.Write((Get-Clipboard -Raw))'".to_string(),
+            "powershell.exe -NoProfile -Command '[Console]::InputEncoding=[Text.Encoding]::UTF8; Set-Clipboard -Value ([Console]::In.ReadToEnd())'".to_string(),
+            None,
+        ),
+        BackendKind::Auto | BackendKind::Arboard | BackendKind::Command => {
+            ClipboardCommands::default()
+        }
+    };
+    Ok(commands)
+}
+
+fn required<'a>(command: &'a Option<String>, what: &str) -> Result<&'a str> {
+    command
+        .as_deref()
+        .ok_or_else(|| eyre!("no clipboard {what} command configured"))
+}
+
+fn shell_command(command: &str, selection: Selection) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    let selection = match selection {
+        Selection::Clipboard => "clipboard",
+        Selection::Primary => "primary",
+    };
+    cmd.env(SELECTION_ENV, selection);
+    cmd
+}
+
+fn run_read(command: &str, selection: Selection) -> Result<Vec<u8>> {
+    let output = shell_command(command, selection)
+        .stdin(Stdio::null())
+        .output()
+        .wrap_err_with(|| format!("failed to run clipboard command `{command}`"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!(
+            "clipboard command `{command}` failed ({}): {}",
+            output.status,
+            stderr.trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+fn run_write(command: &str, selection: Selection, input: &[u8]) -> Result<()> {
+    // Tools like wl-copy and xclip fork a child that keeps serving the selection;
+    // capturing its stdout/stderr would block until that child exits.
+    let mut child = shell_command(command, selection)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .wrap_err_with(|| format!("failed to run clipboard command `{command}`"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input)
+            .wrap_err_with(|| format!("failed to write to clipboard command `{command}`"))?;
+    }
+    let status = child
+        .wait()
+        .wrap_err_with(|| format!("failed to wait for clipboard command `{command}`"))?;
+    if !status.success() {
+        return Err(eyre!("clipboard command `{command}` failed ({status})"));
+    }
+    Ok(())
+}
+
+fn env_present(name: &str) -> bool {
+    std::env::var_os(name).is_some_and(|value| !value.is_empty())
+}
+
+fn is_wsl() -> bool {
+    env_present("WSL_DISTRO_NAME")
+        || std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .map(|release| release.to_ascii_lowercase().contains("microsoft"))
+            .unwrap_or(false)
+}
+
+fn command_exists(name: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| dir.join(name).is_file())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn file_commands(dir: &std::path::Path) -> ClipboardCommands {
+        let text = dir.join("text");
+        let png = dir.join("image.png");
+        ClipboardCommands {
+            read_text: Some(format!("cat '{}'", text.display())),
+            write_text: Some(format!("cat > '{}'", text.display())),
+            read_image: Some(format!("cat '{}'", png.display())),
+            write_image: Some(format!("cat > '{}'", png.display())),
+        }
+    }
+
+    #[test]
+    fn command_backend_round_trips_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut clipboard =
+            CommandClipboard::new(file_commands(dir.path()), 1024, Selection::Clipboard);
+        clipboard.write_text("héllo\n").unwrap();
+        assert_eq!(clipboard.read_text().unwrap(), "héllo\n");
+    }
+
+    #[test]
+    fn command_backend_round_trips_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut clipboard =
+            CommandClipboard::new(file_commands(dir.path()), 1024, Selection::Clipboard);
+        let image = ImageData {
+            width: 1,
+            height: 1,
+            bytes: vec![1, 2, 3, 255].into(),
+        };
+        clipboard.write_image(image).unwrap();
+        let read = clipboard.read_image().unwrap();
+        assert_eq!((read.width, read.height), (1, 1));
+        assert_eq!(read.bytes.as_ref(), &[1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn failing_or_empty_read_is_an_error() {
+        let mut clipboard = CommandClipboard::new(
+            ClipboardCommands {
+                read_text: Some("printf ''".to_string()),
+                read_image: Some("echo nope >&2; exit 3".to_string()),
+                ..ClipboardCommands::default()
+            },
+            1024,
+            Selection::Clipboard,
+        );
+        assert!(clipboard.read_text().is_err());
+        let err = clipboard.read_image().unwrap_err().to_string();
+        assert!(err.contains("nope"), "{err}");
+        assert!(clipboard.write_text("x").is_err());
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_presets() {
+        let config = ClipboardBackendConfig {
+            backend: BackendKind::Xsel,
+            commands: ClipboardCommands {
+                read_text: Some("custom".to_string()),
+                ..ClipboardCommands::default()
+            },
+            ..ClipboardBackendConfig::default()
+        };
+        let merged =
+            config
+                .commands
+                .clone()
+                .or(preset_commands(config.backend, Selection::Clipboard).unwrap());
+        assert_eq!(merged.read_text.as_deref(), Some("custom"));
+        assert_eq!(
+            merged.write_text.as_deref(),
+            Some("xsel --clipboard --input")
+        );
+        assert!(merged.read_image.is_none());
+    }
+
+    #[test]
+    fn presets_map_primary_selection() {
+        let xclip = preset_commands(BackendKind::Xclip, Selection::Primary).unwrap();
+        assert_eq!(
+            xclip.write_text.as_deref(),
+            Some("xclip -selection primary -in")
+        );
+        let wl = preset_commands(BackendKind::WlClipboard, Selection::Primary).unwrap();
+        assert_eq!(
+            wl.read_text.as_deref(),
+            Some("wl-paste --primary --no-newline --type text")
+        );
+        let xsel = preset_commands(BackendKind::Xsel, Selection::Primary).unwrap();
+        assert_eq!(xsel.read_text.as_deref(), Some("xsel --primary --output"));
+        assert!(preset_commands(BackendKind::Pbcopy, Selection::Primary).is_err());
+    }
+
+    #[test]
+    fn command_backend_without_commands_is_rejected() {
+        let config = ClipboardBackendConfig {
+            backend: BackendKind::Command,
+            commands: ClipboardCommands::default(),
+            ..ClipboardBackendConfig::default()
+        };
+        assert!(config.validate().is_err());
+        assert!(config.open(1024, Selection::Clipboard).is_err());
+    }
+
+    #[test]
+    fn commands_see_the_selection() {
+        let commands = ClipboardCommands {
+            read_text: Some(format!("printf %s \"${SELECTION_ENV}\"")),
+            ..ClipboardCommands::default()
+        };
+        let mut primary = CommandClipboard::new(commands.clone(), 1024, Selection::Primary);
+        assert_eq!(primary.read_text().unwrap(), "primary");
+        let mut clipboard = CommandClipboard::new(commands, 1024, Selection::Clipboard);
+        assert_eq!(clipboard.read_text().unwrap(), "clipboard");
+    }
+}
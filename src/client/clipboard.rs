@@ -1,25 +1,67 @@
 use arboard::{Clipboard, ImageData};
+use clap::ValueEnum;
 use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
 
-pub fn read_text() -> Result<String> {
+/// Which system selection to use. `Primary` is the X11/Wayland mouse selection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Selection {
+    #[default]
+    Clipboard,
+    Primary,
+}
+
+/// Target selection(s) for a pull.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullSelection {
+    #[default]
+    Clipboard,
+    Primary,
+    Both,
+}
+
+/// How a process keeps serving a PRIMARY selection it wrote through `arboard`.
+/// X11 and Wayland serve a selection from the process that owns it, and clipboard
+/// managers only take over CLIPBOARD when the owner goes away.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrimaryHold {
+    /// Keep the owning handle for the rest of the process (the agent).
+    #[default]
+    Retain,
+    /// Block the write until another application takes the selection, for
+    /// commands that exit right after writing.
+    Wait,
+}
+
+impl PullSelection {
+    pub fn selections(self) -> &'static [Selection] {
+        match self {
+            PullSelection::Clipboard => &[Selection::Clipboard],
+            PullSelection::Primary => &[Selection::Primary],
+            PullSelection::Both => &[Selection::Clipboard, Selection::Primary],
+        }
+    }
+}
+
+pub fn read_text(selection: Selection) -> Result<String> {
     let mut clipboard = Clipboard::new().map_err(|err| eyre!("clipboard init failed: {err}"))?;
-    clipboard
-        .get_text()
+    platform::get_text(&mut clipboard, selection)
         .map_err(|err| eyre!("clipboard read failed: {err}"))
 }
 
-pub fn write_text(text: &str) -> Result<()> {
+pub fn write_text(text: &str, selection: Selection, hold: PrimaryHold) -> Result<()> {
     let mut clipboard = Clipboard::new().map_err(|err| eyre!("clipboard init failed: {err}"))?;
-    clipboard
-        .set_text(text.to_string())
+    platform::set_text(&mut clipboard, selection, hold, text)
         .map_err(|err| eyre!("clipboard write failed: {err}"))?;
+    platform::retain_owner(clipboard, selection, hold);
     Ok(())
 }
 
-pub fn read_image() -> Result<ImageData<'static>> {
+pub fn read_image(selection: Selection) -> Result<ImageData<'static>> {
     let mut clipboard = Clipboard::new().map_err(|err| eyre!("clipboard init failed: {err}"))?;
-    let image = clipboard
-        .get_image()
+    let image = platform::get_image(&mut clipboard, selection)
         .map_err(|err| eyre!("clipboard image read failed: {err}"))?;
     Ok(ImageData {
         width: image.width,
@@ -28,10 +70,146 @@ pub fn read_image() -> Result<ImageData<'static>> {
     })
 }
 
-pub fn write_image(image: ImageData<'static>) -> Result<()> {
+pub fn write_image(
+    image: ImageData<'static>,
+    selection: Selection,
+    hold: PrimaryHold,
+) -> Result<()> {
     let mut clipboard = Clipboard::new().map_err(|err| eyre!("clipboard init failed: {err}"))?;
-    clipboard
-        .set_image(image)
+    platform::set_image(&mut clipboard, selection, hold, image)
         .map_err(|err| eyre!("clipboard image write failed: {err}"))?;
+    platform::retain_owner(clipboard, selection, hold);
     Ok(())
 }
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{PrimaryHold, Selection};
+    use arboard::{Clipboard, GetExtLinux, ImageData, LinuxClipboardKind, Set, SetExtLinux};
+    use std::sync::Mutex;
+
+    /// The handle that last wrote PRIMARY with [`PrimaryHold::Retain`]. Nothing takes
+    /// PRIMARY over when its owner exits, so the handle lives as long as the process.
+    static PRIMARY_OWNER: Mutex<Option<Clipboard>> = Mutex::new(None);
+
+    fn kind(selection: Selection) -> LinuxClipboardKind {
+        match selection {
+            Selection::Clipboard => LinuxClipboardKind::Clipboard,
+            Selection::Primary => LinuxClipboardKind::Primary,
+        }
+    }
+
+    pub fn get_text(clipboard: &mut Clipboard, selection: Selection) -> Result<String, String> {
+        clipboard
+            .get()
+            .clipboard(kind(selection))
+            .text()
+            .map_err(|err| err.to_string())
+    }
+
+    fn setter(clipboard: &mut Clipboard, selection: Selection, hold: PrimaryHold) -> Set<'_> {
+        let set = clipboard.set().clipboard(kind(selection));
+        if selection == Selection::Primary && hold == PrimaryHold::Wait {
+            eprintln!(
+                "serving the primary selection until another application takes it (Ctrl-C to stop)"
+            );
+            return set.wait();
+        }
+        set
+    }
+
+    pub fn set_text(
+        clipboard: &mut Clipboard,
+        selection: Selection,
+        hold: PrimaryHold,
+        text: &str,
+    ) -> Result<(), String> {
+        setter(clipboard, selection, hold)
+            .text(text.to_string())
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn get_image(
+        clipboard: &mut Clipboard,
+        selection: Selection,
+    ) -> Result<ImageData<'static>, String> {
+        clipboard
+            .get()
+            .clipboard(kind(selection))
+            .image()
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn set_image(
+        clipboard: &mut Clipboard,
+        selection: Selection,
+        hold: PrimaryHold,
+        image: ImageData<'static>,
+    ) -> Result<(), String> {
+        setter(clipboard, selection, hold)
+            .image(image)
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn retain_owner(clipboard: Clipboard, selection: Selection, hold: PrimaryHold) {
+        if selection == Selection::Primary
+            && hold == PrimaryHold::Retain
+            && let Ok(mut owner) = PRIMARY_OWNER.lock()
+        {
+            *owner = Some(clipboard);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::{PrimaryHold, Selection};
+    use arboard::{Clipboard, ImageData};
+
+    const PRIMARY_UNSUPPORTED: &str = "the primary selection is only available on Linux";
+
+    pub fn get_text(clipboard: &mut Clipboard, selection: Selection) -> Result<String, String> {
+        if selection == Selection::Primary {
+            return Err(PRIMARY_UNSUPPORTED.to_string());
+        }
+        clipboard.get_text().map_err(|err| err.to_string())
+    }
+
+    pub fn set_text(
+        clipboard: &mut Clipboard,
+        selection: Selection,
+        _hold: PrimaryHold,
+        text: &str,
+    ) -> Result<(), String> {
+        if selection == Selection::Primary {
+            return Err(PRIMARY_UNSUPPORTED.to_string());
+        }
+        clipboard
+            .set_text(text.to_string())
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn get_image(
+        clipboard: &mut Clipboard,
+        selection: Selection,
+    ) -> Result<ImageData<'static>, String> {
+        if selection == Selection::Primary {
+            return Err(PRIMARY_UNSUPPORTED.to_string());
+        }
+        clipboard.get_image().map_err(|err| err.to_string())
+    }
+
+    pub fn set_image(
+        clipboard: &mut Clipboard,
+        selection: Selection,
+        _hold: PrimaryHold,
+        image: ImageData<'static>,
+    ) -> Result<(), String> {
+        if selection == Selection::Primary {
+            return Err(PRIMARY_UNSUPPORTED.to_string());
+        }
+        clipboard.set_image(image).map_err(|err| err.to_string())
+    }
+
+    pub fn retain_owner(_clipboard: Clipboard, _selection: Selection, _hold: PrimaryHold) {}
+}
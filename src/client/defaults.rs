@@ -0,0 +1,269 @@
+use crate::client::ssh::SshConfig;
+use eyre::{Result, WrapErr, eyre};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_ENV: &str = "SSH_CLIPBOARD_CONFIG";
+const ENV_PREFIX: &str = "SSH_CLIPBOARD_";
+
+/// Connection settings `push`, `pull`, `peek`, `status`, and `doctor` fall back to
+/// when their flags are not given. Read from the client config file, then
+/// `SSH_CLIPBOARD_*` environment variables, which win over the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientDefaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssh_options: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssh_bin: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+/// `$SSH_CLIPBOARD_CONFIG`, else `client.toml` in the per-user config directory
+/// (`%APPDATA%\ssh_clipboard` on Windows, `$XDG_CONFIG_HOME/ssh_clipboard` or
+/// `~/.config/ssh_clipboard` elsewhere).
+pub fn config_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    let config_dir = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA")
+            .map(PathBuf::from)
+            .ok_or_else(|| eyre!("APPDATA is not set"))?
+    } else {
+        match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::var_os("HOME")
+                .map(|home| Path::new(&home).join(".config"))
+                .ok_or_else(|| eyre!("HOME is not set"))?,
+        }
+    };
+    Ok(config_dir.join("ssh_clipboard").join("client.toml"))
+}
+
+impl ClientDefaults {
+    /// The config file overlaid with the environment; a missing file is skipped.
+    pub fn load() -> Result<Self> {
+        let file = Self::load_from(&config_path()?)?;
+        let env = Self::from_env(|name| std::env::var(name).ok())?;
+        Ok(file.overlay(&env))
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).wrap_err_with(|| format!("failed to read {}", path.display()));
+            }
+        };
+        Self::parse(&contents).wrap_err_with(|| format!("invalid config {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        toml::from_str(contents).map_err(|err| eyre!("{err}"))
+    }
+
+    /// `SSH_CLIPBOARD_TARGET`, `_PORT`, `_IDENTITY_FILE`, `_SSH_OPTIONS`
+    /// (`;`-separated), `_SSH_BIN`, and `_TIMEOUT_MS`; empty variables are ignored.
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let get = |key: &str| var(&format!("{ENV_PREFIX}{key}")).filter(|value| !value.is_empty());
+        let parse = |key: &str| -> Result<Option<u64>> {
+            get(key)
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|err| eyre!("invalid {ENV_PREFIX}{key}: {err}"))
+                })
+                .transpose()
+        };
+        let port = parse("PORT")?
+            .map(|port| u16::try_from(port).map_err(|_| eyre!("invalid {ENV_PREFIX}PORT: {port}")))
+            .transpose()?;
+        Ok(Self {
+            target: get("TARGET"),
+            port,
+            identity_file: get("IDENTITY_FILE").map(PathBuf::from),
+            ssh_options: get("SSH_OPTIONS").map(|value| {
+                value
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|opt| !opt.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }),
+            ssh_bin: get("SSH_BIN").map(PathBuf::from),
+            timeout_ms: parse("TIMEOUT_MS")?,
+        })
+    }
+
+    /// `self` with every key that `over` sets replaced. When `over` switches to a
+    /// different target, `self`'s port, identity file, and SSH options are dropped,
+    /// since they belong to `self`'s target.
+    pub fn overlay(&self, over: &ClientDefaults) -> ClientDefaults {
+        let keep_host_settings = match (&self.target, &over.target) {
+            (Some(lower), Some(upper)) => lower.trim() == upper.trim(),
+            _ => true,
+        };
+        ClientDefaults {
+            target: over.target.clone().or_else(|| self.target.clone()),
+            port: over.port.or(self.port.filter(|_| keep_host_settings)),
+            identity_file: over
+                .identity_file
+                .clone()
+                .or_else(|| self.identity_file.clone().filter(|_| keep_host_settings)),
+            ssh_options: over
+                .ssh_options
+                .clone()
+                .or_else(|| self.ssh_options.clone().filter(|_| keep_host_settings)),
+            ssh_bin: over.ssh_bin.clone().or_else(|| self.ssh_bin.clone()),
+            timeout_ms: over.timeout_ms.or(self.timeout_ms),
+        }
+    }
+
+    /// Fills in what the command line left unset. The target is only used when
+    /// neither `--target` nor `--host` was given; the port, identity file, and SSH
+    /// options belong to that target and are skipped for any other host.
+    pub fn apply(&self, ssh: &mut SshConfig) {
+        let Some(target) = &self.target else {
+            self.apply_local(ssh);
+            return;
+        };
+        if ssh.target.trim().is_empty() && ssh.host.is_none() {
+            ssh.target = target.clone();
+        }
+        if ssh.resolve_target().trim() == target.trim() {
+            if ssh.port.is_none() {
+                ssh.port = self.port;
+            }
+            if ssh.identity_file.is_none() {
+                ssh.identity_file = self.identity_file.clone();
+            }
+            if ssh.ssh_options.is_empty()
+                && let Some(options) = &self.ssh_options
+            {
+                ssh.ssh_options = options.clone();
+            }
+        }
+        self.apply_local(ssh);
+    }
+
+    /// Settings that do not depend on the host.
+    fn apply_local(&self, ssh: &mut SshConfig) {
+        if ssh.ssh_bin.is_none() {
+            ssh.ssh_bin = self.ssh_bin.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn ssh(target: &str) -> SshConfig {
+        SshConfig {
+            target: target.to_string(),
+            port: None,
+            user: None,
+            host: None,
+            identity_file: None,
+            ssh_options: Vec::new(),
+            ssh_bin: None,
+        }
+    }
+
+    #[test]
+    fn environment_wins_over_file_and_flags_win_over_both() {
+        let file = ClientDefaults::parse(
+            "target = \"alice@file\"\nport = 2222\nssh_options = [\"BatchMode=yes\"]\ntimeout_ms = 3000\n",
+        )
+        .unwrap();
+        let vars = HashMap::from([
+            ("SSH_CLIPBOARD_TARGET", "alice@env"),
+            (
+                "SSH_CLIPBOARD_SSH_OPTIONS",
+                "ProxyJump=bastion; Compression=yes",
+            ),
+            ("SSH_CLIPBOARD_SSH_BIN", ""),
+        ]);
+        let env =
+            ClientDefaults::from_env(|name| vars.get(name).map(|value| value.to_string())).unwrap();
+        let defaults = file.overlay(&env);
+        assert_eq!(defaults.target.as_deref(), Some("alice@env"));
+        // The file's port belongs to alice@file, not to the target from the environment.
+        assert_eq!(defaults.port, None);
+        assert_eq!(defaults.timeout_ms, Some(3000));
+        assert_eq!(defaults.ssh_bin, None);
+
+        let mut from_defaults = ssh("");
+        defaults.apply(&mut from_defaults);
+        assert_eq!(from_defaults.target, "alice@env");
+        assert_eq!(
+            from_defaults.ssh_options,
+            vec!["ProxyJump=bastion", "Compression=yes"]
+        );
+
+        let mut from_flags = ssh("bob@flag");
+        from_flags.port = Some(22);
+        defaults.apply(&mut from_flags);
+        assert_eq!(from_flags.target, "bob@flag");
+        assert_eq!(from_flags.port, Some(22));
+
+        // The default port and options belong to the default target.
+        let mut other_target = ssh("bob@flag");
+        defaults.apply(&mut other_target);
+        assert_eq!(other_target.port, None);
+        assert!(other_target.ssh_options.is_empty());
+        let mut same_target = ssh("alice@env");
+        defaults.apply(&mut same_target);
+        assert_eq!(same_target.port, None);
+        assert_eq!(
+            same_target.ssh_options,
+            vec!["ProxyJump=bastion", "Compression=yes"]
+        );
+
+        // A layer that keeps the target keeps the lower layer's host settings.
+        let same = ClientDefaults::parse("target = \"alice@file\"\n").unwrap();
+        let kept = file.overlay(&same);
+        assert_eq!(kept.port, Some(2222));
+        assert_eq!(kept.ssh_options, Some(vec!["BatchMode=yes".to_string()]));
+        let without_target = ClientDefaults::parse("timeout_ms = 100\n").unwrap();
+        assert_eq!(file.overlay(&without_target).port, Some(2222));
+
+        let mut with_host = SshConfig {
+            host: Some("flag-host".to_string()),
+            ..ssh("")
+        };
+        defaults.apply(&mut with_host);
+        assert_eq!(with_host.resolve_target(), "flag-host");
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(ClientDefaults::parse("targte = \"host\"").is_err());
+        let port = |value: &str| {
+            let value = value.to_string();
+            ClientDefaults::from_env(move |name| {
+                (name == "SSH_CLIPBOARD_PORT").then(|| value.clone())
+            })
+        };
+        assert!(port("70000").is_err());
+        assert!(port("ssh").is_err());
+        assert_eq!(port("2222").unwrap().port, Some(2222));
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            ClientDefaults::load_from(&dir.path().join("client.toml")).unwrap(),
+            ClientDefaults::default()
+        );
+    }
+}
@@ -1,40 +1,229 @@
+use crate::protocol::{CONTENT_TYPE_JPEG, CONTENT_TYPE_PNG, CONTENT_TYPE_WEBP};
 use arboard::ImageData;
+use clap::ValueEnum;
 use eyre::{Result, eyre};
-use image::codecs::png::{PngDecoder, PngEncoder};
-use image::{ImageBuffer, ImageDecoder, ImageEncoder, ImageFormat, Rgba};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{
+    DynamicImage, ImageBuffer, ImageDecoder, ImageEncoder, ImageFormat, ImageReader, Rgba,
+    RgbaImage,
+};
+use serde::{Deserialize, Serialize};
 use std::io::Cursor;
 
+pub const DEFAULT_JPEG_QUALITY: u8 = 85;
+/// Decode limit for images pushed from files or stdin (8192 x 8192 RGBA).
+pub const MAX_INPUT_DECODED_BYTES: usize = 8192 * 8192 * 4;
+
+/// Formats accepted by `push --image` and image data piped on stdin.
+const INPUT_FORMATS: &[ImageFormat] = &[
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Gif,
+    ImageFormat::Bmp,
+    ImageFormat::WebP,
+    ImageFormat::Tiff,
+];
+
+/// Image encodings the client can send or write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageEncoding {
+    #[default]
+    Png,
+    Jpeg,
+    /// Lossless WebP; the `image` crate has no lossy WebP encoder.
+    Webp,
+}
+
+impl ImageEncoding {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ImageEncoding::Png => CONTENT_TYPE_PNG,
+            ImageEncoding::Jpeg => CONTENT_TYPE_JPEG,
+            ImageEncoding::Webp => CONTENT_TYPE_WEBP,
+        }
+    }
+}
+
+/// How images read from the clipboard are encoded for a push. PNG is always tried
+/// first; the image is only downscaled and/or re-encoded when that PNG is larger
+/// than `threshold` (default: the push `max_size`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageTranscode {
+    pub format: ImageEncoding,
+    /// JPEG quality, 1-100.
+    pub quality: u8,
+    /// Longest side in pixels after downscaling; `None` keeps the original size.
+    pub max_dimension: Option<u32>,
+    pub threshold: Option<usize>,
+}
+
+impl Default for ImageTranscode {
+    fn default() -> Self {
+        Self {
+            format: ImageEncoding::Png,
+            quality: DEFAULT_JPEG_QUALITY,
+            max_dimension: None,
+            threshold: None,
+        }
+    }
+}
+
+impl ImageTranscode {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=100).contains(&self.quality) {
+            return Err(eyre!("image quality must be between 1 and 100"));
+        }
+        if self.max_dimension == Some(0) {
+            return Err(eyre!("max image dimension must be > 0"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EncodedImage {
+    pub content_type: &'static str,
+    pub data: Vec<u8>,
+}
+
 pub fn encode_png(image: ImageData<'static>) -> Result<Vec<u8>> {
-    let width = image.width as u32;
-    let height = image.height as u32;
-    let bytes = image.bytes.into_owned();
-    let buffer = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, bytes)
-        .ok_or_else(|| eyre!("invalid image buffer"))?;
+    encode_rgba(
+        &to_rgba_image(image)?,
+        ImageEncoding::Png,
+        DEFAULT_JPEG_QUALITY,
+    )
+}
 
-    let mut out = Vec::new();
-    let encoder = PngEncoder::new(&mut out);
-    encoder.write_image(
-        buffer.as_raw(),
-        width,
-        height,
-        image::ExtendedColorType::Rgba8,
-    )?;
-    Ok(out)
+/// Encodes a clipboard image for a push, applying `transcode` once the PNG form
+/// exceeds its threshold. The result may still exceed `max_size`; callers check.
+pub fn encode_for_push(
+    image: ImageData<'static>,
+    transcode: &ImageTranscode,
+    max_size: usize,
+) -> Result<EncodedImage> {
+    let buffer = to_rgba_image(image)?;
+    let png = encode_rgba(&buffer, ImageEncoding::Png, transcode.quality)?;
+    if png.len() <= transcode.threshold.unwrap_or(max_size) {
+        return Ok(EncodedImage {
+            content_type: CONTENT_TYPE_PNG,
+            data: png,
+        });
+    }
+
+    let buffer = match transcode.max_dimension {
+        Some(max) if buffer.width() > max || buffer.height() > max => {
+            DynamicImage::ImageRgba8(buffer)
+                .resize(max, max, FilterType::Lanczos3)
+                .into_rgba8()
+        }
+        _ if transcode.format == ImageEncoding::Png => {
+            return Ok(EncodedImage {
+                content_type: CONTENT_TYPE_PNG,
+                data: png,
+            });
+        }
+        _ => buffer,
+    };
+    Ok(EncodedImage {
+        content_type: transcode.format.content_type(),
+        data: encode_rgba(&buffer, transcode.format, transcode.quality)?,
+    })
+}
+
+/// Detects a supported input image format from its magic bytes.
+pub fn guess_input_format(data: &[u8]) -> Option<ImageFormat> {
+    image::guess_format(data)
+        .ok()
+        .filter(|format| INPUT_FORMATS.contains(format))
+}
+
+/// Prepares an encoded image file for a push. PNG, JPEG and WebP are sent as-is when
+/// they fit the transcode threshold; anything else is decoded (within
+/// `MAX_INPUT_DECODED_BYTES`) and handled like a clipboard image.
+pub fn encode_input_for_push(
+    data: Vec<u8>,
+    format: ImageFormat,
+    transcode: &ImageTranscode,
+    max_size: usize,
+) -> Result<EncodedImage> {
+    let native = match format {
+        ImageFormat::Png => Some(CONTENT_TYPE_PNG),
+        ImageFormat::Jpeg => Some(CONTENT_TYPE_JPEG),
+        ImageFormat::WebP => Some(CONTENT_TYPE_WEBP),
+        _ => None,
+    };
+    let image = decode_with_format(&data, format, MAX_INPUT_DECODED_BYTES)?;
+    if let Some(content_type) = native
+        && data.len() <= transcode.threshold.unwrap_or(max_size)
+    {
+        return Ok(EncodedImage { content_type, data });
+    }
+    encode_for_push(image, transcode, max_size)
+}
+
+/// Re-encodes image bytes of `content_type` into `target`.
+pub fn transcode(
+    data: &[u8],
+    content_type: &str,
+    target: ImageEncoding,
+    quality: u8,
+    max_decoded_bytes: usize,
+) -> Result<Vec<u8>> {
+    if target.content_type() == content_type {
+        return Ok(data.to_vec());
+    }
+    let image = decode_image(data, content_type, max_decoded_bytes)?;
+    encode_rgba(&to_rgba_image(image)?, target, quality)
 }
 
 pub fn decode_png(data: &[u8], max_decoded_bytes: usize) -> Result<ImageData<'static>> {
-    let decoder =
-        PngDecoder::new(Cursor::new(data)).map_err(|err| eyre!("png decode failed: {err}"))?;
+    decode_with_format(data, ImageFormat::Png, max_decoded_bytes)
+}
+
+/// Decodes any image content type the protocol carries.
+pub fn decode_image(
+    data: &[u8],
+    content_type: &str,
+    max_decoded_bytes: usize,
+) -> Result<ImageData<'static>> {
+    let format = image_format_for(content_type)
+        .ok_or_else(|| eyre!("unsupported image content type: {content_type}"))?;
+    decode_with_format(data, format, max_decoded_bytes)
+}
+
+pub fn image_format_for(content_type: &str) -> Option<ImageFormat> {
+    match content_type {
+        CONTENT_TYPE_PNG => Some(ImageFormat::Png),
+        CONTENT_TYPE_JPEG => Some(ImageFormat::Jpeg),
+        CONTENT_TYPE_WEBP => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+fn decode_with_format(
+    data: &[u8],
+    format: ImageFormat,
+    max_decoded_bytes: usize,
+) -> Result<ImageData<'static>> {
+    let name = format_name(format);
+    let decoder = ImageReader::with_format(Cursor::new(data), format)
+        .into_decoder()
+        .map_err(|err| eyre!("{name} decode failed: {err}"))?;
     let (width, height) = decoder.dimensions();
     let decoded_bytes = (width as u64)
         .saturating_mul(height as u64)
         .saturating_mul(4);
     if decoded_bytes > max_decoded_bytes as u64 {
-        return Err(eyre!("png image too large to decode safely"));
+        return Err(eyre!("{name} image too large to decode safely"));
     }
 
-    let image = image::load_from_memory_with_format(data, ImageFormat::Png)
-        .map_err(|err| eyre!("png decode failed: {err}"))?;
+    let image =
+        DynamicImage::from_decoder(decoder).map_err(|err| eyre!("{name} decode failed: {err}"))?;
     let rgba = image.into_rgba8();
     let (width, height) = rgba.dimensions();
     let bytes = rgba.into_raw();
@@ -45,6 +234,56 @@ pub fn decode_png(data: &[u8], max_decoded_bytes: usize) -> Result<ImageData<'st
     })
 }
 
+fn format_name(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "png",
+        ImageFormat::Jpeg => "jpeg",
+        ImageFormat::WebP => "webp",
+        ImageFormat::Gif => "gif",
+        ImageFormat::Bmp => "bmp",
+        ImageFormat::Tiff => "tiff",
+        _ => "image",
+    }
+}
+
+fn to_rgba_image(image: ImageData<'static>) -> Result<RgbaImage> {
+    let width = image.width as u32;
+    let height = image.height as u32;
+    let bytes = image.bytes.into_owned();
+    ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, bytes)
+        .ok_or_else(|| eyre!("invalid image buffer"))
+}
+
+fn encode_rgba(buffer: &RgbaImage, encoding: ImageEncoding, quality: u8) -> Result<Vec<u8>> {
+    let (width, height) = buffer.dimensions();
+    let mut out = Vec::new();
+    match encoding {
+        ImageEncoding::Png => PngEncoder::new(&mut out).write_image(
+            buffer.as_raw(),
+            width,
+            height,
+            image::ExtendedColorType::Rgba8,
+        )?,
+        ImageEncoding::Jpeg => {
+            // JPEG has no alpha channel.
+            let rgb = DynamicImage::ImageRgba8(buffer.clone()).into_rgb8();
+            JpegEncoder::new_with_quality(&mut out, quality).write_image(
+                rgb.as_raw(),
+                width,
+                height,
+                image::ExtendedColorType::Rgb8,
+            )?
+        }
+        ImageEncoding::Webp => WebPEncoder::new_lossless(&mut out).write_image(
+            buffer.as_raw(),
+            width,
+            height,
+            image::ExtendedColorType::Rgba8,
+        )?,
+    }
+    Ok(out)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -61,6 +300,23 @@ mod tests {
         (image, bytes)
     }
 
+    fn noisy_image(width: usize, height: usize) -> ImageData<'static> {
+        let mut state = 0x1234_5678u32;
+        let bytes = (0..width * height * 4)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect::<Vec<_>>();
+        ImageData {
+            width,
+            height,
+            bytes: bytes.into(),
+        }
+    }
+
     #[test]
     fn encode_decode_round_trip() {
         let (image, original_bytes) = sample_image();
@@ -84,4 +340,115 @@ mod tests {
         let err = decode_png(b"not a png", 1024).unwrap_err();
         assert!(err.to_string().contains("png decode failed"));
     }
+
+    #[test]
+    fn push_keeps_png_under_threshold() {
+        let (image, _) = sample_image();
+        let transcode = ImageTranscode {
+            format: ImageEncoding::Jpeg,
+            ..ImageTranscode::default()
+        };
+        let encoded = encode_for_push(image, &transcode, 1 << 20).unwrap();
+        assert_eq!(encoded.content_type, CONTENT_TYPE_PNG);
+    }
+
+    #[test]
+    fn push_transcodes_to_jpeg_over_threshold() {
+        let transcode = ImageTranscode {
+            format: ImageEncoding::Jpeg,
+            quality: 50,
+            threshold: Some(0),
+            ..ImageTranscode::default()
+        };
+        let encoded = encode_for_push(noisy_image(64, 64), &transcode, 1 << 20).unwrap();
+        assert_eq!(encoded.content_type, CONTENT_TYPE_JPEG);
+        let decoded = decode_image(&encoded.data, CONTENT_TYPE_JPEG, 1 << 20).unwrap();
+        assert_eq!((decoded.width, decoded.height), (64, 64));
+    }
+
+    #[test]
+    fn push_downscales_to_max_dimension() {
+        let transcode = ImageTranscode {
+            max_dimension: Some(16),
+            threshold: Some(0),
+            ..ImageTranscode::default()
+        };
+        let encoded = encode_for_push(noisy_image(64, 32), &transcode, 1 << 20).unwrap();
+        assert_eq!(encoded.content_type, CONTENT_TYPE_PNG);
+        let decoded = decode_png(&encoded.data, 1 << 20).unwrap();
+        assert_eq!((decoded.width, decoded.height), (16, 8));
+    }
+
+    #[test]
+    fn transcode_png_to_webp_round_trips() {
+        let (image, original_bytes) = sample_image();
+        let png = encode_png(image).unwrap();
+        let webp = transcode(&png, CONTENT_TYPE_PNG, ImageEncoding::Webp, 85, 1024).unwrap();
+        let decoded = decode_image(&webp, CONTENT_TYPE_WEBP, 1024).unwrap();
+        assert_eq!(decoded.bytes.into_owned(), original_bytes);
+    }
+
+    #[test]
+    fn input_jpeg_is_stored_natively() {
+        let jpeg = transcode(
+            &encode_png(noisy_image(8, 8)).unwrap(),
+            CONTENT_TYPE_PNG,
+            ImageEncoding::Jpeg,
+            90,
+            1 << 20,
+        )
+        .unwrap();
+        assert_eq!(guess_input_format(&jpeg), Some(ImageFormat::Jpeg));
+        let encoded = encode_input_for_push(
+            jpeg.clone(),
+            ImageFormat::Jpeg,
+            &ImageTranscode::default(),
+            1 << 20,
+        )
+        .unwrap();
+        assert_eq!(encoded.content_type, CONTENT_TYPE_JPEG);
+        assert_eq!(encoded.data, jpeg);
+    }
+
+    #[test]
+    fn input_bmp_is_normalized_to_png() {
+        let (image, original_bytes) = sample_image();
+        let mut bmp = Vec::new();
+        to_rgba_image(image)
+            .unwrap()
+            .write_to(&mut Cursor::new(&mut bmp), ImageFormat::Bmp)
+            .unwrap();
+        let format = guess_input_format(&bmp).unwrap();
+        let encoded =
+            encode_input_for_push(bmp, format, &ImageTranscode::default(), 1 << 20).unwrap();
+        assert_eq!(encoded.content_type, CONTENT_TYPE_PNG);
+        let decoded = decode_png(&encoded.data, 1024).unwrap();
+        assert_eq!(decoded.bytes.into_owned(), original_bytes);
+    }
+
+    #[test]
+    fn input_rejects_text_and_corrupt_images() {
+        assert_eq!(guess_input_format(b"hello world"), None);
+        let mut truncated = encode_png(noisy_image(8, 8)).unwrap();
+        truncated.truncate(40);
+        assert!(
+            encode_input_for_push(
+                truncated,
+                ImageFormat::Png,
+                &ImageTranscode::default(),
+                1024
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn transcode_validates_quality() {
+        let transcode = ImageTranscode {
+            quality: 0,
+            ..ImageTranscode::default()
+        };
+        assert!(transcode.validate().is_err());
+        assert!(ImageTranscode::default().validate().is_ok());
+    }
 }
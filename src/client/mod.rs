@@ -1,4 +1,6 @@
+pub mod backend;
 pub mod clipboard;
+pub mod defaults;
 pub mod image;
 pub mod ssh;
 pub mod transport;
@@ -1,6 +1,7 @@
 use eyre::{Result, eyre};
 use std::path::PathBuf;
 use tokio::process::{Child, Command};
+use tokio::time::{Duration, timeout};
 
 #[derive(Debug, Clone)]
 pub struct SshConfig {
@@ -60,7 +61,9 @@ pub fn resolve_target_and_port(config: &SshConfig) -> (String, Option<u16>) {
 pub fn spawn_ssh_proxy(config: &SshConfig) -> Result<Child> {
     let (target, port) = resolve_target_and_port(config);
     if target.trim().is_empty() {
-        return Err(eyre!("missing SSH target (use --target or --host)"));
+        return Err(eyre!(
+            "missing SSH target (use --target or --host, or set SSH_CLIPBOARD_TARGET)"
+        ));
     }
 
     let ssh_bin = config
@@ -96,10 +99,121 @@ pub fn spawn_ssh_proxy(config: &SshConfig) -> Result<Child> {
         .map_err(|err| eyre!("failed to spawn ssh: {err}"))
 }
 
+/// What `ssh -G` reports for a target after `~/.ssh/config`, `Match` blocks, and
+/// `-o` options are applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EffectiveSshConfig {
+    pub hostname: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// As written in the config, so `~` is not expanded.
+    pub identity_files: Vec<String>,
+    pub proxy_jump: Option<String>,
+    pub proxy_command: Option<String>,
+    pub control_master: Option<String>,
+    pub request_tty: Option<String>,
+    pub remote_command: Option<String>,
+    pub log_level: Option<String>,
+}
+
+/// Runs `ssh -G` with the options `spawn_ssh_proxy` would use, except `-T`, so a
+/// `RequestTTY` setting in the config stays visible.
+pub async fn effective_config(config: &SshConfig, timeout_ms: u64) -> Result<EffectiveSshConfig> {
+    let (target, port) = resolve_target_and_port(config);
+    if target.trim().is_empty() {
+        return Err(eyre!("missing target"));
+    }
+    let ssh_bin = config
+        .ssh_bin
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("ssh"));
+    let mut command = Command::new(ssh_bin);
+    command.arg("-G");
+    if let Some(port) = port {
+        command.arg("-p").arg(port.to_string());
+    }
+    if let Some(identity_file) = &config.identity_file {
+        command.arg("-i").arg(identity_file);
+    }
+    for opt in &config.ssh_options {
+        command.arg("-o").arg(opt);
+    }
+    command.arg(target).stdin(std::process::Stdio::null());
+
+    let output = timeout(Duration::from_millis(timeout_ms), command.output())
+        .await
+        .map_err(|_| eyre!("`ssh -G` timed out after {timeout_ms}ms"))?
+        .map_err(|err| eyre!("failed to run ssh: {err}"))?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "`ssh -G` exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    parse_effective_config(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parses `ssh -G` output: one lowercase keyword and its value per line.
+pub fn parse_effective_config(output: &str) -> Result<EffectiveSshConfig> {
+    let mut config = EffectiveSshConfig::default();
+    for line in output.lines() {
+        let Some((key, value)) = line.trim().split_once(' ') else {
+            continue;
+        };
+        let value = value.trim();
+        let set = || (!value.eq_ignore_ascii_case("none")).then(|| value.to_string());
+        match key {
+            "hostname" => config.hostname = value.to_string(),
+            "user" => config.user = set(),
+            "port" => config.port = value.parse().ok(),
+            "identityfile" => config.identity_files.push(value.to_string()),
+            "proxyjump" => config.proxy_jump = set(),
+            "proxycommand" => config.proxy_command = set(),
+            "controlmaster" => config.control_master = set(),
+            "requesttty" => config.request_tty = set(),
+            "remotecommand" => config.remote_command = set(),
+            "loglevel" => config.log_level = set(),
+            _ => {}
+        }
+    }
+    if config.hostname.is_empty() {
+        return Err(eyre!("`ssh -G` printed no hostname"));
+    }
+    Ok(config)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn effective_config_is_parsed_from_ssh_g() {
+        let config = parse_effective_config(
+            "host devbox\nuser alice\nhostname 10.0.0.5\nport 2200\ncontrolmaster auto\n\
+             requesttty force\nremotecommand tmux\nloglevel DEBUG2\n\
+             identityfile ~/.ssh/dev\nidentityfile ~/.ssh/id_ed25519\nproxyjump bastion\n\
+             proxycommand none\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            EffectiveSshConfig {
+                hostname: "10.0.0.5".to_string(),
+                user: Some("alice".to_string()),
+                port: Some(2200),
+                identity_files: vec!["~/.ssh/dev".to_string(), "~/.ssh/id_ed25519".to_string()],
+                proxy_jump: Some("bastion".to_string()),
+                proxy_command: None,
+                control_master: Some("auto".to_string()),
+                request_tty: Some("force".to_string()),
+                remote_command: Some("tmux".to_string()),
+                log_level: Some("DEBUG2".to_string()),
+            }
+        );
+        assert!(parse_effective_config("user alice\n").is_err());
+    }
+
     fn base_config(target: &str) -> SshConfig {
         SshConfig {
             target: target.to_string(),
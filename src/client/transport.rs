@@ -1,4 +1,4 @@
-use crate::client::ssh::{SshConfig, spawn_ssh_proxy};
+use crate::client::ssh::{SshConfig, resolve_target_and_port, spawn_ssh_proxy};
 use crate::framing::{
     decode_message, encode_message, read_frame_payload, read_frame_payload_resync,
     write_frame_payload,
@@ -6,13 +6,23 @@ use crate::framing::{
 use crate::protocol::{
     DEFAULT_MAX_SIZE, ErrorCode, RESPONSE_OVERHEAD, Request, RequestKind, Response, ResponseKind,
 };
-use eyre::{Result, WrapErr, eyre};
+use eyre::{Result, eyre};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::atomic::{AtomicU64 as AtomicU64Warn, Ordering as OrderingWarn};
+use thiserror::Error;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStderr};
 use tokio::time::{Duration, timeout};
 use tracing::warn;
 
+pub const DEFAULT_TIMEOUT_MS: u64 = 7000;
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+pub const DEFAULT_RETRY_BACKOFF_MS: u64 = 250;
+const MAX_RETRY_BACKOFF_MS: u64 = 4000;
+
 #[derive(Debug, Clone)]
 pub struct ClientConfig {
     pub ssh: SshConfig,
@@ -20,6 +30,7 @@ pub struct ClientConfig {
     pub timeout_ms: u64,
     pub resync_frames: bool,
     pub resync_max_bytes: usize,
+    pub retry: RetryPolicy,
 }
 
 impl ClientConfig {
@@ -32,20 +43,184 @@ impl ClientConfig {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub jitter_ms: u64,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            attempts: 1,
+            backoff_ms: 0,
+            max_backoff_ms: 0,
+            jitter_ms: 0,
+        }
+    }
+
+    pub fn with_attempts(attempts: u32, backoff_ms: u64) -> Self {
+        Self {
+            attempts: attempts.max(1),
+            backoff_ms,
+            max_backoff_ms: MAX_RETRY_BACKOFF_MS.max(backoff_ms),
+            jitter_ms: backoff_ms / 2,
+        }
+    }
+
+    /// Delay before retry number `retry` (0-based): exponential backoff capped at
+    /// `max_backoff_ms`, plus up to `jitter_ms` of jitter.
+    pub fn delay_for(&self, retry: u32) -> Duration {
+        let base = self
+            .backoff_ms
+            .saturating_mul(1u64 << retry.min(16))
+            .min(self.max_backoff_ms);
+        let jitter = if self.jitter_ms == 0 {
+            0
+        } else {
+            jitter_seed() % (self.jitter_ms + 1)
+        };
+        Duration::from_millis(base.saturating_add(jitter))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::with_attempts(DEFAULT_RETRY_ATTEMPTS, DEFAULT_RETRY_BACKOFF_MS)
+    }
+}
+
+fn jitter_seed() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as u64;
+    nanos ^ REQUEST_COUNTER.load(Ordering::Relaxed).rotate_left(17)
+}
+
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error("failed to spawn ssh: {0}")]
+    Spawn(String),
+    #[error("ssh send timed out")]
+    SendTimeout,
+    #[error("ssh receive timed out")]
+    ReceiveTimeout,
+    #[error("connection reset: {0}")]
+    ConnectionReset(String),
+    #[error("ssh authentication failed: {0}")]
+    AuthFailed(String),
+    #[error("ssh error: {0}")]
+    Ssh(String),
+    #[error("ssh exited with status {0}")]
+    SshStatus(String),
+    #[error(transparent)]
+    Other(#[from] eyre::Report),
+}
+
+impl TransportError {
+    /// Whether the request can safely be sent again. Protocol `Error` responses are
+    /// returned as `Ok(Response)` and never reach this classification.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Spawn(_) | Self::SendTimeout | Self::ConnectionReset(_)
+        )
+    }
+
+    /// Whether the failure looks like the server was unreachable (as opposed to a
+    /// rejected request or misconfiguration).
+    pub fn is_connectivity(&self) -> bool {
+        self.is_retryable() || matches!(self, Self::ReceiveTimeout)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{source} (after {attempts} attempts)")]
+pub struct RetriesExhausted {
+    pub attempts: u32,
+    pub source: TransportError,
+}
+
+/// Finds the transport failure behind an error returned by `send_request*`.
+pub fn transport_error(err: &eyre::Report) -> Option<&TransportError> {
+    if let Some(exhausted) = err.downcast_ref::<RetriesExhausted>() {
+        return Some(&exhausted.source);
+    }
+    err.downcast_ref::<TransportError>()
+}
+
+pub struct SendOutcome {
+    pub response: Response,
+    pub retries: u32,
+}
+
 pub async fn send_request(config: &ClientConfig, request: Request) -> Result<Response> {
+    Ok(send_request_with_retry(config, request).await?.response)
+}
+
+pub async fn send_request_with_retry(
+    config: &ClientConfig,
+    request: Request,
+) -> Result<SendOutcome> {
     let max_size = config.normalized_max_size();
     let payload = encode_message(&request)?;
     if payload.len() > max_size {
-        return Ok(Response {
-            request_id: request.request_id,
-            kind: ResponseKind::Error {
-                code: ErrorCode::PayloadTooLarge,
-                message: "payload too large".to_string(),
+        return Ok(SendOutcome {
+            response: Response {
+                request_id: request.request_id,
+                kind: ResponseKind::Error {
+                    code: ErrorCode::PayloadTooLarge,
+                    message: "payload too large".to_string(),
+                },
             },
+            retries: 0,
         });
     }
+    if resolve_target_and_port(&config.ssh).0.trim().is_empty() {
+        return Err(eyre!(
+            "missing SSH target (use --target or --host, or set SSH_CLIPBOARD_TARGET)"
+        ));
+    }
 
-    let mut child = spawn_ssh_proxy(&config.ssh)?;
+    let attempts = config.retry.attempts.max(1);
+    let mut retries = 0u32;
+    loop {
+        match send_once(config, &payload, max_size).await {
+            Ok(response) => return Ok(SendOutcome { response, retries }),
+            Err(err) if err.is_retryable() && retries + 1 < attempts => {
+                let delay = config.retry.delay_for(retries);
+                retries += 1;
+                warn!(
+                    request_id = request.request_id,
+                    attempt = retries,
+                    delay_ms = delay.as_millis() as u64,
+                    error = %err,
+                    "retrying request after transient failure"
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(err) if retries > 0 => {
+                return Err(RetriesExhausted {
+                    attempts: retries + 1,
+                    source: err,
+                }
+                .into());
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+async fn send_once(
+    config: &ClientConfig,
+    payload: &[u8],
+    max_size: usize,
+) -> std::result::Result<Response, TransportError> {
+    let mut child =
+        spawn_ssh_proxy(&config.ssh).map_err(|err| TransportError::Spawn(err.to_string()))?;
     let mut stdin = child
         .stdin
         .take()
@@ -60,13 +235,20 @@ pub async fn send_request(config: &ClientConfig, request: Request) -> Result<Res
         .ok_or_else(|| eyre!("missing ssh stderr"))?;
 
     let send = async {
-        write_frame_payload(&mut stdin, &payload).await?;
+        write_frame_payload(&mut stdin, payload).await?;
         stdin.shutdown().await?;
         Ok::<(), eyre::Report>(())
     };
-    timeout(Duration::from_millis(config.timeout_ms), send)
-        .await
-        .wrap_err("ssh send timed out")??;
+    match timeout(Duration::from_millis(config.timeout_ms), send).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => {
+            let fallback = classify_io_failure(err);
+            return Err(ssh_failure(&mut child, &mut stderr, fallback).await);
+        }
+        Err(_) => {
+            return Err(ssh_failure(&mut child, &mut stderr, TransportError::SendTimeout).await);
+        }
+    }
 
     let receive = async {
         let response_payload = if config.resync_frames {
@@ -86,14 +268,19 @@ pub async fn send_request(config: &ClientConfig, request: Request) -> Result<Res
         let response: Response = decode_message(&response_payload)?;
         Ok::<Response, eyre::Report>(response)
     };
-    let response = timeout(Duration::from_millis(config.timeout_ms), receive)
-        .await
-        .wrap_err("ssh receive timed out")??;
+    let response = match timeout(Duration::from_millis(config.timeout_ms), receive).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(err)) => {
+            let fallback = classify_io_failure(err);
+            return Err(ssh_failure(&mut child, &mut stderr, fallback).await);
+        }
+        Err(_) => return Err(TransportError::ReceiveTimeout),
+    };
 
     let status = timeout(Duration::from_millis(config.timeout_ms), child.wait())
         .await
-        .wrap_err("ssh wait timed out")?
-        .wrap_err("ssh wait failed")?;
+        .map_err(|_| eyre!("ssh wait timed out"))?
+        .map_err(|err| eyre!("ssh wait failed: {err}"))?;
     if !status.success() {
         let mut stderr_buf = String::new();
         let _ = stderr.read_to_string(&mut stderr_buf).await;
@@ -101,28 +288,97 @@ pub async fn send_request(config: &ClientConfig, request: Request) -> Result<Res
             return Ok(response);
         }
         if stderr_buf.trim().is_empty() {
-            return Err(eyre!("ssh exited with status {status}"));
+            return Err(TransportError::SshStatus(status.to_string()));
         }
-        return Err(eyre!("ssh error: {stderr_buf}"));
+        return Err(
+            classify_ssh_stderr(&stderr_buf).unwrap_or_else(|| TransportError::Ssh(stderr_buf))
+        );
     }
 
     Ok(response)
 }
 
+/// Collects ssh's stderr after a failed exchange so auth and network failures can
+/// be told apart; falls back to `fallback` when stderr is not conclusive.
+async fn ssh_failure(
+    child: &mut Child,
+    stderr: &mut ChildStderr,
+    fallback: TransportError,
+) -> TransportError {
+    let grace = Duration::from_millis(500);
+    if timeout(grace, child.wait()).await.is_err() {
+        let _ = child.start_kill();
+    }
+    let mut stderr_buf = String::new();
+    let _ = timeout(grace, stderr.read_to_string(&mut stderr_buf)).await;
+    classify_ssh_stderr(&stderr_buf).unwrap_or(fallback)
+}
+
+fn classify_io_failure(err: eyre::Report) -> TransportError {
+    match err.downcast_ref::<std::io::Error>().map(|err| err.kind()) {
+        Some(
+            std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::UnexpectedEof,
+        ) => TransportError::ConnectionReset(err.to_string()),
+        _ => TransportError::Other(err),
+    }
+}
+
+pub(crate) fn classify_ssh_stderr(stderr: &str) -> Option<TransportError> {
+    const AUTH_MARKERS: &[&str] = &[
+        "Permission denied",
+        "Host key verification failed",
+        "Too many authentication failures",
+        "REMOTE HOST IDENTIFICATION HAS CHANGED",
+    ];
+    const NETWORK_MARKERS: &[&str] = &[
+        "Connection reset",
+        "Connection refused",
+        "Connection timed out",
+        "Connection closed by",
+        "Network is unreachable",
+        "No route to host",
+        "Could not resolve hostname",
+        "kex_exchange_identification",
+        "Broken pipe",
+    ];
+
+    let trimmed = stderr.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if AUTH_MARKERS.iter().any(|marker| trimmed.contains(marker)) {
+        return Some(TransportError::AuthFailed(trimmed.to_string()));
+    }
+    if NETWORK_MARKERS
+        .iter()
+        .any(|marker| trimmed.contains(marker))
+    {
+        return Some(TransportError::ConnectionReset(trimmed.to_string()));
+    }
+    Some(TransportError::Ssh(trimmed.to_string()))
+}
+
 static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+static REQUEST_ID_KEYS: OnceLock<RandomState> = OnceLock::new();
 
+/// Unique per request, including across processes started in the same
+/// millisecond: the daemon drops a `Set` whose id it has already seen as a retry.
+/// Never `0`, which the daemon treats as "no id".
 pub fn new_request_id() -> u64 {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64;
-    let counter = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed) & 0xffff;
-    (now << 16) | counter
+    let counter = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut hasher = REQUEST_ID_KEYS.get_or_init(RandomState::new).build_hasher();
+    hasher.write_u64(std::process::id().into());
+    hasher.write_u64(counter);
+    hasher.finish().max(1)
 }
 
 pub fn make_request(kind: RequestKind) -> Request {
     Request {
         request_id: new_request_id(),
+        ssh_connection: None,
         kind,
     }
 }
@@ -148,3 +404,103 @@ fn warn_noisy_shell(discarded: usize) {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_ids_are_unique_and_not_time_ordered() {
+        let ids = (0..1000).map(|_| new_request_id()).collect::<Vec<_>>();
+        let unique = ids.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(unique.len(), ids.len());
+        assert!(!ids.contains(&0));
+        assert!(ids.windows(2).any(|pair| pair[0] > pair[1]));
+    }
+
+    #[test]
+    fn retry_delay_grows_exponentially_and_caps() {
+        let policy = RetryPolicy {
+            attempts: 5,
+            backoff_ms: 100,
+            max_backoff_ms: 300,
+            jitter_ms: 0,
+        };
+        assert_eq!(policy.delay_for(0), Duration::from_millis(100));
+        assert_eq!(policy.delay_for(1), Duration::from_millis(200));
+        assert_eq!(policy.delay_for(2), Duration::from_millis(300));
+        assert_eq!(policy.delay_for(40), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn retry_delay_jitter_stays_in_bounds() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            backoff_ms: 100,
+            max_backoff_ms: 1000,
+            jitter_ms: 50,
+        };
+        for _ in 0..32 {
+            let delay = policy.delay_for(0);
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay <= Duration::from_millis(150));
+        }
+    }
+
+    #[test]
+    fn classify_ssh_stderr_separates_auth_from_network() {
+        let auth = classify_ssh_stderr("user@host: Permission denied (publickey).").unwrap();
+        assert!(matches!(auth, TransportError::AuthFailed(_)));
+        assert!(!auth.is_retryable());
+
+        let reset = classify_ssh_stderr("Connection reset by 10.0.0.1 port 22\r\n").unwrap();
+        assert!(matches!(reset, TransportError::ConnectionReset(_)));
+        assert!(reset.is_retryable());
+
+        let other = classify_ssh_stderr("bash: ssh_clipboard: command not found").unwrap();
+        assert!(!other.is_retryable());
+
+        assert!(classify_ssh_stderr("  ").is_none());
+    }
+
+    #[test]
+    fn classify_io_failure_treats_broken_pipe_as_reset() {
+        let err = eyre::Report::new(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        assert!(classify_io_failure(err).is_retryable());
+        let err = eyre!("decode error");
+        assert!(!classify_io_failure(err).is_retryable());
+    }
+
+    #[tokio::test]
+    async fn spawn_failures_are_retried_up_to_attempts() {
+        let config = ClientConfig {
+            ssh: SshConfig {
+                target: "user@example.invalid".to_string(),
+                port: None,
+                user: None,
+                host: None,
+                identity_file: None,
+                ssh_options: Vec::new(),
+                ssh_bin: Some("/nonexistent/ssh_clipboard_test_ssh".into()),
+            },
+            max_size: 1024,
+            timeout_ms: 1000,
+            resync_frames: true,
+            resync_max_bytes: 64,
+            retry: RetryPolicy {
+                attempts: 3,
+                backoff_ms: 1,
+                max_backoff_ms: 1,
+                jitter_ms: 0,
+            },
+        };
+        let err = send_request_with_retry(&config, make_request(RequestKind::Get))
+            .await
+            .err()
+            .unwrap();
+        let message = err.to_string();
+        assert!(message.contains("failed to spawn ssh"));
+        assert!(message.contains("after 3 attempts"));
+        assert!(transport_error(&err).is_some_and(TransportError::is_connectivity));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::protocol::{CONTENT_TYPE_TEXT, ClipboardValue, Response, ResponseKind};
+use clap::ValueEnum;
+use eyre::{Result, eyre};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+static ANSI_ESCAPE: LazyLock<Regex> = LazyLock::new(|| {
+    // CSI sequences (colors, cursor movement) and OSC sequences (titles, hyperlinks).
+    Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)").unwrap()
+});
+
+/// One step of a text transform chain. Parsed from the same string syntax on the
+/// command line (`--transform`) and in the agent config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Transform {
+    /// Convert CRLF and lone CR to LF.
+    Lf,
+    /// Convert all line endings to CRLF.
+    Crlf,
+    StripAnsi,
+    Trim,
+    /// Remove trailing whitespace, including newlines, at the end of the text.
+    TrimEnd,
+    /// Remove the common leading whitespace of all non-blank lines.
+    Dedent,
+    /// Replace tabs with `n` spaces.
+    ExpandTabs(usize),
+    /// `s/pattern/replacement/`; any delimiter may follow the `s`.
+    Replace {
+        spec: String,
+        regex: Regex,
+        replacement: String,
+    },
+}
+
+impl Transform {
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Transform::Lf => text.replace("\r\n", "\n").replace('\r', "\n"),
+            Transform::Crlf => Transform::Lf.apply(text).replace('\n', "\r\n"),
+            Transform::StripAnsi => ANSI_ESCAPE.replace_all(text, "").into_owned(),
+            Transform::Trim => text.trim().to_string(),
+            Transform::TrimEnd => text.trim_end().to_string(),
+            Transform::Dedent => dedent(text),
+            Transform::ExpandTabs(width) => text.replace('\t', &" ".repeat(*width)),
+            Transform::Replace {
+                regex, replacement, ..
+            } => regex.replace_all(text, replacement.as_str()).into_owned(),
+        }
+    }
+}
+
+impl FromStr for Transform {
+    type Err = eyre::Report;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        if let Some(rest) = spec.strip_prefix('s')
+            && let Some(delimiter) = rest.chars().next()
+            && !delimiter.is_alphanumeric()
+            && delimiter != '-'
+        {
+            return parse_replace(spec, &rest[delimiter.len_utf8()..], delimiter);
+        }
+        let (name, arg) = match spec.split_once('=') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (spec, None),
+        };
+        let transform = match name {
+            "lf" => Transform::Lf,
+            "crlf" => Transform::Crlf,
+            "strip-ansi" => Transform::StripAnsi,
+            "trim" => Transform::Trim,
+            "trim-end" => Transform::TrimEnd,
+            "dedent" => Transform::Dedent,
+            "expand-tabs" => {
+                let width = match arg {
+                    Some(arg) => arg
+                        .parse()
+                        .map_err(|_| eyre!("invalid tab width in `{spec}`"))?,
+                    None => 4,
+                };
+                return Ok(Transform::ExpandTabs(width));
+            }
+            _ => {
+                return Err(eyre!(
+                    "unknown transform `{spec}` (expected lf, crlf, strip-ansi, trim, trim-end, dedent, expand-tabs[=N], or s/pattern/replacement/)"
+                ));
+            }
+        };
+        if arg.is_some() {
+            return Err(eyre!("transform `{name}` takes no argument"));
+        }
+        Ok(transform)
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transform::Lf => f.write_str("lf"),
+            Transform::Crlf => f.write_str("crlf"),
+            Transform::StripAnsi => f.write_str("strip-ansi"),
+            Transform::Trim => f.write_str("trim"),
+            Transform::TrimEnd => f.write_str("trim-end"),
+            Transform::Dedent => f.write_str("dedent"),
+            Transform::ExpandTabs(width) => write!(f, "expand-tabs={width}"),
+            Transform::Replace { spec, .. } => f.write_str(spec),
+        }
+    }
+}
+
+impl TryFrom<String> for Transform {
+    type Error = eyre::Report;
+
+    fn try_from(spec: String) -> Result<Self> {
+        spec.parse()
+    }
+}
+
+impl From<Transform> for String {
+    fn from(transform: Transform) -> Self {
+        transform.to_string()
+    }
+}
+
+/// Transform chains for the agent, applied to text only.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformConfig {
+    pub push: Vec<Transform>,
+    pub pull: Vec<Transform>,
+}
+
+/// How `pull --stdout` terminates printed text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum NewlineMode {
+    /// Always append a newline (default).
+    #[default]
+    Always,
+    /// Append a newline only if the text does not already end with one.
+    Auto,
+    Never,
+}
+
+impl NewlineMode {
+    pub fn terminator(self, text: &str) -> &'static str {
+        match self {
+            NewlineMode::Always => "\n",
+            NewlineMode::Auto if !text.ends_with('\n') => "\n",
+            NewlineMode::Auto | NewlineMode::Never => "",
+        }
+    }
+}
+
+pub fn apply_all(text: &str, transforms: &[Transform]) -> String {
+    transforms
+        .iter()
+        .fold(text.to_string(), |text, transform| transform.apply(&text))
+}
+
+/// Applies `transforms` to a text value; other content types are left untouched.
+/// Text that is not valid UTF-8 is also left alone so validation can report it.
+pub fn apply_to_value(value: &mut ClipboardValue, transforms: &[Transform]) {
+    if transforms.is_empty() || value.content_type != CONTENT_TYPE_TEXT {
+        return;
+    }
+    if let Ok(text) = std::str::from_utf8(&value.data) {
+        value.data = apply_all(text, transforms).into_bytes();
+    }
+}
+
+pub fn apply_to_response(response: &mut Response, transforms: &[Transform]) {
+    if let ResponseKind::Value { value } = &mut response.kind {
+        apply_to_value(value, transforms);
+    }
+}
+
+fn parse_replace(spec: &str, body: &str, delimiter: char) -> Result<Transform> {
+    let parts: Vec<&str> = body.split(delimiter).collect();
+    let [pattern, replacement, ""] = parts.as_slice() else {
+        return Err(eyre!(
+            "invalid replace transform `{spec}` (expected s{delimiter}pattern{delimiter}replacement{delimiter})"
+        ));
+    };
+    let regex =
+        Regex::new(pattern).map_err(|err| eyre!("invalid regex in transform `{spec}`: {err}"))?;
+    Ok(Transform::Replace {
+        spec: spec.to_string(),
+        regex,
+        replacement: replacement.to_string(),
+    })
+}
+
+fn dedent(text: &str) -> String {
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    if indent == 0 {
+        return text.to_string();
+    }
+    text.split_inclusive('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.trim_start_matches([' ', '\t'])
+            } else {
+                &line[indent..]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(specs: &[&str]) -> Vec<Transform> {
+        specs.iter().map(|spec| spec.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn line_endings_normalize_both_ways() {
+        assert_eq!(apply_all("a\r\nb\rc\n", &chain(&["lf"])), "a\nb\nc\n");
+        assert_eq!(apply_all("a\r\nb\n", &chain(&["crlf"])), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn strip_ansi_removes_colors_and_osc() {
+        let text = "\x1b[1;31merror\x1b[0m: \x1b]8;;http://x\x07link\x1b]8;;\x07";
+        assert_eq!(apply_all(text, &chain(&["strip-ansi"])), "error: link");
+    }
+
+    #[test]
+    fn dedent_keeps_relative_indent() {
+        let text = "    fn a() {\n        b();\n\n    }\n";
+        assert_eq!(
+            apply_all(text, &chain(&["dedent"])),
+            "fn a() {\n    b();\n\n}\n"
+        );
+    }
+
+    #[test]
+    fn chain_applies_in_order() {
+        let transforms = chain(&["expand-tabs=2", "s/(\\d+)/<$1>/", "trim-end"]);
+        assert_eq!(apply_all("\tv1 10\n\n", &transforms), "  v<1> <10>");
+    }
+
+    #[test]
+    fn replace_accepts_other_delimiters() {
+        let transforms = chain(&["s|/home/[^/]+|~|"]);
+        assert_eq!(apply_all("/home/alice/src", &transforms), "~/src");
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        assert!("upper".parse::<Transform>().is_err());
+        assert!("trim=1".parse::<Transform>().is_err());
+        assert!("s/a/b".parse::<Transform>().is_err());
+        assert!("s/(/b/".parse::<Transform>().is_err());
+        assert!("expand-tabs=x".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn transforms_round_trip_through_strings() {
+        for spec in ["lf", "strip-ansi", "expand-tabs=8", "s#a#b#"] {
+            let transform: Transform = spec.parse().unwrap();
+            assert_eq!(transform.to_string(), spec);
+        }
+    }
+
+    #[test]
+    fn only_text_values_are_transformed() {
+        let mut value = ClipboardValue {
+            content_type: crate::protocol::CONTENT_TYPE_PNG.to_string(),
+            data: b"  png  ".to_vec(),
+            created_at: 0,
+            origin: None,
+        };
+        apply_to_value(&mut value, &chain(&["trim"]));
+        assert_eq!(value.data, b"  png  ");
+    }
+
+    #[test]
+    fn newline_modes() {
+        assert_eq!(NewlineMode::Always.terminator("a\n"), "\n");
+        assert_eq!(NewlineMode::Auto.terminator("a\n"), "");
+        assert_eq!(NewlineMode::Auto.terminator("a"), "\n");
+        assert_eq!(NewlineMode::Never.terminator("a"), "");
+    }
+}
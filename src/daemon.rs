@@ -2,20 +2,24 @@ use crate::framing::{
     FramingError, decode_message, encode_message, read_frame_payload, write_frame_payload,
 };
 use crate::protocol::{
-    CONTENT_TYPE_PNG, CONTENT_TYPE_TEXT, ClipboardValue, ErrorCode, Request, RequestKind, Response,
-    ResponseKind,
+    CONTENT_TYPE_TEXT, ClipboardValue, DaemonStatus, ErrorCode, Request, RequestKind, Response,
+    ResponseKind, is_image_content_type,
 };
+use crate::server_config::ServerConfig;
 use eyre::{Result, WrapErr};
+use std::collections::VecDeque;
 use std::env;
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 use thiserror::Error;
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::Mutex;
-use tokio::time::{Duration, timeout};
-use tracing::{error, info};
+use tokio::sync::{Mutex, mpsc};
+use tokio::task::JoinSet;
+use tokio::time::{Duration, Instant, timeout};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Error)]
 pub enum DaemonError {
@@ -25,11 +29,281 @@ pub enum DaemonError {
     InvalidUtf8,
     #[error("payload too large")]
     PayloadTooLarge,
+    #[error("{}", .0.message)]
+    Policy(PolicyViolation),
+    #[error("not permitted")]
+    Forbidden,
 }
 
+const RECENT_SET_IDS: usize = 32;
+pub const DEFAULT_DRAIN_TIMEOUT_MS: u64 = 5000;
+
+mod access;
+mod audit;
+mod metrics;
+#[cfg(feature = "server-clipboard")]
+mod mirror;
+mod policy;
+mod status;
+mod systemd;
+
+pub use access::Grant;
+use access::{Access, AccessList};
+use audit::AuditLog;
+pub use audit::{
+    AUDIT_KEEP_FILES, AuditRecord, DEFAULT_AUDIT_MAX_BYTES, default_audit_path, rotated_path,
+};
+pub use metrics::MetricsListen;
+pub use policy::Policy;
+use policy::{PolicyRules, PolicyViolation};
+use status::DaemonStats;
+
 #[derive(Debug, Clone)]
+pub struct DaemonConfig {
+    pub socket_path: PathBuf,
+    pub max_size: usize,
+    pub io_timeout_ms: u64,
+    /// Poll interval for mirroring to the server's desktop clipboard; `None` disables it.
+    pub mirror_interval_ms: Option<u64>,
+    /// Content policy file; without one only the built-in checks apply.
+    pub policy_path: Option<PathBuf>,
+    /// Accept peers other than the owner according to `grants`, and record who
+    /// stored each value.
+    pub shared: bool,
+    pub grants: Vec<Grant>,
+    /// JSON-lines audit log of every request; `None` disables auditing.
+    pub audit_path: Option<PathBuf>,
+    pub audit_max_bytes: u64,
+    /// OpenMetrics endpoint on a loopback port or UNIX socket; `None` disables it.
+    pub metrics_listen: Option<MetricsListen>,
+    /// How long connections in flight at SIGTERM/SIGINT may take to finish.
+    pub drain_timeout_ms: u64,
+    /// Exit after this long without connections, but only while no value is stored
+    /// (values are kept in memory only). Meant for socket activation.
+    pub idle_timeout_secs: Option<u64>,
+    /// Settings given as flags. When set, SIGHUP re-reads the server config files
+    /// and applies them underneath these.
+    pub config_flags: Option<ServerConfig>,
+}
+
+/// Per-daemon settings and counters every connection needs.
+#[derive(Debug)]
+struct DaemonContext {
+    socket_path: PathBuf,
+    /// Limits and policy path may change on SIGHUP.
+    max_size: AtomicUsize,
+    io_timeout_ms: AtomicU64,
+    started: Instant,
+    /// Re-read from `policy_path` on SIGHUP.
+    policy: RwLock<Arc<Policy>>,
+    policy_path: RwLock<Option<PathBuf>>,
+    config_flags: Option<ServerConfig>,
+    access: AccessList,
+    audit: Option<AuditLog>,
+    stats: DaemonStats,
+}
+
+impl DaemonContext {
+    fn policy(&self) -> Arc<Policy> {
+        match self.policy.read() {
+            Ok(policy) => Arc::clone(&policy),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    fn max_size(&self) -> usize {
+        self.max_size.load(Ordering::Relaxed)
+    }
+
+    fn io_timeout(&self) -> Duration {
+        Duration::from_millis(self.io_timeout_ms.load(Ordering::Relaxed))
+    }
+
+    /// The checks a `Set` from the daemon's owner goes through; returns the TTL
+    /// the policy assigns.
+    #[cfg(feature = "server-clipboard")]
+    fn check_owner_set(
+        &self,
+        value: &ClipboardValue,
+    ) -> std::result::Result<Option<Duration>, DaemonError> {
+        validate_set(value, self.max_size())?;
+        let owner = access::user_name(get_uid());
+        self.policy()
+            .rules_for(owner.as_deref())
+            .check(value)
+            .map_err(DaemonError::Policy)
+    }
+
+    fn policy_path(&self) -> Option<PathBuf> {
+        match self.policy_path.read() {
+            Ok(path) => path.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Re-reads the server config files (when the daemon uses them) and the policy
+    /// file. A file that no longer loads keeps the current settings in place.
+    fn reload(&self) {
+        systemd::notify("RELOADING=1");
+        if let Some(flags) = &self.config_flags {
+            match ServerConfig::load() {
+                Ok(files) => self.apply_config(&files.overlay(flags)),
+                Err(err) => {
+                    error!(error = %err, "server config reload failed; keeping the current settings");
+                }
+            }
+        }
+        match &self.policy_path() {
+            Some(path) => match Policy::load(path) {
+                Ok(policy) => {
+                    if let Ok(mut current) = self.policy.write() {
+                        *current = Arc::new(policy);
+                    }
+                    info!(path = %path.display(), "reloaded content policy");
+                }
+                Err(err) => {
+                    error!(error = %err, path = %path.display(), "policy reload failed; keeping the current policy");
+                }
+            },
+            None => {
+                if let Ok(mut current) = self.policy.write() {
+                    *current = Arc::new(Policy::default());
+                }
+                info!("reload requested; no policy file configured");
+            }
+        }
+        systemd::notify("READY=1");
+    }
+
+    fn apply_config(&self, config: &ServerConfig) {
+        if config
+            .socket_path
+            .as_ref()
+            .is_some_and(|path| *path != self.socket_path)
+        {
+            warn!("socket_path changes take effect after a restart");
+        }
+        let (max_size, io_timeout_ms) = (
+            config.effective_max_size(),
+            config.effective_io_timeout_ms(),
+        );
+        self.max_size.store(max_size, Ordering::Relaxed);
+        self.io_timeout_ms.store(io_timeout_ms, Ordering::Relaxed);
+        if let Ok(mut path) = self.policy_path.write() {
+            path.clone_from(&config.policy);
+        }
+        info!(max_size, io_timeout_ms, "reloaded server config");
+    }
+
+    fn status(&self, stored_bytes: Option<u64>) -> DaemonStatus {
+        let (requests, errors) = self.stats.snapshot();
+        DaemonStatus {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: crate::protocol::VERSION,
+            pid: std::process::id(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            socket_path: self.socket_path.display().to_string(),
+            max_size: self.max_size() as u64,
+            io_timeout_ms: self.io_timeout_ms.load(Ordering::Relaxed),
+            stored_values: stored_bytes.map_or(0, |_| 1),
+            stored_bytes: stored_bytes.unwrap_or(0),
+            rss_bytes: status::rss_bytes(),
+            requests,
+            errors,
+        }
+    }
+}
+
+/// What the signal handlers ask the accept loop to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Reload,
+    Shutdown,
+}
+
+/// `SO_PEERCRED` of a connected client.
+#[derive(Debug, Clone, Copy)]
+struct PeerCred {
+    uid: u32,
+    gid: u32,
+    pid: i32,
+}
+
+/// What the current peer may do, resolved when it connects.
+struct Session<'a> {
+    /// Peer uid; `Set` request ids are deduplicated per peer.
+    uid: u32,
+    rules: &'a PolicyRules,
+    access: Access,
+    /// Recorded with stored values in shared mode.
+    author: Option<String>,
+}
+
+impl<'a> Session<'a> {
+    #[cfg(test)]
+    fn owner(rules: &'a PolicyRules) -> Self {
+        Self {
+            uid: get_uid(),
+            rules,
+            access: Access::FULL,
+            author: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 struct ClipboardState {
     value: Option<ClipboardValue>,
+    /// `(peer uid, request id)` of recent sets.
+    recent_set_ids: VecDeque<(u32, u64)>,
+    /// Bumped on every stored value so observers can detect changes.
+    generation: u64,
+    /// Set when the policy limits how long the current value may be served.
+    expires_at: Option<Instant>,
+    /// Who stored the current value (shared mode only).
+    author: Option<String>,
+}
+
+impl ClipboardState {
+    fn set_value(&mut self, value: ClipboardValue) {
+        self.value = Some(value);
+        self.expires_at = None;
+        self.author = None;
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    fn store(&mut self, value: ClipboardValue, ttl: Option<Duration>, author: Option<String>) {
+        self.set_value(value);
+        self.expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.author = author;
+    }
+
+    /// The stored value, dropping it first if its TTL has passed.
+    fn current(&mut self) -> Option<&ClipboardValue> {
+        if self
+            .expires_at
+            .is_some_and(|expires_at| Instant::now() >= expires_at)
+        {
+            self.value = None;
+            self.expires_at = None;
+            self.author = None;
+            self.generation = self.generation.wrapping_add(1);
+        }
+        self.value.as_ref()
+    }
+
+    /// Records a `Set` request id; returns false if it was already applied, so a
+    /// client retry does not overwrite a newer value.
+    fn remember_set(&mut self, uid: u32, request_id: u64) -> bool {
+        if request_id != 0 && self.recent_set_ids.contains(&(uid, request_id)) {
+            return false;
+        }
+        if self.recent_set_ids.len() == RECENT_SET_IDS {
+            self.recent_set_ids.pop_front();
+        }
+        self.recent_set_ids.push_back((uid, request_id));
+        true
+    }
 }
 
 pub fn default_socket_path() -> Result<PathBuf> {
@@ -52,32 +326,236 @@ fn get_uid() -> u32 {
     unsafe { libc::getuid() }
 }
 
-pub async fn run_daemon(socket_path: PathBuf, max_size: usize, io_timeout_ms: u64) -> Result<()> {
-    prepare_socket_path(&socket_path)?;
+pub async fn run_daemon(config: DaemonConfig) -> Result<()> {
+    let DaemonConfig {
+        socket_path,
+        max_size,
+        io_timeout_ms,
+        mirror_interval_ms,
+        policy_path,
+        shared,
+        grants,
+        audit_path,
+        audit_max_bytes,
+        metrics_listen,
+        drain_timeout_ms,
+        idle_timeout_secs,
+        config_flags,
+    } = config;
+    let policy = match &policy_path {
+        Some(path) => {
+            let policy = Policy::load(path)?;
+            info!(path = %path.display(), "loaded content policy");
+            policy
+        }
+        None => Policy::default(),
+    };
+    let access = if shared {
+        AccessList::shared(get_uid(), &grants)?
+    } else {
+        AccessList::owner_only(get_uid())
+    };
+    let audit = audit_path
+        .map(|path| AuditLog::open(&path, audit_max_bytes))
+        .transpose()?;
+    if let Some(audit) = &audit {
+        info!(path = %audit.path().display(), "writing audit log");
+    }
+    let context = Arc::new(DaemonContext {
+        socket_path: socket_path.clone(),
+        max_size: AtomicUsize::new(max_size),
+        io_timeout_ms: AtomicU64::new(io_timeout_ms),
+        started: Instant::now(),
+        policy: RwLock::new(Arc::new(policy)),
+        policy_path: RwLock::new(policy_path),
+        config_flags,
+        access,
+        audit,
+        stats: DaemonStats::default(),
+    });
+
+    // Under socket activation systemd owns the socket file and its permissions.
+    let (listener, activated) = match systemd::listen_fd()? {
+        Some(listener) => (UnixListener::from_std(listener)?, true),
+        None => (bind_socket(&socket_path, shared)?, false),
+    };
+    info!(path = %socket_path.display(), shared, activated, "daemon listening");
+
+    let state = Arc::new(Mutex::new(ClipboardState::default()));
+    if let Some(interval_ms) = mirror_interval_ms {
+        start_mirror(Arc::clone(&state), Arc::clone(&context), interval_ms)?;
+    }
+    if let Some(listen) = &metrics_listen {
+        metrics::spawn(listen, Arc::clone(&state), Arc::clone(&context)).await?;
+    }
+
+    let (control_tx, control) = mpsc::channel(4);
+    forward_signals(control_tx)?;
+    if let Some(interval) = systemd::watchdog_interval() {
+        tokio::spawn(async move {
+            loop {
+                systemd::notify("WATCHDOG=1");
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+    systemd::notify(&format!(
+        "READY=1\nSTATUS=listening on {}",
+        socket_path.display()
+    ));
+
+    let served = serve(
+        listener,
+        state,
+        context,
+        control,
+        Duration::from_millis(drain_timeout_ms),
+        idle_timeout_secs.map(Duration::from_secs),
+    )
+    .await;
+    if !activated {
+        remove_socket(&socket_path);
+    }
+    if let Some(MetricsListen::Unix(path)) = &metrics_listen {
+        remove_socket(path);
+    }
+    served
+}
+
+fn bind_socket(socket_path: &Path, shared: bool) -> Result<UnixListener> {
+    prepare_socket_path(socket_path, shared)?;
     let old_umask = set_umask();
-    let listener = UnixListener::bind(&socket_path);
+    let listener = UnixListener::bind(socket_path);
     unsafe { libc::umask(old_umask) };
     let listener = listener.wrap_err("bind unix socket")?;
-    std::fs::set_permissions(&socket_path, std::fs::Permissions::from_mode(0o600))?;
-    info!(path = %socket_path.display(), "daemon listening");
-
-    let state = Arc::new(Mutex::new(ClipboardState { value: None }));
+    // In shared mode the peer ACL, not file permissions, decides who gets in.
+    let socket_mode = if shared { 0o666 } else { 0o600 };
+    std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(socket_mode))?;
+    Ok(listener)
+}
 
+/// Accepts connections until told to shut down (or idle with nothing stored, when
+/// `idle_timeout` is set), then gives the ones in flight up to `drain_timeout` to
+/// finish.
+async fn serve(
+    listener: UnixListener,
+    state: Arc<Mutex<ClipboardState>>,
+    context: Arc<DaemonContext>,
+    mut control: mpsc::Receiver<Control>,
+    drain_timeout: Duration,
+    idle_timeout: Option<Duration>,
+) -> Result<()> {
+    let mut connections = JoinSet::new();
+    let mut last_activity = Instant::now();
     loop {
-        let (stream, _) = listener.accept().await?;
-        let state = Arc::clone(&state);
-        tokio::spawn(async move {
-            if let Err(err) = handle_connection(stream, state, max_size, io_timeout_ms).await {
-                error!(error = %err, "connection error");
+        let idle_deadline = last_activity + idle_timeout.unwrap_or_default();
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                last_activity = Instant::now();
+                let state = Arc::clone(&state);
+                let context = Arc::clone(&context);
+                connections.spawn(async move {
+                    if let Err(err) = handle_connection(stream, state, context).await {
+                        error!(error = %err, "connection error");
+                    }
+                });
             }
-        });
+            Some(_) = connections.join_next(), if !connections.is_empty() => {
+                last_activity = Instant::now();
+            }
+            () = tokio::time::sleep_until(idle_deadline),
+                if idle_timeout.is_some() && connections.is_empty() =>
+            {
+                if state.lock().await.current().is_none() {
+                    info!("idle timeout reached with nothing stored");
+                    break;
+                }
+                last_activity = Instant::now();
+            }
+            message = control.recv() => match message {
+                Some(Control::Reload) => context.reload(),
+                Some(Control::Shutdown) | None => break,
+            },
+        }
+    }
+
+    drop(listener);
+    systemd::notify("STOPPING=1");
+    info!(in_flight = connections.len(), "daemon shutting down");
+    let drained = timeout(drain_timeout, async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        warn!(
+            remaining = connections.len(),
+            "drain timeout reached; closing remaining connections"
+        );
+        connections.shutdown().await;
     }
+    Ok(())
 }
 
-fn prepare_socket_path(path: &Path) -> Result<()> {
+/// SIGTERM and SIGINT shut the daemon down; SIGHUP reloads the policy file.
+fn forward_signals(control: mpsc::Sender<Control>) -> Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate()).wrap_err("install SIGTERM handler")?;
+    let mut interrupt = signal(SignalKind::interrupt()).wrap_err("install SIGINT handler")?;
+    let mut hangup = signal(SignalKind::hangup()).wrap_err("install SIGHUP handler")?;
+    tokio::spawn(async move {
+        loop {
+            let message = tokio::select! {
+                _ = terminate.recv() => Control::Shutdown,
+                _ = interrupt.recv() => Control::Shutdown,
+                _ = hangup.recv() => Control::Reload,
+            };
+            if control.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+fn remove_socket(path: &Path) {
+    if let Err(err) = std::fs::remove_file(path)
+        && err.kind() != std::io::ErrorKind::NotFound
+    {
+        warn!(error = %err, path = %path.display(), "failed to remove socket");
+    }
+}
+
+#[cfg(feature = "server-clipboard")]
+fn start_mirror(
+    state: Arc<Mutex<ClipboardState>>,
+    context: Arc<DaemonContext>,
+    interval_ms: u64,
+) -> Result<()> {
+    mirror::spawn(state, context, Duration::from_millis(interval_ms.max(50)))?;
+    info!(interval_ms, "mirroring the desktop clipboard");
+    Ok(())
+}
+
+#[cfg(not(feature = "server-clipboard"))]
+fn start_mirror(
+    _state: Arc<Mutex<ClipboardState>>,
+    _context: Arc<DaemonContext>,
+    _interval_ms: u64,
+) -> Result<()> {
+    Err(eyre::eyre!(
+        "clipboard mirroring requires a build with the `server-clipboard` feature"
+    ))
+}
+
+/// Shared daemons need other users to reach the socket, so the directory is made
+/// traversable (but not listable) for them.
+fn prepare_socket_path(path: &Path, shared: bool) -> Result<()> {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
-        std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))?;
+        let mode = if shared { 0o711 } else { 0o700 };
+        std::fs::set_permissions(parent, std::fs::Permissions::from_mode(mode))?;
     }
 
     if path.exists() {
@@ -94,77 +572,128 @@ fn set_umask() -> libc::mode_t {
 async fn handle_connection(
     mut stream: UnixStream,
     state: Arc<Mutex<ClipboardState>>,
-    max_size: usize,
-    io_timeout_ms: u64,
+    context: Arc<DaemonContext>,
 ) -> Result<()> {
-    if let Err(err) = verify_peer_credentials(&stream) {
-        let response = Response {
-            request_id: 0,
-            kind: ResponseKind::Error {
-                code: ErrorCode::InvalidRequest,
-                message: format!("peer credential check failed: {err}"),
-            },
-        };
-        let payload = encode_message(&response)?;
-        let _ = write_frame_payload(&mut stream, &payload).await;
-        return Ok(());
+    let peer = peer_cred(&stream);
+    let mut record = context.audit.as_ref().map(|_| {
+        let peer = peer.as_ref().ok();
+        AuditRecord::new(peer.map(|peer| peer.uid), peer.map(|peer| peer.pid))
+    });
+    let response = serve_connection(&mut stream, peer, state, &context, &mut record).await;
+    if let (Some(audit), Some(record)) = (&context.audit, record) {
+        audit.append(&record.finish(&response));
     }
+    let payload = encode_message(&response)?;
+    context.stats.count_response(&response, payload.len());
+    timeout(
+        context.io_timeout(),
+        write_frame_payload(&mut stream, &payload),
+    )
+    .await??;
+    Ok(())
+}
+
+/// Reads and answers one request; every failure becomes an error response so the
+/// caller can audit and send it the same way.
+async fn serve_connection(
+    stream: &mut UnixStream,
+    peer: Result<PeerCred>,
+    state: Arc<Mutex<ClipboardState>>,
+    context: &DaemonContext,
+    record: &mut Option<AuditRecord>,
+) -> Response {
+    let error = |code, message| Response {
+        request_id: 0,
+        kind: ResponseKind::Error { code, message },
+    };
+    let peer = match peer {
+        Ok(peer) => peer,
+        Err(err) => {
+            context.stats.count_peer_rejection();
+            return error(
+                ErrorCode::InvalidRequest,
+                format!("peer credential check failed: {err}"),
+            );
+        }
+    };
+    let access = match verify_peer_credentials(&peer, &context.access) {
+        Ok(access) => access,
+        Err((code, message)) => {
+            context.stats.count_peer_rejection();
+            return error(code, message);
+        }
+    };
+    let user = access::user_name(peer.uid);
+    let policy = context.policy();
+    let session = Session {
+        uid: peer.uid,
+        rules: policy.rules_for(user.as_deref()),
+        access,
+        author: context
+            .access
+            .is_shared()
+            .then(|| user.clone().unwrap_or_else(|| peer.uid.to_string())),
+    };
 
     let payload = match timeout(
-        Duration::from_millis(io_timeout_ms),
-        read_frame_payload(&mut stream, max_size),
+        context.io_timeout(),
+        read_frame_payload(stream, context.max_size()),
     )
     .await
     {
         Ok(Ok(payload)) => payload,
         Ok(Err(err)) => {
-            let response = framing_error_response(&err, 0);
-            let payload = encode_message(&response)?;
-            let _ = write_frame_payload(&mut stream, &payload).await;
-            return Ok(());
+            context
+                .stats
+                .count_framing_error(framing_error_reason(&err));
+            return framing_error_response(&err, 0);
         }
         Err(_) => {
-            let response = Response {
-                request_id: 0,
-                kind: ResponseKind::Error {
-                    code: ErrorCode::Internal,
-                    message: "read timeout".to_string(),
-                },
-            };
-            let payload = encode_message(&response)?;
-            let _ = write_frame_payload(&mut stream, &payload).await;
-            return Ok(());
+            context.stats.count_framing_error("timeout");
+            return error(ErrorCode::Internal, "read timeout".to_string());
         }
     };
-    let response = match decode_message::<Request>(&payload) {
-        Ok(request) => handle_request(request, state, max_size).await,
-        Err(err) => Response {
-            request_id: 0,
-            kind: ResponseKind::Error {
-                code: ErrorCode::InvalidRequest,
-                message: format!("decode error: {err}"),
-            },
-        },
-    };
-    let payload = encode_message(&response)?;
-    timeout(
-        Duration::from_millis(io_timeout_ms),
-        write_frame_payload(&mut stream, &payload),
-    )
-    .await??;
-    Ok(())
+    match decode_message::<Request>(&payload) {
+        Ok(request) => {
+            let kind = request.kind.name();
+            context.stats.count_request(&request.kind, payload.len());
+            if let Some(record) = record {
+                record.note_request(&request);
+            }
+            let started = Instant::now();
+            let response = handle_request(request, state, context, &session).await;
+            context.stats.observe_duration(kind, started.elapsed());
+            response
+        }
+        Err(err) => {
+            context.stats.count_framing_error("decode");
+            error(ErrorCode::InvalidRequest, format!("decode error: {err}"))
+        }
+    }
 }
 
 async fn handle_request(
     request: Request,
     state: Arc<Mutex<ClipboardState>>,
-    max_size: usize,
+    context: &DaemonContext,
+    session: &Session<'_>,
 ) -> Response {
     let request_id = request.request_id;
+    let permitted = match request.kind {
+        RequestKind::Get | RequestKind::PeekMeta => session.access.read,
+        RequestKind::Set { .. } => session.access.write,
+        RequestKind::Status => session.access.read || session.access.write,
+    };
+    if !permitted {
+        return Response {
+            request_id,
+            kind: to_error_response(DaemonError::Forbidden),
+        };
+    }
     let kind = match request.kind {
         RequestKind::Get => {
-            let state = state.lock().await;
-            match &state.value {
+            let mut state = state.lock().await;
+            match state.current() {
                 Some(value) => ResponseKind::Value {
                     value: value.clone(),
                 },
@@ -172,20 +701,36 @@ async fn handle_request(
             }
         }
         RequestKind::PeekMeta => {
-            let state = state.lock().await;
-            match &state.value {
+            let mut state = state.lock().await;
+            let author = state.author.clone();
+            match state.current() {
                 Some(value) => ResponseKind::Meta {
                     content_type: value.content_type.clone(),
                     size: value.data.len() as u64,
                     created_at: value.created_at,
+                    author,
+                    origin: value.origin.clone(),
                 },
                 None => ResponseKind::Empty,
             }
         }
-        RequestKind::Set { value } => match validate_set(&value, max_size) {
-            Ok(()) => {
+        RequestKind::Status => {
+            let mut state = state.lock().await;
+            let stored_bytes = state.current().map(|value| value.data.len() as u64);
+            ResponseKind::Status {
+                status: context.status(stored_bytes),
+            }
+        }
+        RequestKind::Set { value } => match validate_set(&value, context.max_size())
+            .and_then(|()| session.rules.check(&value).map_err(DaemonError::Policy))
+        {
+            Ok(ttl) => {
                 let mut state = state.lock().await;
-                state.value = Some(value);
+                if state.remember_set(session.uid, request_id) {
+                    state.store(value, ttl, session.author.clone());
+                } else {
+                    info!(request_id, "ignoring duplicate set request");
+                }
                 ResponseKind::Ok
             }
             Err(err) => to_error_response(err),
@@ -195,7 +740,7 @@ async fn handle_request(
 }
 
 fn validate_set(value: &ClipboardValue, max_size: usize) -> std::result::Result<(), DaemonError> {
-    if value.content_type != CONTENT_TYPE_TEXT && value.content_type != CONTENT_TYPE_PNG {
+    if value.content_type != CONTENT_TYPE_TEXT && !is_image_content_type(&value.content_type) {
         return Err(DaemonError::InvalidContentType);
     }
     if value.data.len() > max_size {
@@ -221,6 +766,27 @@ fn to_error_response(err: DaemonError) -> ResponseKind {
             code: ErrorCode::PayloadTooLarge,
             message: "payload too large".to_string(),
         },
+        DaemonError::Forbidden => ResponseKind::Error {
+            code: ErrorCode::Forbidden,
+            message: "operation not permitted for this user".to_string(),
+        },
+        DaemonError::Policy(violation) => {
+            info!(reason = %violation.message, "set rejected by policy");
+            ResponseKind::Error {
+                code: violation.code,
+                message: violation.message,
+            }
+        }
+    }
+}
+
+/// Metric label for a frame that could not be read.
+fn framing_error_reason(err: &eyre::Report) -> &'static str {
+    match err.downcast_ref::<FramingError>() {
+        Some(FramingError::InvalidMagic | FramingError::MagicNotFound) => "invalid_magic",
+        Some(FramingError::UnsupportedVersion(_)) => "unsupported_version",
+        Some(FramingError::PayloadTooLarge(_)) => "payload_too_large",
+        None => "io",
     }
 }
 
@@ -260,22 +826,33 @@ fn framing_error_response(err: &eyre::Report, request_id: u64) -> Response {
     }
 }
 
-fn verify_peer_credentials(stream: &UnixStream) -> Result<()> {
-    let expected = get_uid();
-    let actual = peer_uid(stream)?;
-    if !peer_uid_matches(actual, expected) {
-        return Err(eyre::eyre!(
-            "peer uid mismatch (expected {expected}, got {actual})"
-        ));
+/// Returns what the peer may do, or the error to send back.
+fn verify_peer_credentials(
+    peer: &PeerCred,
+    acl: &AccessList,
+) -> std::result::Result<Access, (ErrorCode, String)> {
+    let uid = peer.uid;
+    match acl.access_for(uid, peer.gid) {
+        Some(access) => Ok(access),
+        None if !acl.is_shared() && !peer_uid_matches(uid, get_uid()) => Err((
+            ErrorCode::InvalidRequest,
+            format!(
+                "peer credential check failed: peer uid mismatch (expected {}, got {uid})",
+                get_uid()
+            ),
+        )),
+        None => Err((
+            ErrorCode::Forbidden,
+            format!("uid {uid} is not allowed to use this clipboard"),
+        )),
     }
-    Ok(())
 }
 
 fn peer_uid_matches(actual: u32, expected: u32) -> bool {
     actual == expected
 }
 
-fn peer_uid(stream: &UnixStream) -> Result<u32> {
+fn peer_cred(stream: &UnixStream) -> Result<PeerCred> {
     let fd = stream.as_raw_fd();
     let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
     let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
@@ -291,7 +868,11 @@ fn peer_uid(stream: &UnixStream) -> Result<u32> {
     if ret != 0 {
         return Err(eyre::eyre!("getsockopt SO_PEERCRED failed"));
     }
-    Ok(cred.uid)
+    Ok(PeerCred {
+        uid: cred.uid,
+        gid: cred.gid,
+        pid: cred.pid,
+    })
 }
 
 #[cfg(test)]
@@ -302,18 +883,40 @@ mod tests {
     use tokio::net::UnixListener;
     use tokio::time::Duration;
 
+    /// A 1024-byte limit and a 10ms I/O timeout.
+    pub(super) fn owner_context() -> Arc<DaemonContext> {
+        owner_context_with_timeout(10)
+    }
+
+    fn owner_context_with_timeout(io_timeout_ms: u64) -> Arc<DaemonContext> {
+        Arc::new(DaemonContext {
+            socket_path: PathBuf::from("/tmp/daemon.sock"),
+            max_size: AtomicUsize::new(1024),
+            io_timeout_ms: AtomicU64::new(io_timeout_ms),
+            started: Instant::now(),
+            policy: RwLock::new(Arc::new(Policy::default())),
+            policy_path: RwLock::new(None),
+            config_flags: None,
+            access: AccessList::owner_only(get_uid()),
+            audit: None,
+            stats: DaemonStats::default(),
+        })
+    }
+
     #[tokio::test]
     async fn read_timeout_returns_error_response() {
         let dir = tempfile::tempdir().unwrap();
         let socket_path = dir.path().join("daemon.sock");
         let listener = UnixListener::bind(&socket_path).unwrap();
-        let state = Arc::new(Mutex::new(ClipboardState { value: None }));
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
 
         let server = tokio::spawn({
             let state = Arc::clone(&state);
             async move {
                 let (stream, _) = listener.accept().await.unwrap();
-                handle_connection(stream, state, 1024, 10).await.unwrap();
+                handle_connection(stream, state, owner_context())
+                    .await
+                    .unwrap();
             }
         });
 
@@ -343,7 +946,7 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         let socket_path = dir.path().join("runtime").join("daemon.sock");
 
-        prepare_socket_path(&socket_path).unwrap();
+        prepare_socket_path(&socket_path, false).unwrap();
 
         let parent = socket_path.parent().unwrap();
         let mode = std::fs::metadata(parent).unwrap().permissions().mode() & 0o777;
@@ -356,6 +959,7 @@ mod tests {
             content_type: CONTENT_TYPE_TEXT.to_string(),
             data: vec![0xff, 0xfe],
             created_at: 0,
+            origin: None,
         };
         let err = validate_set(&value, 1024).unwrap_err();
         assert!(matches!(err, DaemonError::InvalidUtf8));
@@ -367,17 +971,30 @@ mod tests {
             content_type: "application/octet-stream".to_string(),
             data: vec![1, 2, 3],
             created_at: 0,
+            origin: None,
         };
         let err = validate_set(&value, 1024).unwrap_err();
         assert!(matches!(err, DaemonError::InvalidContentType));
     }
 
+    #[tokio::test]
+    async fn validate_set_accepts_jpeg() {
+        let value = ClipboardValue {
+            content_type: crate::protocol::CONTENT_TYPE_JPEG.to_string(),
+            data: vec![1, 2, 3],
+            created_at: 0,
+            origin: None,
+        };
+        assert!(validate_set(&value, 1024).is_ok());
+    }
+
     #[tokio::test]
     async fn validate_set_rejects_oversize() {
         let value = ClipboardValue {
             content_type: CONTENT_TYPE_TEXT.to_string(),
             data: vec![b'a'; 5],
             created_at: 0,
+            origin: None,
         };
         let err = validate_set(&value, 4).unwrap_err();
         assert!(matches!(err, DaemonError::PayloadTooLarge));
@@ -385,15 +1002,368 @@ mod tests {
 
     #[tokio::test]
     async fn handle_request_preserves_request_id() {
-        let state = Arc::new(Mutex::new(ClipboardState { value: None }));
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
         let request = Request {
             request_id: 7,
+            ssh_connection: None,
             kind: RequestKind::Get,
         };
-        let response = handle_request(request, state, 1024).await;
+        let response = handle_request(
+            request,
+            state,
+            &owner_context(),
+            &Session::owner(&PolicyRules::default()),
+        )
+        .await;
         assert_eq!(response.request_id, 7);
     }
 
+    #[tokio::test]
+    async fn duplicate_set_request_id_does_not_overwrite() {
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
+        let set = |request_id: u64, text: &str| Request {
+            request_id,
+            ssh_connection: None,
+            kind: RequestKind::Set {
+                value: ClipboardValue {
+                    content_type: CONTENT_TYPE_TEXT.to_string(),
+                    data: text.as_bytes().to_vec(),
+                    created_at: 0,
+                    origin: None,
+                },
+            },
+        };
+
+        let rules = PolicyRules::default();
+        let session = Session::owner(&rules);
+        handle_request(
+            set(1, "first"),
+            Arc::clone(&state),
+            &owner_context(),
+            &session,
+        )
+        .await;
+        handle_request(
+            set(2, "second"),
+            Arc::clone(&state),
+            &owner_context(),
+            &session,
+        )
+        .await;
+        let response = handle_request(
+            set(1, "first"),
+            Arc::clone(&state),
+            &owner_context(),
+            &session,
+        )
+        .await;
+
+        assert!(matches!(response.kind, ResponseKind::Ok));
+        assert_eq!(state.lock().await.value.as_ref().unwrap().data, b"second");
+
+        // Another peer may pick the same id; it is not a retry of ours.
+        let other = Session {
+            uid: session.uid.wrapping_add(1),
+            ..Session::owner(&rules)
+        };
+        handle_request(
+            set(1, "first"),
+            Arc::clone(&state),
+            &owner_context(),
+            &other,
+        )
+        .await;
+        assert_eq!(state.lock().await.value.as_ref().unwrap().data, b"first");
+    }
+
+    #[tokio::test]
+    async fn policy_rejection_and_ttl() {
+        let policy = Policy::parse(
+            "[default]\nallowed_content_types = [\"text/plain\"]\n[default.ttl_secs]\n\"text/plain\" = 60\n",
+        )
+        .unwrap();
+        let session = Session::owner(policy.rules_for(None));
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
+        let set = |content_type: &str| Request {
+            request_id: 0,
+            ssh_connection: None,
+            kind: RequestKind::Set {
+                value: ClipboardValue {
+                    content_type: content_type.to_string(),
+                    data: b"x".to_vec(),
+                    created_at: 0,
+                    origin: None,
+                },
+            },
+        };
+
+        let response = handle_request(
+            set(crate::protocol::CONTENT_TYPE_PNG),
+            Arc::clone(&state),
+            &owner_context(),
+            &session,
+        )
+        .await;
+        assert!(matches!(
+            response.kind,
+            ResponseKind::Error {
+                code: ErrorCode::ContentTypeNotAllowed { .. },
+                ..
+            }
+        ));
+
+        handle_request(
+            set(CONTENT_TYPE_TEXT),
+            Arc::clone(&state),
+            &owner_context(),
+            &session,
+        )
+        .await;
+        let mut state = state.lock().await;
+        assert!(state.current().is_some());
+        state.expires_at = Some(Instant::now());
+        assert!(state.current().is_none());
+    }
+
+    #[tokio::test]
+    async fn status_reports_stored_value_and_counters() {
+        let context = owner_context();
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
+        let rules = PolicyRules::default();
+        let session = Session::owner(&rules);
+        let request = |kind| Request {
+            request_id: 1,
+            ssh_connection: None,
+            kind,
+        };
+        let set = request(RequestKind::Set {
+            value: ClipboardValue {
+                content_type: CONTENT_TYPE_TEXT.to_string(),
+                data: b"hello".to_vec(),
+                created_at: 0,
+                origin: None,
+            },
+        });
+        context.stats.count_request(&set.kind, 0);
+        let response = handle_request(set, Arc::clone(&state), &context, &session).await;
+        context.stats.count_response(&response, 0);
+        context.stats.count_response(
+            &Response {
+                request_id: 2,
+                kind: to_error_response(DaemonError::PayloadTooLarge),
+            },
+            0,
+        );
+
+        let response =
+            handle_request(request(RequestKind::Status), state, &context, &session).await;
+        match response.kind {
+            ResponseKind::Status { status } => {
+                assert_eq!(status.version, env!("CARGO_PKG_VERSION"));
+                assert_eq!(status.pid, std::process::id());
+                assert_eq!((status.stored_values, status.stored_bytes), (1, 5));
+                assert_eq!(status.max_size, 1024);
+                assert_eq!(status.requests[0].name, "set");
+                assert_eq!(status.errors[0].name, "payload_too_large");
+                assert!(status.rss_bytes.is_some());
+            }
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_drains_connections_in_flight() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("daemon.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
+        let (control_tx, control) = mpsc::channel(4);
+        let server = tokio::spawn(serve(
+            listener,
+            state,
+            owner_context_with_timeout(2000),
+            control,
+            Duration::from_secs(2),
+            None,
+        ));
+
+        let mut client = UnixStream::connect(&socket_path).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        control_tx.send(Control::Shutdown).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(UnixStream::connect(&socket_path).await.is_err());
+
+        let get = Request {
+            request_id: 3,
+            ssh_connection: None,
+            kind: RequestKind::Get,
+        };
+        write_frame_payload(&mut client, &encode_message(&get).unwrap())
+            .await
+            .unwrap();
+        let response: Response =
+            decode_message(&read_frame_payload(&mut client, 2048).await.unwrap()).unwrap();
+        assert!(matches!(response.kind, ResponseKind::Empty));
+        tokio::time::timeout(Duration::from_secs(1), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn idle_timeout_exits_only_when_nothing_is_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
+        let serve_idle = |name: &str, state: Arc<Mutex<ClipboardState>>| {
+            let listener = UnixListener::bind(dir.path().join(name)).unwrap();
+            let (control_tx, control) = mpsc::channel(4);
+            let server = tokio::spawn(serve(
+                listener,
+                state,
+                owner_context(),
+                control,
+                Duration::from_secs(1),
+                Some(Duration::from_millis(20)),
+            ));
+            (server, control_tx)
+        };
+
+        let (server, _control) = serve_idle("empty.sock", Arc::clone(&state));
+        tokio::time::timeout(Duration::from_secs(1), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        state.lock().await.set_value(ClipboardValue {
+            content_type: CONTENT_TYPE_TEXT.to_string(),
+            data: b"keep".to_vec(),
+            created_at: 0,
+            origin: None,
+        });
+        let (server, _control) = serve_idle("stored.sock", state);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!server.is_finished());
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn metrics_endpoint_reports_handled_connections() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("daemon.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
+        let context = owner_context();
+
+        let server = tokio::spawn({
+            let state = Arc::clone(&state);
+            let context = Arc::clone(&context);
+            async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                handle_connection(stream, state, context).await.unwrap();
+            }
+        });
+        let mut client = UnixStream::connect(&socket_path).await.unwrap();
+        let set = Request {
+            request_id: 1,
+            ssh_connection: None,
+            kind: RequestKind::Set {
+                value: ClipboardValue {
+                    content_type: CONTENT_TYPE_TEXT.to_string(),
+                    data: b"hello".to_vec(),
+                    created_at: 0,
+                    origin: None,
+                },
+            },
+        };
+        write_frame_payload(&mut client, &encode_message(&set).unwrap())
+            .await
+            .unwrap();
+        read_frame_payload(&mut client, 2048).await.unwrap();
+        server.await.unwrap();
+
+        let (mut scraper, endpoint) = tokio::io::duplex(64 * 1024);
+        let scrape = tokio::spawn({
+            let context = Arc::clone(&context);
+            async move { metrics::serve_scrape(endpoint, &state, &context).await }
+        });
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        scraper
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        scraper.read_to_string(&mut response).await.unwrap();
+        scrape.await.unwrap().unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: application/openmetrics-text"));
+        assert!(response.contains("\nssh_clipboard_requests_total{kind=\"set\"} 1\n"));
+        assert!(response.contains("\nssh_clipboard_stored_bytes 5\n"));
+        assert!(response.contains("\nssh_clipboard_request_bytes_count{kind=\"set\"} 1\n"));
+        assert!(
+            response.contains("\nssh_clipboard_request_duration_seconds_count{kind=\"set\"} 1\n")
+        );
+        assert!(response.contains("\nssh_clipboard_response_bytes_count 1\n"));
+        assert!(response.ends_with("# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn shared_session_checks_access_and_records_author() {
+        let rules = PolicyRules::default();
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
+        let set = Request {
+            request_id: 1,
+            ssh_connection: None,
+            kind: RequestKind::Set {
+                value: ClipboardValue {
+                    content_type: CONTENT_TYPE_TEXT.to_string(),
+                    data: b"shared".to_vec(),
+                    created_at: 0,
+                    origin: None,
+                },
+            },
+        };
+        let peek = Request {
+            request_id: 2,
+            ssh_connection: None,
+            kind: RequestKind::PeekMeta,
+        };
+
+        let reader = Session {
+            uid: 1001,
+            rules: &rules,
+            access: Access {
+                read: true,
+                write: false,
+            },
+            author: Some("bob".to_string()),
+        };
+        let response =
+            handle_request(set.clone(), Arc::clone(&state), &owner_context(), &reader).await;
+        assert!(matches!(
+            response.kind,
+            ResponseKind::Error {
+                code: ErrorCode::Forbidden,
+                ..
+            }
+        ));
+
+        let writer = Session {
+            uid: 1000,
+            rules: &rules,
+            access: Access::FULL,
+            author: Some("alice".to_string()),
+        };
+        handle_request(set, Arc::clone(&state), &owner_context(), &writer).await;
+        let response = handle_request(peek, Arc::clone(&state), &owner_context(), &reader).await;
+        match response.kind {
+            ResponseKind::Meta { author, .. } => assert_eq!(author.as_deref(), Some("alice")),
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
     #[test]
     fn peer_uid_match_helper() {
         assert!(peer_uid_matches(1000, 1000));
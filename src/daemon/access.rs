@@ -0,0 +1,302 @@
+use eyre::{Result, eyre};
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::str::FromStr;
+
+/// What a peer may do with the stored value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Access {
+    pub read: bool,
+    pub write: bool,
+}
+
+impl Access {
+    pub const FULL: Access = Access {
+        read: true,
+        write: true,
+    };
+
+    fn union(self, other: Access) -> Access {
+        Access {
+            read: self.read || other.read,
+            write: self.write || other.write,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Principal {
+    User(String),
+    Group(String),
+}
+
+/// `--grant user:<name|uid>=<r|w|rw>` or `--grant group:<name|gid>=<r|w|rw>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    principal: Principal,
+    access: Access,
+}
+
+impl FromStr for Grant {
+    type Err = eyre::Report;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let invalid =
+            || eyre!("invalid grant `{spec}` (expected user:<name>=rw or group:<name>=r)");
+        let (who, perms) = spec.split_once('=').ok_or_else(invalid)?;
+        let (kind, name) = who.split_once(':').ok_or_else(invalid)?;
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        let principal = match kind {
+            "user" => Principal::User(name.to_string()),
+            "group" => Principal::Group(name.to_string()),
+            _ => return Err(invalid()),
+        };
+        let access = match perms {
+            "r" => Access {
+                read: true,
+                write: false,
+            },
+            "w" => Access {
+                read: false,
+                write: true,
+            },
+            "rw" => Access::FULL,
+            _ => return Err(invalid()),
+        };
+        Ok(Self { principal, access })
+    }
+}
+
+impl fmt::Display for Grant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let perms = match (self.access.read, self.access.write) {
+            (true, true) => "rw",
+            (true, false) => "r",
+            _ => "w",
+        };
+        match &self.principal {
+            Principal::User(name) => write!(f, "user:{name}={perms}"),
+            Principal::Group(name) => write!(f, "group:{name}={perms}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ResolvedPrincipal {
+    Uid(u32),
+    Gid(u32),
+}
+
+/// Who may connect to the daemon. The owner always has full access; in shared mode
+/// the grants add other users and groups, checked against the peer's `SO_PEERCRED`
+/// uid and group membership.
+#[derive(Debug, Clone)]
+pub struct AccessList {
+    owner: u32,
+    shared: bool,
+    grants: Vec<(ResolvedPrincipal, Access)>,
+}
+
+impl AccessList {
+    pub fn owner_only(owner: u32) -> Self {
+        Self {
+            owner,
+            shared: false,
+            grants: Vec::new(),
+        }
+    }
+
+    /// Resolves user and group names once, so a typo fails at startup.
+    pub fn shared(owner: u32, grants: &[Grant]) -> Result<Self> {
+        let grants = grants
+            .iter()
+            .map(|grant| {
+                let principal = match &grant.principal {
+                    Principal::User(name) => ResolvedPrincipal::Uid(
+                        name.parse()
+                            .ok()
+                            .or_else(|| uid_for_name(name))
+                            .ok_or_else(|| eyre!("unknown user `{name}` in grant `{grant}`"))?,
+                    ),
+                    Principal::Group(name) => ResolvedPrincipal::Gid(
+                        name.parse()
+                            .ok()
+                            .or_else(|| gid_for_name(name))
+                            .ok_or_else(|| eyre!("unknown group `{name}` in grant `{grant}`"))?,
+                    ),
+                };
+                Ok((principal, grant.access))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            owner,
+            shared: true,
+            grants,
+        })
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Access for a peer, or `None` if it may not connect at all. Group grants are
+    /// matched against the primary gid and, when needed, the supplementary groups.
+    pub fn access_for(&self, uid: u32, gid: u32) -> Option<Access> {
+        if uid == self.owner {
+            return Some(Access::FULL);
+        }
+        if !self.shared {
+            return None;
+        }
+        let needs_groups = self
+            .grants
+            .iter()
+            .any(|(principal, _)| matches!(principal, ResolvedPrincipal::Gid(_)));
+        let groups = if needs_groups {
+            user_groups(uid, gid)
+        } else {
+            Vec::new()
+        };
+        self.access_with_groups(uid, &groups)
+    }
+
+    fn access_with_groups(&self, uid: u32, groups: &[u32]) -> Option<Access> {
+        self.grants
+            .iter()
+            .filter(|(principal, _)| match principal {
+                ResolvedPrincipal::Uid(id) => *id == uid,
+                ResolvedPrincipal::Gid(id) => groups.contains(id),
+            })
+            .map(|(_, access)| *access)
+            .reduce(Access::union)
+    }
+}
+
+/// Login name for `uid`.
+pub fn user_name(uid: u32) -> Option<String> {
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let ret = unsafe {
+        libc::getpwuid_r(
+            uid,
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+fn uid_for_name(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let ret = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    (ret == 0 && !result.is_null()).then_some(passwd.pw_uid)
+}
+
+fn gid_for_name(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; 16384];
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    let ret = unsafe {
+        libc::getgrnam_r(
+            name.as_ptr(),
+            &mut group,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    (ret == 0 && !result.is_null()).then_some(group.gr_gid)
+}
+
+/// The peer's primary gid plus its supplementary groups from the group database.
+fn user_groups(uid: u32, gid: u32) -> Vec<u32> {
+    let Some(name) = user_name(uid).and_then(|name| CString::new(name).ok()) else {
+        return vec![gid];
+    };
+    let mut groups: Vec<libc::gid_t> = vec![0; 64];
+    loop {
+        let mut count = groups.len() as libc::c_int;
+        let ret =
+            unsafe { libc::getgrouplist(name.as_ptr(), gid, groups.as_mut_ptr(), &mut count) };
+        if ret >= 0 {
+            groups.truncate(count.max(0) as usize);
+            return groups;
+        }
+        if groups.len() >= 65536 {
+            return vec![gid];
+        }
+        groups.resize((count.max(0) as usize).max(groups.len() * 2), 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grants(specs: &[&str]) -> Vec<Grant> {
+        specs.iter().map(|spec| spec.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn grant_specs_parse() {
+        for spec in ["user:alice=rw", "group:dev=r", "user:1001=w"] {
+            assert_eq!(spec.parse::<Grant>().unwrap().to_string(), spec);
+        }
+        for spec in ["alice=rw", "user:alice", "user:=r", "host:x=r", "user:a=x"] {
+            assert!(spec.parse::<Grant>().is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn owner_only_rejects_everyone_else() {
+        let acl = AccessList::owner_only(1000);
+        assert_eq!(acl.access_for(1000, 1000), Some(Access::FULL));
+        assert_eq!(acl.access_for(1001, 1000), None);
+    }
+
+    #[test]
+    fn shared_grants_combine_user_and_group() {
+        let acl = AccessList::shared(
+            1000,
+            &grants(&["user:1001=w", "group:2000=r", "group:3000=w"]),
+        )
+        .unwrap();
+        assert_eq!(acl.access_with_groups(1001, &[2000]), Some(Access::FULL));
+        assert_eq!(
+            acl.access_with_groups(1002, &[2000]),
+            Some(Access {
+                read: true,
+                write: false
+            })
+        );
+        assert_eq!(acl.access_with_groups(1003, &[4000]), None);
+    }
+
+    #[test]
+    fn unknown_names_fail_at_startup() {
+        let err =
+            AccessList::shared(1000, &grants(&["user:no-such-user-ssh-clipboard=r"])).unwrap_err();
+        assert!(err.to_string().contains("unknown user"));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::protocol::{ErrorCode, Request, RequestKind, Response, ResponseKind};
+use eyre::{Result, WrapErr, eyre};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tracing::warn;
+
+pub const DEFAULT_AUDIT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+/// Rotated files kept next to the active log (`audit.jsonl.1` is the newest).
+pub const AUDIT_KEEP_FILES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    Get,
+    Set,
+    Peek,
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditResult {
+    Ok,
+    Empty,
+    Error,
+}
+
+/// One line of the audit log. Clipboard contents are never recorded, only their
+/// type and size. `operation` is `None` when the request could not be read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub request_id: u64,
+    pub operation: Option<AuditOperation>,
+    pub peer_uid: Option<u32>,
+    pub peer_pid: Option<i32>,
+    /// The request's `ssh_connection`. The proxy fills it in, but a local client that
+    /// talks to the socket directly can send anything, so it is not verified.
+    pub reported_ssh_connection: Option<String>,
+    pub content_type: Option<String>,
+    pub size: Option<u64>,
+    pub result: AuditResult,
+    pub error: Option<ErrorCode>,
+}
+
+impl AuditRecord {
+    pub fn new(peer_uid: Option<u32>, peer_pid: Option<i32>) -> Self {
+        Self {
+            timestamp: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            request_id: 0,
+            operation: None,
+            peer_uid,
+            peer_pid,
+            reported_ssh_connection: None,
+            content_type: None,
+            size: None,
+            result: AuditResult::Error,
+            error: None,
+        }
+    }
+
+    pub fn note_request(&mut self, request: &Request) {
+        self.request_id = request.request_id;
+        self.reported_ssh_connection = request.ssh_connection.clone();
+        self.operation = Some(match &request.kind {
+            RequestKind::Get => AuditOperation::Get,
+            RequestKind::PeekMeta => AuditOperation::Peek,
+            RequestKind::Status => AuditOperation::Status,
+            RequestKind::Set { value } => {
+                self.content_type = Some(value.content_type.clone());
+                self.size = Some(value.data.len() as u64);
+                AuditOperation::Set
+            }
+        });
+    }
+
+    /// Fills in the outcome; reads take the type and size of the value served.
+    pub fn finish(mut self, response: &Response) -> Self {
+        self.request_id = response.request_id;
+        self.result = match &response.kind {
+            ResponseKind::Ok | ResponseKind::Status { .. } => AuditResult::Ok,
+            ResponseKind::Value { value } => {
+                self.content_type = Some(value.content_type.clone());
+                self.size = Some(value.data.len() as u64);
+                AuditResult::Ok
+            }
+            ResponseKind::Meta {
+                content_type, size, ..
+            } => {
+                self.content_type = Some(content_type.clone());
+                self.size = Some(*size);
+                AuditResult::Ok
+            }
+            ResponseKind::Empty => AuditResult::Empty,
+            ResponseKind::Error { code, .. } => {
+                self.error = Some(code.clone());
+                AuditResult::Error
+            }
+        };
+        self
+    }
+}
+
+/// `$XDG_STATE_HOME/ssh_clipboard/audit.jsonl`, falling back to `~/.local/state`.
+pub fn default_audit_path() -> Result<PathBuf> {
+    let state_dir = match std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".local").join("state"))
+            .ok_or_else(|| eyre!("HOME is not set; pass --audit-path"))?,
+    };
+    Ok(state_dir.join("ssh_clipboard").join("audit.jsonl"))
+}
+
+struct AuditFile {
+    file: File,
+    len: u64,
+}
+
+/// Append-only JSON-lines log, rotated by size. Write failures are logged and never
+/// fail the request being audited.
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+    file: Mutex<AuditFile>,
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("path", &self.path)
+            .field("max_bytes", &self.max_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AuditLog {
+    pub fn open(path: &Path, max_bytes: u64) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("failed to create {}", parent.display()))?;
+        }
+        let file = open_append(path)
+            .wrap_err_with(|| format!("failed to open audit log {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, record: &AuditRecord) {
+        if let Err(err) = self.try_append(record) {
+            warn!(error = %err, path = %self.path.display(), "failed to write audit record");
+        }
+    }
+
+    fn try_append(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut current = self
+            .file
+            .lock()
+            .map_err(|_| eyre!("audit log lock poisoned"))?;
+        if current.len > 0 && current.len + line.len() as u64 > self.max_bytes {
+            rotate(&self.path)?;
+            *current = open_append(&self.path)?;
+        }
+        current.file.write_all(&line)?;
+        current.len += line.len() as u64;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<AuditFile> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    let len = file.metadata()?.len();
+    Ok(AuditFile { file, len })
+}
+
+pub fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+/// Shifts `audit.jsonl.N` up by one, dropping the oldest, and moves the active log
+/// to `.1`.
+fn rotate(path: &Path) -> Result<()> {
+    for index in (1..AUDIT_KEEP_FILES).rev() {
+        let from = rotated_path(path, index);
+        if from.exists() {
+            std::fs::rename(&from, rotated_path(path, index + 1))?;
+        }
+    }
+    std::fs::rename(path, rotated_path(path, 1))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{CONTENT_TYPE_TEXT, ClipboardValue};
+
+    fn set_request() -> Request {
+        Request {
+            request_id: 11,
+            ssh_connection: Some("10.0.0.2 51234 10.0.0.1 22".to_string()),
+            kind: RequestKind::Set {
+                value: ClipboardValue {
+                    content_type: CONTENT_TYPE_TEXT.to_string(),
+                    data: b"secret".to_vec(),
+                    created_at: 0,
+                    origin: None,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn records_describe_requests_without_contents() {
+        let mut record = AuditRecord::new(Some(1000), Some(42));
+        record.note_request(&set_request());
+        let record = record.finish(&Response {
+            request_id: 11,
+            kind: ResponseKind::Ok,
+        });
+
+        let line = serde_json::to_string(&record).unwrap();
+        assert!(!line.contains("secret"));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["operation"], "set");
+        assert_eq!(value["result"], "ok");
+        assert_eq!(value["size"], 6);
+        assert_eq!(value["peer_pid"], 42);
+        assert_eq!(
+            value["reported_ssh_connection"],
+            "10.0.0.2 51234 10.0.0.1 22"
+        );
+
+        let rejected = AuditRecord::new(Some(1001), None).finish(&Response {
+            request_id: 0,
+            kind: ResponseKind::Error {
+                code: ErrorCode::Forbidden,
+                message: "no".to_string(),
+            },
+        });
+        let value = serde_json::to_value(&rejected).unwrap();
+        assert_eq!(value["result"], "error");
+        assert_eq!(value["error"], "forbidden");
+        assert!(value["operation"].is_null());
+    }
+
+    #[test]
+    fn log_rotates_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit").join("audit.jsonl");
+        let log = AuditLog::open(&path, 300).unwrap();
+        for _ in 0..(AUDIT_KEEP_FILES + 3) * 2 {
+            let mut record = AuditRecord::new(Some(1000), Some(1));
+            record.note_request(&set_request());
+            log.append(&record);
+        }
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+        assert!(std::fs::metadata(&path).unwrap().len() <= 300);
+        assert!(rotated_path(&path, AUDIT_KEEP_FILES).exists());
+        assert!(!rotated_path(&path, AUDIT_KEEP_FILES + 1).exists());
+        for line in std::fs::read_to_string(rotated_path(&path, 1))
+            .unwrap()
+            .lines()
+        {
+            serde_json::from_str::<AuditRecord>(line).unwrap();
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use super::status::{Counters, DaemonStats};
+use super::{ClipboardState, DaemonContext};
+use crate::protocol::DaemonStatus;
+use eyre::{Result, WrapErr, eyre};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::net::SocketAddr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, UnixListener};
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+use tracing::{error, info};
+
+/// Upper bounds for payload size histograms, in bytes.
+pub(super) const SIZE_BUCKETS: &[f64] = &[
+    64.0, 1024.0, 16384.0, 262144.0, 1048576.0, 4194304.0, 16777216.0,
+];
+/// Upper bounds for request handling histograms, in seconds.
+pub(super) const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0,
+];
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+/// Scrapers send a short request line and a few headers; anything longer is cut off.
+const MAX_HTTP_REQUEST_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, Clone)]
+pub(super) struct Histogram {
+    bounds: &'static [f64],
+    /// Observations per bucket, not cumulative; the last slot is `+Inf`.
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: vec![0; bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let index = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.buckets[index] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// `--metrics-listen 127.0.0.1:<port>` or `--metrics-listen unix:<path>`. TCP
+/// addresses must be loopback: the endpoint has no authentication.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetricsListen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for MetricsListen {
+    type Err = eyre::Report;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        if let Some(path) = spec.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(eyre!("invalid metrics address `{spec}` (missing path)"));
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        let addr: SocketAddr = spec.parse().map_err(|_| {
+            eyre!("invalid metrics address `{spec}` (expected 127.0.0.1:<port> or unix:<path>)")
+        })?;
+        if !addr.ip().is_loopback() {
+            return Err(eyre!(
+                "metrics address `{spec}` is not loopback; the endpoint is unauthenticated"
+            ));
+        }
+        Ok(Self::Tcp(addr))
+    }
+}
+
+impl fmt::Display for MetricsListen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Binds the metrics endpoint and serves scrapes in the background.
+pub(super) async fn spawn(
+    listen: &MetricsListen,
+    state: Arc<Mutex<ClipboardState>>,
+    context: Arc<DaemonContext>,
+) -> Result<()> {
+    match listen {
+        MetricsListen::Tcp(addr) => {
+            let listener = TcpListener::bind(addr)
+                .await
+                .wrap_err_with(|| format!("bind metrics address {addr}"))?;
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => serve_in_background(stream, &state, &context),
+                        Err(err) => error!(error = %err, "metrics accept failed"),
+                    }
+                }
+            });
+        }
+        MetricsListen::Unix(path) => {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            let old_umask = super::set_umask();
+            let listener = UnixListener::bind(path);
+            unsafe { libc::umask(old_umask) };
+            let listener =
+                listener.wrap_err_with(|| format!("bind metrics socket {}", path.display()))?;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => serve_in_background(stream, &state, &context),
+                        Err(err) => error!(error = %err, "metrics accept failed"),
+                    }
+                }
+            });
+        }
+    }
+    info!(listen = %listen, "serving metrics");
+    Ok(())
+}
+
+fn serve_in_background<S>(
+    stream: S,
+    state: &Arc<Mutex<ClipboardState>>,
+    context: &Arc<DaemonContext>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let state = Arc::clone(state);
+    let context = Arc::clone(context);
+    tokio::spawn(async move {
+        if let Err(err) = serve_scrape(stream, &state, &context).await {
+            error!(error = %err, "metrics request failed");
+        }
+    });
+}
+
+/// Answers one HTTP request: `GET /metrics` gets the exposition, anything else an
+/// error status. The connection is closed afterwards.
+pub(super) async fn serve_scrape<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    state: &Mutex<ClipboardState>,
+    context: &DaemonContext,
+) -> Result<()> {
+    let limit = context.io_timeout();
+    let mut request = Vec::new();
+    timeout(limit, async {
+        let mut chunk = [0u8; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n")
+            && request.len() < MAX_HTTP_REQUEST_BYTES
+        {
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&chunk[..read]);
+        }
+        Ok::<_, std::io::Error>(())
+    })
+    .await
+    .wrap_err("metrics request timed out")??;
+
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => {
+            let stored_bytes = state
+                .lock()
+                .await
+                .current()
+                .map(|value| value.data.len() as u64);
+            let body = render(&context.status(stored_bytes), &context.stats);
+            ("200 OK", CONTENT_TYPE, body)
+        }
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    timeout(limit, stream.write_all(response.as_bytes()))
+        .await
+        .wrap_err("metrics response timed out")??;
+    timeout(limit, stream.shutdown()).await.ok();
+    Ok(())
+}
+
+/// The OpenMetrics text exposition of the daemon's counters.
+pub(super) fn render(status: &DaemonStatus, stats: &DaemonStats) -> String {
+    let mut out = String::new();
+    family(
+        &mut out,
+        "ssh_clipboard_build",
+        "info",
+        "Daemon build information.",
+    );
+    let _ = writeln!(
+        out,
+        "ssh_clipboard_build_info{{version=\"{}\",protocol=\"{}\"}} 1",
+        status.version, status.protocol_version
+    );
+    gauge(
+        &mut out,
+        "ssh_clipboard_uptime_seconds",
+        "Seconds since the daemon started.",
+        status.uptime_secs,
+    );
+    gauge(
+        &mut out,
+        "ssh_clipboard_stored_values",
+        "Values currently stored.",
+        status.stored_values,
+    );
+    gauge(
+        &mut out,
+        "ssh_clipboard_stored_bytes",
+        "Size of the stored value.",
+        status.stored_bytes,
+    );
+    if let Some(rss) = status.rss_bytes {
+        gauge(
+            &mut out,
+            "ssh_clipboard_resident_memory_bytes",
+            "Resident set size of the daemon.",
+            rss,
+        );
+    }
+
+    if let Some(counters) = stats.counters() {
+        render_counters(&mut out, &counters);
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn render_counters(out: &mut String, counters: &Counters) {
+    labelled_counter(
+        out,
+        "ssh_clipboard_requests",
+        "Requests decoded, by kind.",
+        "kind",
+        &counters.requests,
+    );
+    labelled_counter(
+        out,
+        "ssh_clipboard_errors",
+        "Error responses sent, by error code.",
+        "code",
+        &counters.errors,
+    );
+    family(
+        out,
+        "ssh_clipboard_peer_rejections",
+        "counter",
+        "Connections refused by the peer credential check.",
+    );
+    let _ = writeln!(
+        out,
+        "ssh_clipboard_peer_rejections_total {}",
+        counters.peer_rejections
+    );
+    labelled_counter(
+        out,
+        "ssh_clipboard_framing_errors",
+        "Requests dropped because their frame could not be read, by reason.",
+        "reason",
+        &counters.framing_errors,
+    );
+
+    family(
+        out,
+        "ssh_clipboard_request_bytes",
+        "histogram",
+        "Request payload size, by kind.",
+    );
+    for (kind, histogram) in &counters.request_bytes {
+        histogram_samples(
+            out,
+            "ssh_clipboard_request_bytes",
+            &format!("kind=\"{kind}\","),
+            histogram,
+        );
+    }
+    family(
+        out,
+        "ssh_clipboard_response_bytes",
+        "histogram",
+        "Response payload size.",
+    );
+    if let Some(histogram) = &counters.response_bytes {
+        histogram_samples(out, "ssh_clipboard_response_bytes", "", histogram);
+    }
+    family(
+        out,
+        "ssh_clipboard_request_duration_seconds",
+        "histogram",
+        "Time spent handling a decoded request, by kind.",
+    );
+    for (kind, histogram) in &counters.durations {
+        histogram_samples(
+            out,
+            "ssh_clipboard_request_duration_seconds",
+            &format!("kind=\"{kind}\","),
+            histogram,
+        );
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    let _ = writeln!(out, "# HELP {name} {help}");
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    family(out, name, "gauge", help);
+    let _ = writeln!(out, "{name} {value}");
+}
+
+fn labelled_counter(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    label: &str,
+    values: &BTreeMap<&'static str, u64>,
+) {
+    family(out, name, "counter", help);
+    for (value, count) in values {
+        let _ = writeln!(out, "{name}_total{{{label}=\"{value}\"}} {count}");
+    }
+}
+
+/// `labels` is either empty or ends with a comma, ready for `le`.
+fn histogram_samples(out: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    let mut cumulative = 0;
+    for (bound, count) in histogram.bounds.iter().zip(&histogram.buckets) {
+        cumulative += count;
+        let _ = writeln!(out, "{name}_bucket{{{labels}le=\"{bound}\"}} {cumulative}");
+    }
+    let _ = writeln!(
+        out,
+        "{name}_bucket{{{labels}le=\"+Inf\"}} {}",
+        histogram.count
+    );
+    let labels = labels.trim_end_matches(',');
+    let labels = if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{labels}}}")
+    };
+    let _ = writeln!(out, "{name}_sum{labels} {}", histogram.sum);
+    let _ = writeln!(out, "{name}_count{labels} {}", histogram.count);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Duration;
+
+    #[test]
+    fn listen_addresses_must_be_loopback_or_unix() {
+        assert_eq!(
+            "127.0.0.1:9464".parse::<MetricsListen>().unwrap(),
+            MetricsListen::Tcp("127.0.0.1:9464".parse().unwrap())
+        );
+        assert!("[::1]:9464".parse::<MetricsListen>().is_ok());
+        assert_eq!(
+            "unix:/run/ssh_clipboard/metrics.sock"
+                .parse::<MetricsListen>()
+                .unwrap()
+                .to_string(),
+            "unix:/run/ssh_clipboard/metrics.sock"
+        );
+        for spec in ["0.0.0.0:9464", "10.0.0.1:9464", "localhost:9464", "unix:"] {
+            assert!(spec.parse::<MetricsListen>().is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn histograms_render_cumulative_buckets() {
+        let stats = DaemonStats::default();
+        stats.observe_duration("get", Duration::from_micros(300));
+        stats.observe_duration("get", Duration::from_millis(20));
+        stats.observe_duration("get", Duration::from_secs(3));
+        stats.count_peer_rejection();
+        stats.count_framing_error("invalid_magic");
+
+        let mut out = String::new();
+        render_counters(&mut out, &stats.counters().unwrap());
+        assert!(out.contains(
+            "ssh_clipboard_request_duration_seconds_bucket{kind=\"get\",le=\"0.0005\"} 1\n"
+        ));
+        assert!(out.contains(
+            "ssh_clipboard_request_duration_seconds_bucket{kind=\"get\",le=\"0.025\"} 2\n"
+        ));
+        assert!(out.contains(
+            "ssh_clipboard_request_duration_seconds_bucket{kind=\"get\",le=\"+Inf\"} 3\n"
+        ));
+        assert!(out.contains("ssh_clipboard_request_duration_seconds_count{kind=\"get\"} 3\n"));
+        assert!(out.contains("ssh_clipboard_peer_rejections_total 1\n"));
+        assert!(out.contains("ssh_clipboard_framing_errors_total{reason=\"invalid_magic\"} 1\n"));
+    }
+}
@@ -0,0 +1,378 @@
+use super::{ClipboardState, DaemonContext};
+use crate::client::image;
+use crate::client_actions::ClipboardAccess;
+use crate::protocol::{CONTENT_TYPE_PNG, CONTENT_TYPE_TEXT, ClipboardValue, is_image_content_type};
+use arboard::{Clipboard, ImageData};
+use eyre::{Result, eyre};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+use tracing::{debug, warn};
+
+/// Keeps one `arboard::Clipboard` alive for the daemon's lifetime. On X11 the owner
+/// of a selection must stay around to serve it, so a per-call handle would lose the
+/// value as soon as it is dropped.
+pub struct PersistentClipboard {
+    inner: Clipboard,
+}
+
+impl PersistentClipboard {
+    pub fn new() -> Result<Self> {
+        let inner = Clipboard::new().map_err(|err| eyre!("clipboard init failed: {err}"))?;
+        Ok(Self { inner })
+    }
+}
+
+impl ClipboardAccess for PersistentClipboard {
+    fn read_text(&mut self) -> Result<String> {
+        self.inner
+            .get_text()
+            .map_err(|err| eyre!("clipboard read failed: {err}"))
+    }
+
+    fn read_image(&mut self) -> Result<ImageData<'static>> {
+        let image = self
+            .inner
+            .get_image()
+            .map_err(|err| eyre!("clipboard image read failed: {err}"))?;
+        Ok(ImageData {
+            width: image.width,
+            height: image.height,
+            bytes: image.bytes.into_owned().into(),
+        })
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<()> {
+        self.inner
+            .set_text(text.to_string())
+            .map_err(|err| eyre!("clipboard write failed: {err}"))
+    }
+
+    fn write_image(&mut self, image: ImageData<'static>) -> Result<()> {
+        self.inner
+            .set_image(image)
+            .map_err(|err| eyre!("clipboard image write failed: {err}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncAction {
+    Idle,
+    ToDesktop,
+    FromDesktop,
+}
+
+/// Two-way sync between `ClipboardState` and a desktop clipboard. Values written in
+/// either direction are fingerprinted so they are not echoed back. Desktop values
+/// pass the same checks and policy as a `Set` from the daemon's owner.
+pub(super) struct ClipboardMirror<C> {
+    clipboard: C,
+    context: Arc<DaemonContext>,
+    applied_generation: u64,
+    last_fingerprint: Option<u64>,
+}
+
+impl<C: ClipboardAccess> ClipboardMirror<C> {
+    pub(super) fn new(clipboard: C, context: Arc<DaemonContext>) -> Self {
+        Self {
+            clipboard,
+            context,
+            applied_generation: 0,
+            last_fingerprint: None,
+        }
+    }
+
+    /// Runs one sync step. Daemon-side changes win over desktop changes observed in
+    /// the same step. The state lock is never held during clipboard I/O.
+    pub fn sync_once(&mut self, state: &Mutex<ClipboardState>) -> Result<SyncAction> {
+        let pending = {
+            let state = state.blocking_lock();
+            (state.generation != self.applied_generation)
+                .then(|| (state.generation, state.value.clone()))
+        };
+        if let Some((generation, value)) = pending {
+            self.applied_generation = generation;
+            return match value {
+                Some(value) => {
+                    self.write_desktop(&value)?;
+                    Ok(SyncAction::ToDesktop)
+                }
+                None => Ok(SyncAction::Idle),
+            };
+        }
+
+        let Some((fingerprint, value)) = self.read_desktop()? else {
+            return Ok(SyncAction::Idle);
+        };
+        if self.last_fingerprint == Some(fingerprint) {
+            return Ok(SyncAction::Idle);
+        }
+        self.last_fingerprint = Some(fingerprint);
+        let ttl = match self.context.check_owner_set(&value) {
+            Ok(ttl) => ttl,
+            Err(err) => {
+                warn!(
+                    size = value.data.len(),
+                    error = %err,
+                    "desktop clipboard value rejected; not mirrored"
+                );
+                return Ok(SyncAction::Idle);
+            }
+        };
+
+        let mut state = state.blocking_lock();
+        if state.generation != self.applied_generation {
+            // A client set a value while we were reading; it takes precedence.
+            return Ok(SyncAction::Idle);
+        }
+        state.store(value, ttl, None);
+        self.applied_generation = state.generation;
+        Ok(SyncAction::FromDesktop)
+    }
+
+    fn read_desktop(&mut self) -> Result<Option<(u64, ClipboardValue)>> {
+        if let Ok(text) = self.clipboard.read_text() {
+            let fingerprint = text_fingerprint(&text);
+            return Ok(Some((
+                fingerprint,
+                ClipboardValue {
+                    content_type: CONTENT_TYPE_TEXT.to_string(),
+                    data: text.into_bytes(),
+                    created_at: now_epoch_millis(),
+                    origin: None,
+                },
+            )));
+        }
+        let Ok(img) = self.clipboard.read_image() else {
+            return Ok(None);
+        };
+        let fingerprint = image_fingerprint(&img);
+        if self.last_fingerprint == Some(fingerprint) {
+            // Skip the PNG encode for an image we already know about.
+            return Ok(Some((
+                fingerprint,
+                ClipboardValue {
+                    content_type: CONTENT_TYPE_PNG.to_string(),
+                    data: Vec::new(),
+                    created_at: 0,
+                    origin: None,
+                },
+            )));
+        }
+        let png = image::encode_png(img)?;
+        Ok(Some((
+            fingerprint,
+            ClipboardValue {
+                content_type: CONTENT_TYPE_PNG.to_string(),
+                data: png,
+                created_at: now_epoch_millis(),
+                origin: None,
+            },
+        )))
+    }
+
+    fn write_desktop(&mut self, value: &ClipboardValue) -> Result<()> {
+        if value.content_type == CONTENT_TYPE_TEXT {
+            let text = std::str::from_utf8(&value.data)
+                .map_err(|_| eyre!("stored text is not valid UTF-8"))?;
+            self.clipboard.write_text(text)?;
+            self.last_fingerprint = Some(text_fingerprint(text));
+            return Ok(());
+        }
+        if is_image_content_type(&value.content_type) {
+            let img =
+                image::decode_image(&value.data, &value.content_type, self.context.max_size())?;
+            let fingerprint = image_fingerprint(&img);
+            self.clipboard.write_image(img)?;
+            self.last_fingerprint = Some(fingerprint);
+            return Ok(());
+        }
+        Err(eyre!("unsupported content type: {}", value.content_type))
+    }
+}
+
+pub(super) fn spawn(
+    state: Arc<Mutex<ClipboardState>>,
+    context: Arc<DaemonContext>,
+    interval: Duration,
+) -> Result<()> {
+    let clipboard = PersistentClipboard::new()?;
+    std::thread::Builder::new()
+        .name("clipboard-mirror".to_string())
+        .spawn(move || {
+            let mut mirror = ClipboardMirror::new(clipboard, context);
+            loop {
+                std::thread::sleep(interval);
+                match mirror.sync_once(&state) {
+                    Ok(SyncAction::Idle) => {}
+                    Ok(action) => debug!(?action, "clipboard mirrored"),
+                    Err(err) => warn!(error = %err, "clipboard mirror failed"),
+                }
+            }
+        })?;
+    Ok(())
+}
+
+fn text_fingerprint(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    CONTENT_TYPE_TEXT.hash(&mut hasher);
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn image_fingerprint(img: &ImageData<'_>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    CONTENT_TYPE_PNG.hash(&mut hasher);
+    img.width.hash(&mut hasher);
+    img.height.hash(&mut hasher);
+    img.bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn now_epoch_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::Policy;
+    use crate::daemon::tests::owner_context;
+
+    #[derive(Default)]
+    struct FakeDesktop {
+        text: Option<String>,
+        image: Option<ImageData<'static>>,
+        writes: usize,
+    }
+
+    impl ClipboardAccess for FakeDesktop {
+        fn read_text(&mut self) -> Result<String> {
+            self.text.clone().ok_or_else(|| eyre!("no text"))
+        }
+
+        fn read_image(&mut self) -> Result<ImageData<'static>> {
+            self.image.clone().ok_or_else(|| eyre!("no image"))
+        }
+
+        fn write_text(&mut self, text: &str) -> Result<()> {
+            self.text = Some(text.to_string());
+            self.image = None;
+            self.writes += 1;
+            Ok(())
+        }
+
+        fn write_image(&mut self, image: ImageData<'static>) -> Result<()> {
+            self.image = Some(image);
+            self.text = None;
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    fn text_value(text: &str) -> ClipboardValue {
+        ClipboardValue {
+            content_type: CONTENT_TYPE_TEXT.to_string(),
+            data: text.as_bytes().to_vec(),
+            created_at: 1,
+            origin: None,
+        }
+    }
+
+    #[test]
+    fn daemon_value_is_written_to_desktop_once() {
+        let state = Mutex::new(ClipboardState::default());
+        state.blocking_lock().set_value(text_value("from client"));
+        let mut mirror = ClipboardMirror::new(FakeDesktop::default(), owner_context());
+
+        assert_eq!(mirror.sync_once(&state).unwrap(), SyncAction::ToDesktop);
+        assert_eq!(mirror.clipboard.text.as_deref(), Some("from client"));
+        // The value we just wrote must not bounce back as a new desktop change.
+        assert_eq!(mirror.sync_once(&state).unwrap(), SyncAction::Idle);
+        assert_eq!(state.blocking_lock().generation, 1);
+        assert_eq!(mirror.clipboard.writes, 1);
+    }
+
+    #[test]
+    fn desktop_change_is_stored_in_daemon() {
+        let state = Mutex::new(ClipboardState::default());
+        let desktop = FakeDesktop {
+            text: Some("copied on server".to_string()),
+            ..FakeDesktop::default()
+        };
+        let mut mirror = ClipboardMirror::new(desktop, owner_context());
+
+        assert_eq!(mirror.sync_once(&state).unwrap(), SyncAction::FromDesktop);
+        assert_eq!(
+            state.blocking_lock().value.as_ref().unwrap().data,
+            b"copied on server"
+        );
+        assert_eq!(mirror.sync_once(&state).unwrap(), SyncAction::Idle);
+        assert_eq!(mirror.clipboard.writes, 0);
+    }
+
+    #[test]
+    fn desktop_image_round_trips_without_echo() {
+        let state = Mutex::new(ClipboardState::default());
+        let desktop = FakeDesktop {
+            image: Some(ImageData {
+                width: 1,
+                height: 1,
+                bytes: vec![10, 20, 30, 255].into(),
+            }),
+            ..FakeDesktop::default()
+        };
+        let mut mirror = ClipboardMirror::new(desktop, owner_context());
+
+        assert_eq!(mirror.sync_once(&state).unwrap(), SyncAction::FromDesktop);
+        assert_eq!(
+            state.blocking_lock().value.as_ref().unwrap().content_type,
+            CONTENT_TYPE_PNG
+        );
+        assert_eq!(mirror.sync_once(&state).unwrap(), SyncAction::Idle);
+    }
+
+    #[test]
+    fn oversize_desktop_value_is_ignored() {
+        let state = Mutex::new(ClipboardState::default());
+        let desktop = FakeDesktop {
+            text: Some("too long for the limit".to_string()),
+            ..FakeDesktop::default()
+        };
+        let context = owner_context();
+        context
+            .max_size
+            .store(4, std::sync::atomic::Ordering::Relaxed);
+        let mut mirror = ClipboardMirror::new(desktop, context);
+
+        assert_eq!(mirror.sync_once(&state).unwrap(), SyncAction::Idle);
+        assert!(state.blocking_lock().value.is_none());
+    }
+
+    #[test]
+    fn desktop_value_goes_through_owner_policy() {
+        let state = Mutex::new(ClipboardState::default());
+        let context = owner_context();
+        let policy = Policy::parse(
+            "[default]\ndeny = [\"secret\"]\n[default.ttl_secs]\n\"text/plain\" = 60\n",
+        )
+        .unwrap();
+        *context.policy.write().unwrap() = Arc::new(policy);
+        let desktop = FakeDesktop {
+            text: Some("a secret".to_string()),
+            ..FakeDesktop::default()
+        };
+        let mut mirror = ClipboardMirror::new(desktop, context);
+
+        assert_eq!(mirror.sync_once(&state).unwrap(), SyncAction::Idle);
+        assert!(state.blocking_lock().value.is_none());
+
+        mirror.clipboard.text = Some("allowed".to_string());
+        assert_eq!(mirror.sync_once(&state).unwrap(), SyncAction::FromDesktop);
+        assert!(state.blocking_lock().expires_at.is_some());
+    }
+}
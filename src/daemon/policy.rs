@@ -0,0 +1,358 @@
+use crate::protocol::{CONTENT_TYPE_TEXT, ClipboardValue, ErrorCode, PolicyLimit};
+use eyre::{Result, WrapErr, eyre};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::Duration;
+
+/// Policy file layout. `[default]` applies to every user; a `[users.<name>]` table
+/// overrides individual fields of the default for that user.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PolicyFile {
+    default: RulesSpec,
+    users: HashMap<String, RulesSpec>,
+}
+
+/// Content-type keys match the type without parameters (`text/plain` matches
+/// `text/plain; charset=utf-8`) and accept a `type/*` wildcard.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RulesSpec {
+    allowed_content_types: Option<Vec<String>>,
+    max_size: Option<HashMap<String, usize>>,
+    max_image_width: Option<u32>,
+    max_image_height: Option<u32>,
+    ttl_secs: Option<HashMap<String, u64>>,
+    deny: Option<Vec<String>>,
+}
+
+impl RulesSpec {
+    fn overlay(&self, user: &RulesSpec) -> RulesSpec {
+        RulesSpec {
+            allowed_content_types: user
+                .allowed_content_types
+                .clone()
+                .or_else(|| self.allowed_content_types.clone()),
+            max_size: user.max_size.clone().or_else(|| self.max_size.clone()),
+            max_image_width: user.max_image_width.or(self.max_image_width),
+            max_image_height: user.max_image_height.or(self.max_image_height),
+            ttl_secs: user.ttl_secs.clone().or_else(|| self.ttl_secs.clone()),
+            deny: user.deny.clone().or_else(|| self.deny.clone()),
+        }
+    }
+}
+
+/// A rejected `Set`, already in protocol terms.
+#[derive(Debug, Clone)]
+pub struct PolicyViolation {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// Compiled rules for one user.
+#[derive(Debug, Clone, Default)]
+pub struct PolicyRules {
+    allowed_content_types: Option<Vec<String>>,
+    max_size: Vec<(String, usize)>,
+    max_image_width: Option<u32>,
+    max_image_height: Option<u32>,
+    ttl_secs: Vec<(String, u64)>,
+    deny: Vec<Regex>,
+}
+
+impl PolicyRules {
+    fn compile(spec: &RulesSpec) -> Result<Self> {
+        let deny = spec
+            .deny
+            .iter()
+            .flatten()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|err| eyre!("invalid deny pattern `{pattern}`: {err}"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            allowed_content_types: spec.allowed_content_types.clone(),
+            max_size: sorted_by_specificity(spec.max_size.clone().unwrap_or_default()),
+            max_image_width: spec.max_image_width,
+            max_image_height: spec.max_image_height,
+            ttl_secs: sorted_by_specificity(spec.ttl_secs.clone().unwrap_or_default()),
+            deny,
+        })
+    }
+
+    /// Checks a value the daemon already validated and returns how long it may be
+    /// kept, if the policy limits it.
+    pub fn check(
+        &self,
+        value: &ClipboardValue,
+    ) -> std::result::Result<Option<Duration>, PolicyViolation> {
+        let content_type = value.content_type.as_str();
+        if let Some(allowed) = &self.allowed_content_types
+            && !allowed
+                .iter()
+                .any(|pattern| content_type_matches(pattern, content_type))
+        {
+            return Err(PolicyViolation {
+                code: ErrorCode::ContentTypeNotAllowed {
+                    content_type: content_type.to_string(),
+                },
+                message: format!("content type {content_type} is not allowed by server policy"),
+            });
+        }
+
+        if let Some(max) = lookup(&self.max_size, content_type)
+            && value.data.len() > max
+        {
+            return Err(limit_exceeded(
+                PolicyLimit::Size,
+                value.data.len() as u64,
+                max as u64,
+            ));
+        }
+
+        if (self.max_image_width.is_some() || self.max_image_height.is_some())
+            && content_type != CONTENT_TYPE_TEXT
+        {
+            let (width, height) = image_dimensions(&value.data).ok_or_else(|| PolicyViolation {
+                code: ErrorCode::InvalidRequest,
+                message: "could not read image dimensions".to_string(),
+            })?;
+            if let Some(max) = self.max_image_width
+                && width > max
+            {
+                return Err(limit_exceeded(
+                    PolicyLimit::ImageWidth,
+                    width.into(),
+                    max.into(),
+                ));
+            }
+            if let Some(max) = self.max_image_height
+                && height > max
+            {
+                return Err(limit_exceeded(
+                    PolicyLimit::ImageHeight,
+                    height.into(),
+                    max.into(),
+                ));
+            }
+        }
+
+        if content_type == CONTENT_TYPE_TEXT
+            && let Ok(text) = std::str::from_utf8(&value.data)
+            && let Some(rule) = self.deny.iter().find(|rule| rule.is_match(text))
+        {
+            return Err(PolicyViolation {
+                code: ErrorCode::ContentDenied {
+                    rule: rule.as_str().to_string(),
+                },
+                message: "content rejected by server policy".to_string(),
+            });
+        }
+
+        Ok(lookup(&self.ttl_secs, content_type).map(Duration::from_secs))
+    }
+}
+
+/// Rules for every user, loaded from the daemon's policy file.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    default: PolicyRules,
+    users: HashMap<String, PolicyRules>,
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read policy {}", path.display()))?;
+        Self::parse(&contents).wrap_err_with(|| format!("invalid policy {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let file: PolicyFile = toml::from_str(contents).map_err(|err| eyre!("{err}"))?;
+        let default = PolicyRules::compile(&file.default).wrap_err("in [default]")?;
+        let users = file
+            .users
+            .iter()
+            .map(|(name, spec)| {
+                let rules = PolicyRules::compile(&file.default.overlay(spec))
+                    .wrap_err_with(|| format!("in [users.{name}]"))?;
+                Ok((name.clone(), rules))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { default, users })
+    }
+
+    pub fn rules_for(&self, user: Option<&str>) -> &PolicyRules {
+        user.and_then(|name| self.users.get(name))
+            .unwrap_or(&self.default)
+    }
+}
+
+fn limit_exceeded(limit: PolicyLimit, actual: u64, max: u64) -> PolicyViolation {
+    let what = match limit {
+        PolicyLimit::Size => "size",
+        PolicyLimit::ImageWidth => "image width",
+        PolicyLimit::ImageHeight => "image height",
+    };
+    PolicyViolation {
+        code: ErrorCode::PolicyLimitExceeded { limit, actual, max },
+        message: format!("{what} {actual} exceeds server policy limit {max}"),
+    }
+}
+
+/// Exact types first, then wildcards, so `image/png` wins over `image/*`.
+fn sorted_by_specificity<V>(map: HashMap<String, V>) -> Vec<(String, V)> {
+    let mut entries: Vec<_> = map.into_iter().collect();
+    entries.sort_by_key(|(pattern, _)| (pattern.ends_with("/*"), pattern.clone()));
+    entries
+}
+
+fn lookup<V: Copy>(entries: &[(String, V)], content_type: &str) -> Option<V> {
+    entries
+        .iter()
+        .find(|(pattern, _)| content_type_matches(pattern, content_type))
+        .map(|(_, value)| *value)
+}
+
+fn content_type_matches(pattern: &str, content_type: &str) -> bool {
+    let essence = |value: &str| {
+        value
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+    };
+    let pattern = essence(pattern);
+    let content_type = essence(content_type);
+    match pattern.strip_suffix("/*") {
+        Some(major) => content_type
+            .split_once('/')
+            .is_some_and(|(ty, _)| ty == major),
+        None => pattern == content_type,
+    }
+}
+
+/// Reads only the image header, so oversized images are rejected before anything
+/// decodes them.
+fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    image::ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::CONTENT_TYPE_PNG;
+
+    fn value(content_type: &str, data: Vec<u8>) -> ClipboardValue {
+        ClipboardValue {
+            content_type: content_type.to_string(),
+            data,
+            created_at: 0,
+            origin: None,
+        }
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        image::RgbaImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut out), image::ImageFormat::Png)
+            .unwrap();
+        out
+    }
+
+    const POLICY: &str = r#"
+        [default]
+        allowed_content_types = ["text/plain", "image/*"]
+        max_image_width = 64
+        max_image_height = 64
+        deny = ["(?i)password\\s*="]
+
+        [default.max_size]
+        "text/plain" = 16
+        "image/*" = 100000
+
+        [default.ttl_secs]
+        "text/plain" = 300
+
+        [users.alice]
+        max_image_width = 256
+    "#;
+
+    #[test]
+    fn allowed_types_and_sizes() {
+        let policy = Policy::parse(POLICY).unwrap();
+        let rules = policy.rules_for(None);
+
+        let ttl = rules
+            .check(&value(CONTENT_TYPE_TEXT, b"hello".to_vec()))
+            .unwrap();
+        assert_eq!(ttl, Some(Duration::from_secs(300)));
+
+        let err = rules
+            .check(&value(CONTENT_TYPE_TEXT, vec![b'a'; 17]))
+            .unwrap_err();
+        assert!(matches!(
+            err.code,
+            ErrorCode::PolicyLimitExceeded {
+                limit: PolicyLimit::Size,
+                actual: 17,
+                max: 16
+            }
+        ));
+
+        let err = rules.check(&value("application/pdf", vec![1])).unwrap_err();
+        assert!(matches!(err.code, ErrorCode::ContentTypeNotAllowed { .. }));
+    }
+
+    #[test]
+    fn image_dimensions_use_user_overrides() {
+        let policy = Policy::parse(POLICY).unwrap();
+        let wide = value(CONTENT_TYPE_PNG, png(128, 8));
+
+        let err = policy.rules_for(Some("bob")).check(&wide).unwrap_err();
+        assert!(matches!(
+            err.code,
+            ErrorCode::PolicyLimitExceeded {
+                limit: PolicyLimit::ImageWidth,
+                actual: 128,
+                max: 64
+            }
+        ));
+        assert_eq!(policy.rules_for(Some("alice")).check(&wide).unwrap(), None);
+
+        let garbage = value(CONTENT_TYPE_PNG, vec![0; 16]);
+        let err = policy.rules_for(None).check(&garbage).unwrap_err();
+        assert!(matches!(err.code, ErrorCode::InvalidRequest));
+    }
+
+    #[test]
+    fn deny_patterns_reject_text() {
+        let policy = Policy::parse(POLICY).unwrap();
+        let err = policy
+            .rules_for(None)
+            .check(&value(CONTENT_TYPE_TEXT, b"Password = x".to_vec()))
+            .unwrap_err();
+        assert!(matches!(err.code, ErrorCode::ContentDenied { .. }));
+    }
+
+    #[test]
+    fn empty_policy_allows_everything() {
+        let rules = PolicyRules::default();
+        let big = value(CONTENT_TYPE_PNG, png(4000, 1));
+        assert_eq!(rules.check(&big).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected() {
+        assert!(Policy::parse("[default]\nmax_width = 1").is_err());
+        assert!(Policy::parse("[users.bob]\ndeny = [\"(\"]").is_err());
+    }
+}
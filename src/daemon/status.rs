@@ -0,0 +1,100 @@
+use super::metrics::{Histogram, LATENCY_BUCKETS, SIZE_BUCKETS};
+use crate::protocol::{RequestKind, Response, ResponseKind, StatusCounter};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Counters reported by `RequestKind::Status` and the metrics endpoint.
+#[derive(Debug, Default)]
+pub struct DaemonStats {
+    counters: Mutex<Counters>,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Counters {
+    pub requests: BTreeMap<&'static str, u64>,
+    pub errors: BTreeMap<&'static str, u64>,
+    pub peer_rejections: u64,
+    pub framing_errors: BTreeMap<&'static str, u64>,
+    /// Request payload sizes by kind.
+    pub request_bytes: BTreeMap<&'static str, Histogram>,
+    pub response_bytes: Option<Histogram>,
+    /// Time spent in `handle_request`, by kind.
+    pub durations: BTreeMap<&'static str, Histogram>,
+}
+
+impl DaemonStats {
+    pub fn count_request(&self, kind: &RequestKind, payload_len: usize) {
+        if let Ok(mut counters) = self.counters.lock() {
+            *counters.requests.entry(kind.name()).or_default() += 1;
+            counters
+                .request_bytes
+                .entry(kind.name())
+                .or_insert_with(|| Histogram::new(SIZE_BUCKETS))
+                .observe(payload_len as f64);
+        }
+    }
+
+    pub fn observe_duration(&self, kind: &'static str, elapsed: Duration) {
+        if let Ok(mut counters) = self.counters.lock() {
+            counters
+                .durations
+                .entry(kind)
+                .or_insert_with(|| Histogram::new(LATENCY_BUCKETS))
+                .observe(elapsed.as_secs_f64());
+        }
+    }
+
+    pub fn count_response(&self, response: &Response, payload_len: usize) {
+        let Ok(mut counters) = self.counters.lock() else {
+            return;
+        };
+        counters
+            .response_bytes
+            .get_or_insert_with(|| Histogram::new(SIZE_BUCKETS))
+            .observe(payload_len as f64);
+        if let ResponseKind::Error { code, .. } = &response.kind {
+            *counters.errors.entry(code.name()).or_default() += 1;
+        }
+    }
+
+    pub fn count_peer_rejection(&self) {
+        if let Ok(mut counters) = self.counters.lock() {
+            counters.peer_rejections += 1;
+        }
+    }
+
+    pub fn count_framing_error(&self, reason: &'static str) {
+        if let Ok(mut counters) = self.counters.lock() {
+            *counters.framing_errors.entry(reason).or_default() += 1;
+        }
+    }
+
+    /// Request and error counters, sorted by name.
+    pub fn snapshot(&self) -> (Vec<StatusCounter>, Vec<StatusCounter>) {
+        let to_vec = |map: &BTreeMap<&'static str, u64>| {
+            map.iter()
+                .map(|(name, count)| StatusCounter {
+                    name: name.to_string(),
+                    count: *count,
+                })
+                .collect()
+        };
+        match self.counters.lock() {
+            Ok(counters) => (to_vec(&counters.requests), to_vec(&counters.errors)),
+            Err(_) => (Vec::new(), Vec::new()),
+        }
+    }
+
+    pub(super) fn counters(&self) -> Option<MutexGuard<'_, Counters>> {
+        self.counters.lock().ok()
+    }
+}
+
+/// Resident set size from `/proc/self/statm`.
+pub fn rss_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    (page_size > 0).then(|| pages * page_size as u64)
+}
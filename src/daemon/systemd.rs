@@ -0,0 +1,136 @@
+use eyre::{Result, eyre};
+use std::ffi::OsStr;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::{SocketAddr, UnixDatagram, UnixListener};
+use tokio::time::Duration;
+use tracing::debug;
+
+/// First file descriptor passed by socket activation (`SD_LISTEN_FDS_START`).
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Sends a `sd_notify` state string (e.g. `READY=1`) to the service manager. Does
+/// nothing when the daemon was not started by systemd with `Type=notify`.
+pub fn notify(state: &str) {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(err) = notify_to(&socket, state) {
+        debug!(error = %err, "sd_notify failed");
+    }
+}
+
+fn notify_to(socket: &OsStr, state: &str) -> std::io::Result<()> {
+    let bytes = socket.as_bytes();
+    let addr = match bytes.strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(socket)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+/// The listening socket passed by systemd socket activation, if `LISTEN_FDS` is set
+/// for this process. Only the first descriptor is used.
+pub fn listen_fd() -> Result<Option<UnixListener>> {
+    let count = parse_listen_fds(
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::process::id(),
+    );
+    if count == 0 {
+        return Ok(None);
+    }
+    if count > 1 {
+        debug!(count, "ignoring extra socket activation descriptors");
+    }
+    let fd = LISTEN_FDS_START;
+    let mut domain: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_DOMAIN,
+            &mut domain as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 || domain != libc::AF_UNIX {
+        return Err(eyre!(
+            "socket activation passed fd {fd}, which is not a UNIX socket (use ListenStream=<path>)"
+        ));
+    }
+    unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    let listener = unsafe { UnixListener::from_raw_fd(fd) };
+    listener.set_nonblocking(true)?;
+    Ok(Some(listener))
+}
+
+fn parse_listen_fds(fds: Option<&str>, pid: Option<&str>, own_pid: u32) -> usize {
+    if pid.and_then(|pid| pid.parse::<u32>().ok()) != Some(own_pid) {
+        return 0;
+    }
+    fds.and_then(|fds| fds.parse().ok()).unwrap_or(0)
+}
+
+/// How often to send `WATCHDOG=1`: half of `WATCHDOG_USEC`, when the watchdog is
+/// enabled for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid
+        && pid.parse::<u32>().ok() != Some(own_pid)
+    {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_sends_state_to_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+
+        notify_to(path.as_os_str(), "READY=1").unwrap();
+        let mut buf = [0u8; 64];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+    }
+
+    #[test]
+    fn listen_fds_must_name_this_process() {
+        assert_eq!(parse_listen_fds(Some("1"), Some("7"), 7), 1);
+        assert_eq!(parse_listen_fds(Some("1"), Some("8"), 7), 0);
+        assert_eq!(parse_listen_fds(Some("1"), None, 7), 0);
+        assert_eq!(parse_listen_fds(None, Some("7"), 7), 0);
+    }
+
+    #[test]
+    fn watchdog_is_half_the_timeout_for_this_pid() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 7),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("7"), 7),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(parse_watchdog(Some("30000000"), Some("8"), 7), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 7), None);
+        assert_eq!(parse_watchdog(None, None, 7), None);
+    }
+}
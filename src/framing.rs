@@ -158,6 +158,7 @@ mod tests {
     async fn round_trip_frame() {
         let request = Request {
             request_id: 1,
+            ssh_connection: None,
             kind: RequestKind::Get,
         };
         let payload = encode_message(&request).unwrap();
@@ -221,6 +222,7 @@ mod tests {
     async fn resync_skips_garbage_prefix() {
         let request = Request {
             request_id: 9,
+            ssh_connection: None,
             kind: RequestKind::Get,
         };
         let payload = encode_message(&request).unwrap();
@@ -241,6 +243,7 @@ mod tests {
     async fn resync_fails_when_strict() {
         let request = Request {
             request_id: 9,
+            ssh_connection: None,
             kind: RequestKind::Get,
         };
         let payload = encode_message(&request).unwrap();
@@ -260,11 +263,13 @@ mod tests {
     fn encode_allows_payload_larger_than_default_preallocation_limit() {
         let request = Request {
             request_id: 77,
+            ssh_connection: None,
             kind: RequestKind::Set {
                 value: ClipboardValue {
                     content_type: "text/plain; charset=utf-8".to_string(),
                     data: vec![b'a'; 5 * 1024 * 1024],
                     created_at: 1234,
+                    origin: None,
                 },
             },
         };
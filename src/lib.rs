@@ -7,6 +7,8 @@ pub mod protocol;
 pub mod daemon;
 #[cfg(target_os = "linux")]
 pub mod proxy;
+#[cfg(target_os = "linux")]
+pub mod server_config;
 
 pub mod client;
 
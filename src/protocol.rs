@@ -2,30 +2,70 @@ use serde::{Deserialize, Serialize};
 use wincode::{SchemaRead, SchemaWrite};
 
 pub const MAGIC: [u8; 4] = *b"SCB1";
-pub const VERSION: u16 = 2;
+pub const VERSION: u16 = 3;
 pub const CONTENT_TYPE_TEXT: &str = "text/plain; charset=utf-8";
 pub const CONTENT_TYPE_PNG: &str = "image/png";
+pub const CONTENT_TYPE_JPEG: &str = "image/jpeg";
+pub const CONTENT_TYPE_WEBP: &str = "image/webp";
+/// Image content types the daemon stores and clients can decode.
+pub const IMAGE_CONTENT_TYPES: &[&str] = &[CONTENT_TYPE_PNG, CONTENT_TYPE_JPEG, CONTENT_TYPE_WEBP];
 pub const DEFAULT_MAX_SIZE: usize = 10 * 1024 * 1024;
 pub const RESPONSE_OVERHEAD: usize = 1024;
 
+pub fn is_image_content_type(content_type: &str) -> bool {
+    IMAGE_CONTENT_TYPES.contains(&content_type)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, SchemaWrite, SchemaRead)]
 pub struct ClipboardValue {
     pub content_type: String,
     pub data: Vec<u8>,
     pub created_at: i64,
+    /// Machine the value was pushed from, as reported by the client.
+    pub origin: Option<Origin>,
+}
+
+/// Identifies the client that pushed a value, so values from several machines can be
+/// told apart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SchemaWrite, SchemaRead)]
+pub struct Origin {
+    pub hostname: String,
+    pub os: String,
+    pub username: String,
+    /// Optional name chosen by the user, e.g. `work-laptop`.
+    pub label: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, SchemaWrite, SchemaRead)]
 pub struct Request {
     pub request_id: u64,
     pub kind: RequestKind,
+    /// `SSH_CONNECTION` of the session, filled in by the proxy for the daemon's audit
+    /// log; clients leave it empty and the proxy overwrites whatever they send.
+    pub ssh_connection: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, SchemaWrite, SchemaRead)]
 pub enum RequestKind {
-    Set { value: ClipboardValue },
+    Set {
+        value: ClipboardValue,
+    },
     Get,
     PeekMeta,
+    /// Daemon health and counters; does not touch the stored value.
+    Status,
+}
+
+impl RequestKind {
+    /// Short name used in logs and status counters.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RequestKind::Set { .. } => "set",
+            RequestKind::Get => "get",
+            RequestKind::PeekMeta => "peek",
+            RequestKind::Status => "status",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, SchemaWrite, SchemaRead)]
@@ -44,12 +84,47 @@ pub enum ResponseKind {
         content_type: String,
         size: u64,
         created_at: i64,
+        /// User who stored the value; only set by a shared daemon.
+        author: Option<String>,
+        origin: Option<Origin>,
     },
     Empty,
     Error {
         code: ErrorCode,
         message: String,
     },
+    Status {
+        status: DaemonStatus,
+    },
+}
+
+/// Answer to `RequestKind::Status`.
+#[derive(Debug, Clone, Serialize, Deserialize, SchemaWrite, SchemaRead)]
+pub struct DaemonStatus {
+    /// Package version of the daemon binary.
+    pub version: String,
+    pub protocol_version: u16,
+    pub pid: u32,
+    pub uptime_secs: u64,
+    pub socket_path: String,
+    pub max_size: u64,
+    pub io_timeout_ms: u64,
+    /// `0` or `1`; the daemon keeps a single value.
+    pub stored_values: u64,
+    /// Bytes held by the stored value.
+    pub stored_bytes: u64,
+    /// Resident memory of the daemon process, if it could be read.
+    pub rss_bytes: Option<u64>,
+    /// Requests handled since startup, by request kind name.
+    pub requests: Vec<StatusCounter>,
+    /// Error responses sent since startup, by error code name.
+    pub errors: Vec<StatusCounter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SchemaWrite, SchemaRead)]
+pub struct StatusCounter {
+    pub name: String,
+    pub count: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, SchemaWrite, SchemaRead)]
@@ -61,6 +136,51 @@ pub enum ErrorCode {
     Internal,
     DaemonNotRunning,
     VersionMismatch,
+    /// The daemon policy does not accept this content type.
+    ContentTypeNotAllowed {
+        content_type: String,
+    },
+    /// A daemon policy limit was exceeded; `actual` and `max` are in the limit's unit.
+    PolicyLimitExceeded {
+        limit: PolicyLimit,
+        actual: u64,
+        max: u64,
+    },
+    /// Text matched one of the daemon policy's deny patterns.
+    ContentDenied {
+        rule: String,
+    },
+    /// The proxy was started without permission for this operation (`proxy --allow`).
+    Forbidden,
+}
+
+impl ErrorCode {
+    /// The snake_case name used in JSON output and status counters.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "invalid_request",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+            ErrorCode::InvalidUtf8 => "invalid_utf8",
+            ErrorCode::Internal => "internal",
+            ErrorCode::DaemonNotRunning => "daemon_not_running",
+            ErrorCode::VersionMismatch => "version_mismatch",
+            ErrorCode::ContentTypeNotAllowed { .. } => "content_type_not_allowed",
+            ErrorCode::PolicyLimitExceeded { .. } => "policy_limit_exceeded",
+            ErrorCode::ContentDenied { .. } => "content_denied",
+            ErrorCode::Forbidden => "forbidden",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SchemaWrite, SchemaRead)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyLimit {
+    /// Bytes.
+    Size,
+    /// Pixels.
+    ImageWidth,
+    /// Pixels.
+    ImageHeight,
 }
 
 #[cfg(test)]
@@ -69,22 +189,24 @@ mod tests {
     use crate::framing::codec_config;
     use wincode::config;
 
-    const REQUEST_V2_SET_FIXTURE: &[u8] = &[
+    const REQUEST_V3_SET_FIXTURE: &[u8] = &[
         42, 0, 25, 116, 101, 120, 116, 47, 112, 108, 97, 105, 110, 59, 32, 99, 104, 97, 114, 115,
-        101, 116, 61, 117, 116, 102, 45, 56, 5, 104, 101, 108, 108, 111, 246,
+        101, 116, 61, 117, 116, 102, 45, 56, 5, 104, 101, 108, 108, 111, 246, 0, 0,
     ];
 
-    const RESPONSE_V2_ERROR_FIXTURE: &[u8] = &[7, 4, 1, 7, 116, 111, 111, 32, 98, 105, 103];
+    const RESPONSE_V3_ERROR_FIXTURE: &[u8] = &[7, 4, 1, 7, 116, 111, 111, 32, 98, 105, 103];
 
     #[test]
     fn request_round_trip_codec() {
         let request = Request {
             request_id: 42,
+            ssh_connection: None,
             kind: RequestKind::Set {
                 value: ClipboardValue {
                     content_type: CONTENT_TYPE_TEXT.to_string(),
                     data: b"hello".to_vec(),
                     created_at: 123,
+                    origin: None,
                 },
             },
         };
@@ -109,6 +231,13 @@ mod tests {
                 content_type: CONTENT_TYPE_PNG.to_string(),
                 size: 999,
                 created_at: 456,
+                author: Some("alice".to_string()),
+                origin: Some(Origin {
+                    hostname: "laptop".to_string(),
+                    os: "macos".to_string(),
+                    username: "alice".to_string(),
+                    label: Some("work".to_string()),
+                }),
             },
         };
         let payload = config::serialize(&response, codec_config()).unwrap();
@@ -119,10 +248,16 @@ mod tests {
                 content_type,
                 size,
                 created_at,
+                author,
+                origin,
             } => {
                 assert_eq!(content_type, CONTENT_TYPE_PNG);
                 assert_eq!(size, 999);
                 assert_eq!(created_at, 456);
+                assert_eq!(author.as_deref(), Some("alice"));
+                let origin = origin.unwrap();
+                assert_eq!(origin.hostname, "laptop");
+                assert_eq!(origin.label.as_deref(), Some("work"));
             }
             other => panic!("unexpected response kind: {other:?}"),
         }
@@ -132,18 +267,20 @@ mod tests {
     fn request_wire_fixture_is_stable() {
         let request = Request {
             request_id: 42,
+            ssh_connection: None,
             kind: RequestKind::Set {
                 value: ClipboardValue {
                     content_type: CONTENT_TYPE_TEXT.to_string(),
                     data: b"hello".to_vec(),
                     created_at: 123,
+                    origin: None,
                 },
             },
         };
         let payload = config::serialize(&request, codec_config()).unwrap();
-        assert_eq!(payload, REQUEST_V2_SET_FIXTURE);
+        assert_eq!(payload, REQUEST_V3_SET_FIXTURE);
         let decoded =
-            config::deserialize::<Request, _>(REQUEST_V2_SET_FIXTURE, codec_config()).unwrap();
+            config::deserialize::<Request, _>(REQUEST_V3_SET_FIXTURE, codec_config()).unwrap();
         assert_eq!(decoded.request_id, 42);
     }
 
@@ -157,9 +294,9 @@ mod tests {
             },
         };
         let payload = config::serialize(&response, codec_config()).unwrap();
-        assert_eq!(payload, RESPONSE_V2_ERROR_FIXTURE);
+        assert_eq!(payload, RESPONSE_V3_ERROR_FIXTURE);
         let decoded =
-            config::deserialize::<Response, _>(RESPONSE_V2_ERROR_FIXTURE, codec_config()).unwrap();
+            config::deserialize::<Response, _>(RESPONSE_V3_ERROR_FIXTURE, codec_config()).unwrap();
         assert_eq!(decoded.request_id, 7);
     }
 
@@ -171,10 +308,103 @@ mod tests {
         assert_eq!(encoded, "\"version_mismatch\"");
     }
 
+    #[test]
+    fn error_code_names_match_json() {
+        let codes = [
+            ErrorCode::InvalidRequest,
+            ErrorCode::ContentDenied {
+                rule: "x".to_string(),
+            },
+            ErrorCode::Forbidden,
+        ];
+        for code in codes {
+            let json = serde_json::to_value(&code).unwrap();
+            let name = match &json {
+                serde_json::Value::String(name) => name.clone(),
+                serde_json::Value::Object(map) => map.keys().next().unwrap().clone(),
+                other => panic!("unexpected json: {other}"),
+            };
+            assert_eq!(code.name(), name);
+        }
+    }
+
+    #[test]
+    fn status_round_trips() {
+        let response = Response {
+            request_id: 5,
+            kind: ResponseKind::Status {
+                status: DaemonStatus {
+                    version: "0.3.0".to_string(),
+                    protocol_version: VERSION,
+                    pid: 42,
+                    uptime_secs: 60,
+                    socket_path: "/run/user/1000/ssh_clipboard/daemon.sock".to_string(),
+                    max_size: DEFAULT_MAX_SIZE as u64,
+                    io_timeout_ms: 7000,
+                    stored_values: 1,
+                    stored_bytes: 5,
+                    rss_bytes: None,
+                    requests: vec![StatusCounter {
+                        name: "get".to_string(),
+                        count: 3,
+                    }],
+                    errors: Vec::new(),
+                },
+            },
+        };
+        let payload = config::serialize(&response, codec_config()).unwrap();
+        match config::deserialize::<Response, _>(&payload, codec_config())
+            .unwrap()
+            .kind
+        {
+            ResponseKind::Status { status } => {
+                assert_eq!(status.pid, 42);
+                assert_eq!(status.requests[0].count, 3);
+            }
+            other => panic!("unexpected response kind: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn policy_error_codes_round_trip() {
+        let response = Response {
+            request_id: 3,
+            kind: ResponseKind::Error {
+                code: ErrorCode::PolicyLimitExceeded {
+                    limit: PolicyLimit::ImageWidth,
+                    actual: 9000,
+                    max: 4096,
+                },
+                message: "image too wide".to_string(),
+            },
+        };
+        let payload = config::serialize(&response, codec_config()).unwrap();
+        let decoded = config::deserialize::<Response, _>(&payload, codec_config()).unwrap();
+        match decoded.kind {
+            ResponseKind::Error {
+                code:
+                    ErrorCode::PolicyLimitExceeded {
+                        limit: PolicyLimit::ImageWidth,
+                        actual: 9000,
+                        max: 4096,
+                    },
+                ..
+            } => {}
+            other => panic!("unexpected response kind: {other:?}"),
+        }
+
+        let encoded = serde_json::to_string(&ErrorCode::ContentDenied {
+            rule: "secret".to_string(),
+        })
+        .unwrap();
+        assert_eq!(encoded, r#"{"content_denied":{"rule":"secret"}}"#);
+    }
+
     #[test]
     fn codec_rejects_truncated_payload() {
         let request = Request {
             request_id: 1,
+            ssh_connection: None,
             kind: RequestKind::Get,
         };
         let mut payload = config::serialize(&request, codec_config()).unwrap();
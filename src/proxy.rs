@@ -1,5 +1,9 @@
+use crate::cli::exit::{
+    EXIT_DAEMON_NOT_RUNNING, EXIT_OK, PROXY_EXIT_FORBIDDEN, PROXY_EXIT_INTERNAL, proxy_exit_code,
+};
 use crate::framing::{decode_message, encode_message, read_frame_payload, write_frame_payload};
-use crate::protocol::{ErrorCode, RESPONSE_OVERHEAD, Request, Response, ResponseKind};
+use crate::protocol::{ErrorCode, RESPONSE_OVERHEAD, Request, RequestKind, Response, ResponseKind};
+use clap::ValueEnum;
 use eyre::{Result, WrapErr};
 #[cfg(unix)]
 use std::os::unix::process::CommandExt;
@@ -9,17 +13,32 @@ use tokio::io::{stdin, stdout};
 use tokio::net::UnixStream;
 use tokio::time::{Duration, timeout};
 
-pub const EXIT_OK: i32 = 0;
-pub const EXIT_INVALID_REQUEST: i32 = 2;
-pub const EXIT_PAYLOAD_TOO_LARGE: i32 = 3;
-pub const EXIT_DAEMON_NOT_RUNNING: i32 = 4;
-pub const EXIT_INTERNAL: i32 = 5;
+/// Request kinds a proxy may forward, for keys restricted with `proxy --allow`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProxyOperation {
+    Get,
+    Set,
+    Peek,
+    Status,
+}
+
+impl ProxyOperation {
+    fn of(kind: &RequestKind) -> Self {
+        match kind {
+            RequestKind::Get => ProxyOperation::Get,
+            RequestKind::Set { .. } => ProxyOperation::Set,
+            RequestKind::PeekMeta => ProxyOperation::Peek,
+            RequestKind::Status => ProxyOperation::Status,
+        }
+    }
+}
 
 pub async fn run_proxy(
     socket_path: PathBuf,
     max_size: usize,
     io_timeout_ms: u64,
     autostart_daemon: bool,
+    allow: &[ProxyOperation],
 ) -> Result<i32> {
     let mut input = stdin();
     let mut output = stdout();
@@ -30,6 +49,20 @@ pub async fn run_proxy(
     )
     .await??;
 
+    if let Err(response) = check_allowed(&request_payload, allow) {
+        if let ResponseKind::Error { message, .. } = &response.kind {
+            eprintln!("{message}");
+        }
+        let payload = encode_message(response.as_ref())?;
+        write_frame_payload(&mut output, &payload).await?;
+        return Ok(PROXY_EXIT_FORBIDDEN);
+    }
+    let request_payload = stamp_ssh_connection(
+        request_payload,
+        std::env::var("SSH_CONNECTION").ok(),
+        max_size,
+    );
+
     let mut stream =
         match connect_daemon(&socket_path, io_timeout_ms, autostart_daemon, max_size).await {
             Ok(stream) => stream,
@@ -74,12 +107,12 @@ pub async fn run_proxy(
             ..
         }) => {
             eprintln!("{message}");
-            map_error_code(code)
+            proxy_exit_code(&code)
         }
         Ok(_) => EXIT_OK,
         Err(err) => {
             eprintln!("failed to decode response: {err}");
-            EXIT_INTERNAL
+            PROXY_EXIT_INTERNAL
         }
     };
 
@@ -87,14 +120,57 @@ pub async fn run_proxy(
     Ok(exit_code)
 }
 
-fn map_error_code(code: ErrorCode) -> i32 {
-    match code {
-        ErrorCode::InvalidRequest => EXIT_INVALID_REQUEST,
-        ErrorCode::PayloadTooLarge => EXIT_PAYLOAD_TOO_LARGE,
-        ErrorCode::InvalidUtf8 => EXIT_INVALID_REQUEST,
-        ErrorCode::DaemonNotRunning => EXIT_DAEMON_NOT_RUNNING,
-        ErrorCode::VersionMismatch => EXIT_INVALID_REQUEST,
-        ErrorCode::Internal => EXIT_INTERNAL,
+/// With an allow list, the request is decoded before anything is forwarded; a request
+/// that cannot be decoded is refused too, since its kind is unknown. An empty list
+/// allows everything and forwards the frame untouched.
+fn check_allowed(payload: &[u8], allow: &[ProxyOperation]) -> Result<(), Box<Response>> {
+    if allow.is_empty() {
+        return Ok(());
+    }
+    let (request_id, message) = match decode_message::<Request>(payload) {
+        Ok(request) => {
+            let operation = ProxyOperation::of(&request.kind);
+            if allow.contains(&operation) {
+                return Ok(());
+            }
+            (
+                request.request_id,
+                format!(
+                    "operation `{}` is not allowed for this key",
+                    operation.to_possible_value().unwrap().get_name()
+                ),
+            )
+        }
+        Err(err) => (0, format!("decode error: {err}")),
+    };
+    Err(Box::new(Response {
+        request_id,
+        kind: ResponseKind::Error {
+            code: ErrorCode::Forbidden,
+            message,
+        },
+    }))
+}
+
+/// Records the session's `SSH_CONNECTION` in the request for the daemon's audit log,
+/// replacing whatever the client sent. Undecodable requests are forwarded untouched
+/// for the daemon to reject, and the field is cleared if it would push the request
+/// over the size limit.
+fn stamp_ssh_connection(
+    payload: Vec<u8>,
+    ssh_connection: Option<String>,
+    max_size: usize,
+) -> Vec<u8> {
+    let Ok(mut request) = decode_message::<Request>(&payload) else {
+        return payload;
+    };
+    request.ssh_connection = ssh_connection;
+    match encode_message(&request) {
+        Ok(stamped) if stamped.len() <= max_size => stamped,
+        _ => {
+            request.ssh_connection = None;
+            encode_message(&request).unwrap_or(payload)
+        }
     }
 }
 
@@ -252,4 +328,73 @@ mod tests {
 
         assert_eq!(received.len(), max_size + 1);
     }
+
+    fn request_payload(kind: RequestKind) -> Vec<u8> {
+        encode_message(&Request {
+            request_id: 9,
+            ssh_connection: None,
+            kind,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn allow_list_restricts_request_kinds() {
+        let get = request_payload(RequestKind::Get);
+        let peek = request_payload(RequestKind::PeekMeta);
+        let read_only = [ProxyOperation::Get, ProxyOperation::Peek];
+
+        assert!(check_allowed(&get, &[]).is_ok());
+        assert!(check_allowed(&get, &read_only).is_ok());
+        assert!(check_allowed(&peek, &read_only).is_ok());
+
+        let response = check_allowed(&get, &[ProxyOperation::Set]).unwrap_err();
+        assert_eq!(response.request_id, 9);
+        match response.kind {
+            ResponseKind::Error {
+                code: ErrorCode::Forbidden,
+                message,
+            } => assert!(message.contains("`get`")),
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn ssh_connection_is_overwritten_by_the_proxy() {
+        let mut spoofed: Request = decode_message(&request_payload(RequestKind::Get)).unwrap();
+        spoofed.ssh_connection = Some("1.2.3.4 1 5.6.7.8 22".to_string());
+        let payload = encode_message(&spoofed).unwrap();
+
+        let stamped = stamp_ssh_connection(
+            payload.clone(),
+            Some("10.0.0.2 51234 10.0.0.1 22".to_string()),
+            1024,
+        );
+        let request: Request = decode_message(&stamped).unwrap();
+        assert_eq!(request.request_id, 9);
+        assert_eq!(
+            request.ssh_connection.as_deref(),
+            Some("10.0.0.2 51234 10.0.0.1 22")
+        );
+
+        let cleared: Request = decode_message(&stamp_ssh_connection(payload, None, 1024)).unwrap();
+        assert_eq!(cleared.ssh_connection, None);
+        assert_eq!(
+            stamp_ssh_connection(b"garbage".to_vec(), None, 1024),
+            b"garbage"
+        );
+    }
+
+    #[test]
+    fn allow_list_refuses_undecodable_requests() {
+        assert!(check_allowed(b"garbage", &[]).is_ok());
+        let response = check_allowed(b"garbage", &[ProxyOperation::Get]).unwrap_err();
+        assert!(matches!(
+            response.kind,
+            ResponseKind::Error {
+                code: ErrorCode::Forbidden,
+                ..
+            }
+        ));
+    }
 }
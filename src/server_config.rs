@@ -0,0 +1,303 @@
+use crate::protocol::DEFAULT_MAX_SIZE;
+use eyre::{Result, WrapErr, eyre};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Defaults for every daemon and proxy on the host.
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/ssh_clipboard/config";
+pub const DEFAULT_IO_TIMEOUT_MS: u64 = 7000;
+
+/// Settings shared by `daemon` and `proxy`, read from the system file and then the
+/// user's file; a key set in a later layer wins, and command-line flags win over
+/// both. Every key is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socket_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_timeout_ms: Option<u64>,
+    /// Content policy file for the daemon.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PathBuf>,
+    /// Let the proxy start a daemon when none is running.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autostart_daemon: Option<bool>,
+}
+
+/// Which layer a setting came from, for `server-config show`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    System,
+    User,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Default => "default",
+            Self::System => "system",
+            Self::User => "user",
+        })
+    }
+}
+
+pub const KEYS: &[&str] = &[
+    "socket_path",
+    "max_size",
+    "io_timeout_ms",
+    "policy",
+    "autostart_daemon",
+];
+
+/// `$XDG_CONFIG_HOME/ssh_clipboard/server.toml`, falling back to `~/.config`.
+pub fn user_config_path() -> Result<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".config"))
+            .ok_or_else(|| eyre!("HOME is not set"))?,
+    };
+    Ok(config_dir.join("ssh_clipboard").join("server.toml"))
+}
+
+impl ServerConfig {
+    /// The system file overlaid with the user's; missing files are skipped.
+    pub fn load() -> Result<Self> {
+        Self::load_layers(Path::new(SYSTEM_CONFIG_PATH))
+    }
+
+    fn load_layers(system_path: &Path) -> Result<Self> {
+        let system = Self::load_from(system_path)?;
+        Ok(system.overlay(&Self::load_user()?))
+    }
+
+    /// Services started by an init system may run without `HOME` or with a home they
+    /// cannot read; their user layer is then empty, with a warning. A file that can be
+    /// read but is invalid is still an error.
+    fn load_user() -> Result<Self> {
+        let path = match user_config_path() {
+            Ok(path) => path,
+            Err(err) => {
+                tracing::warn!("skipping the user server config: {err}");
+                return Ok(Self::default());
+            }
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents)
+                .wrap_err_with(|| format!("invalid config {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => {
+                tracing::warn!("skipping the user server config {}: {err}", path.display());
+                Ok(Self::default())
+            }
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).wrap_err_with(|| format!("failed to read {}", path.display()));
+            }
+        };
+        Self::parse(&contents).wrap_err_with(|| format!("invalid config {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents).map_err(|err| eyre!("{err}"))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (key, path) in [("socket_path", &self.socket_path), ("policy", &self.policy)] {
+            if let Some(path) = path
+                && !path.is_absolute()
+            {
+                return Err(eyre!("{key} must be an absolute path"));
+            }
+        }
+        if self.max_size == Some(0) {
+            return Err(eyre!("max_size must be greater than zero"));
+        }
+        Ok(())
+    }
+
+    /// `self` with every key that `over` sets replaced.
+    pub fn overlay(&self, over: &ServerConfig) -> ServerConfig {
+        ServerConfig {
+            socket_path: over
+                .socket_path
+                .clone()
+                .or_else(|| self.socket_path.clone()),
+            max_size: over.max_size.or(self.max_size),
+            io_timeout_ms: over.io_timeout_ms.or(self.io_timeout_ms),
+            policy: over.policy.clone().or_else(|| self.policy.clone()),
+            autostart_daemon: over.autostart_daemon.or(self.autostart_daemon),
+        }
+    }
+
+    pub fn effective_max_size(&self) -> usize {
+        self.max_size.unwrap_or(DEFAULT_MAX_SIZE)
+    }
+
+    pub fn effective_io_timeout_ms(&self) -> u64 {
+        self.io_timeout_ms.unwrap_or(DEFAULT_IO_TIMEOUT_MS)
+    }
+
+    pub fn is_set(&self, key: &str) -> bool {
+        match key {
+            "socket_path" => self.socket_path.is_some(),
+            "max_size" => self.max_size.is_some(),
+            "io_timeout_ms" => self.io_timeout_ms.is_some(),
+            "policy" => self.policy.is_some(),
+            "autostart_daemon" => self.autostart_daemon.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Sets `key` from its string form, or removes it when `value` is `None`.
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<()> {
+        fn parse<T: std::str::FromStr>(key: &str, value: Option<&str>) -> Result<Option<T>>
+        where
+            T::Err: fmt::Display,
+        {
+            value
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|err| eyre!("invalid value for {key}: {err}"))
+                })
+                .transpose()
+        }
+        let mut updated = self.clone();
+        match key {
+            "socket_path" => updated.socket_path = value.map(PathBuf::from),
+            "max_size" => updated.max_size = parse(key, value)?,
+            "io_timeout_ms" => updated.io_timeout_ms = parse(key, value)?,
+            "policy" => updated.policy = value.map(PathBuf::from),
+            "autostart_daemon" => updated.autostart_daemon = parse(key, value)?,
+            _ => {
+                return Err(eyre!(
+                    "unknown key `{key}` (expected one of: {})",
+                    KEYS.join(", ")
+                ));
+            }
+        }
+        updated.validate()?;
+        *self = updated;
+        Ok(())
+    }
+
+    /// File contents for `server-config set` and `install-daemon --system`.
+    /// Comments in an existing file are not kept.
+    pub fn render(&self) -> Result<String> {
+        let body = toml::to_string(self).map_err(|err| eyre!("{err}"))?;
+        Ok(format!(
+            "# ssh_clipboard daemon and proxy settings (`ssh_clipboard server-config show`).\n\
+             # Command-line flags take precedence.\n\
+             {body}"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_home_leaves_the_user_layer_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("config");
+        std::fs::write(&system, "max_size = 1024\n").unwrap();
+        let saved: Vec<_> = ["HOME", "XDG_CONFIG_HOME"]
+            .into_iter()
+            .map(|name| (name, std::env::var_os(name)))
+            .collect();
+        // SAFETY: no other test depends on these variables.
+        unsafe {
+            std::env::remove_var("HOME");
+            std::env::remove_var("XDG_CONFIG_HOME");
+        }
+        let loaded = ServerConfig::load_layers(&system);
+        for (name, value) in saved {
+            if let Some(value) = value {
+                // SAFETY: as above.
+                unsafe { std::env::set_var(name, value) };
+            }
+        }
+        assert_eq!(loaded.unwrap().max_size, Some(1024));
+    }
+
+    #[test]
+    fn later_layers_win_per_key() {
+        let system = ServerConfig::parse(
+            "max_size = 1024\nio_timeout_ms = 3000\npolicy = \"/etc/ssh_clipboard/policy.toml\"\n",
+        )
+        .unwrap();
+        let user = ServerConfig::parse("max_size = 2048\nautostart_daemon = true\n").unwrap();
+        let flags = ServerConfig {
+            io_timeout_ms: Some(9000),
+            ..ServerConfig::default()
+        };
+
+        let merged = system.overlay(&user).overlay(&flags);
+        assert_eq!(merged.max_size, Some(2048));
+        assert_eq!(merged.io_timeout_ms, Some(9000));
+        assert_eq!(
+            merged.policy.as_deref(),
+            Some(Path::new("/etc/ssh_clipboard/policy.toml"))
+        );
+        assert_eq!(merged.autostart_daemon, Some(true));
+        assert_eq!(merged.socket_path, None);
+        assert_eq!(
+            ServerConfig::default().effective_max_size(),
+            DEFAULT_MAX_SIZE
+        );
+        assert_eq!(
+            ServerConfig::default().effective_io_timeout_ms(),
+            DEFAULT_IO_TIMEOUT_MS
+        );
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        assert!(ServerConfig::parse("policy = \"policy.toml\"").is_err());
+        assert!(ServerConfig::parse("max_size = 0").is_err());
+        assert!(ServerConfig::parse("max_sise = 10").is_err());
+        assert!(ServerConfig::parse("io_timeout_ms = \"fast\"").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            ServerConfig::load_from(&dir.path().join("server.toml")).unwrap(),
+            ServerConfig::default()
+        );
+    }
+
+    #[test]
+    fn set_round_trips_through_toml() {
+        let mut config = ServerConfig::default();
+        config.set("max_size", Some("4096")).unwrap();
+        config.set("autostart_daemon", Some("true")).unwrap();
+        config
+            .set("socket_path", Some("/srv/clip/daemon.sock"))
+            .unwrap();
+        assert!(config.set("io_timeout_ms", Some("soon")).is_err());
+        assert!(config.set("policy", Some("relative.toml")).is_err());
+        assert!(config.set("colour", Some("blue")).is_err());
+
+        let parsed = ServerConfig::parse(&config.render().unwrap()).unwrap();
+        assert_eq!(parsed, config);
+        assert_eq!(parsed.max_size, Some(4096));
+        assert_eq!(parsed.autostart_daemon, Some(true));
+
+        config.set("max_size", None).unwrap();
+        assert!(!config.render().unwrap().contains("max_size"));
+    }
+}